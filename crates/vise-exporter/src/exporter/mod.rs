@@ -21,22 +21,140 @@ use hyper_util::{
     client::legacy::Client,
     rt::{TokioExecutor, TokioIo},
 };
-use tokio::{io, net::TcpListener, sync::watch};
-use vise::{Format, MetricsCollection, Registry};
+use tokio::{
+    io,
+    net::TcpListener,
+    sync::{watch, Mutex},
+};
+use vise::{
+    descriptors::MetricGroupDescriptor, Format, MetricsCollection, MetricsFilter, Registry,
+};
 
 use crate::metrics::{Facade, EXPORTER_METRICS};
 
 #[cfg(test)]
 mod tests;
 
+/// Result of the last scrape shared among scrapes.
+struct CachedScrape {
+    started_at: Instant,
+    finished_at: Instant,
+    body: String,
+}
+
 #[derive(Clone)]
 struct MetricsExporterInner {
     registry: Arc<Registry>,
     format: Format,
+    expose_group_switches: bool,
+    scrape_cache_ttl: Option<Duration>,
+    last_scrape: Arc<Mutex<Option<CachedScrape>>>,
 }
 
 impl MetricsExporterInner {
+    async fn handle(&self, method: &Method, uri: &Uri) -> Response<String> {
+        if self.expose_group_switches {
+            if let Some(path) = uri.path().strip_prefix("/groups") {
+                return self.handle_group_switch(method, path);
+            }
+        }
+        match parse_filter(uri.query().unwrap_or_default()) {
+            Ok(filter) => self.render(&filter).await,
+            Err(message) => plain_response(StatusCode::BAD_REQUEST, message),
+        }
+    }
+
+    /// Handles the `/groups` admin endpoint; `path` is the remaining part of the request path.
+    fn handle_group_switch(&self, method: &Method, path: &str) -> Response<String> {
+        let groups = self.switchable_groups();
+        if path.is_empty() || path == "/" {
+            if method != Method::GET {
+                return plain_response(StatusCode::METHOD_NOT_ALLOWED, String::new());
+            }
+            let mut body = String::new();
+            for (key, group) in &groups {
+                let state = if group.switch().is_enabled() {
+                    "enabled"
+                } else {
+                    "disabled"
+                };
+                writeln!(body, "{key} {state}").unwrap();
+            }
+            return plain_response(StatusCode::OK, body);
+        }
+
+        let Some((group_path, action)) = path.trim_start_matches('/').rsplit_once('/') else {
+            return plain_response(StatusCode::NOT_FOUND, String::new());
+        };
+        let enabled = match action {
+            "enable" => true,
+            "disable" => false,
+            _ => return plain_response(StatusCode::NOT_FOUND, String::new()),
+        };
+        if method != Method::POST {
+            return plain_response(StatusCode::METHOD_NOT_ALLOWED, String::new());
+        }
+
+        let Some((_, group)) = groups.iter().find(|(key, _)| key == group_path) else {
+            return plain_response(
+                StatusCode::NOT_FOUND,
+                format!("Group `{group_path}` is not registered"),
+            );
+        };
+        group.switch().set_enabled(enabled);
+        tracing::info!(
+            enabled,
+            "Switched metrics group `{group_path}` via the admin endpoint"
+        );
+        plain_response(StatusCode::NO_CONTENT, String::new())
+    }
+
+    /// Returns registered groups, including nested ones, keyed by `{crate_name}/{module_path}::{name}`.
+    /// Since switches are shared by all instances of a group, each group is only listed once.
+    fn switchable_groups(&self) -> Vec<(String, &MetricGroupDescriptor)> {
+        let mut groups = vec![];
+        let mut keys = HashSet::new();
+        for group in self.registry.descriptors().groups() {
+            for group in std::iter::once(group).chain(group.nested_groups.iter().copied()) {
+                let key = format!("{}/{}::{}", group.crate_name, group.module_path, group.name);
+                if keys.insert(key.clone()) {
+                    groups.push((key, group));
+                }
+            }
+        }
+        groups
+    }
+
+    /// Returns the encoded metrics. Only one scrape is performed at a time; scrapes requested while another scrape
+    /// is in progress reuse its result. If the scrape cache is enabled, the result is also reused
+    /// for scrapes requested within the cache TTL.
     async fn render_body(&self) -> String {
+        let requested_at = Instant::now();
+        let mut last_scrape = self.last_scrape.lock().await;
+        if let Some(scrape) = &*last_scrape {
+            // If the last scrape has finished after this request was received, it was in progress
+            // while we were waiting for the lock.
+            let is_coalesced = scrape.finished_at >= requested_at;
+            let is_fresh = self
+                .scrape_cache_ttl
+                .is_some_and(|ttl| scrape.started_at.elapsed() < ttl);
+            if is_coalesced || is_fresh {
+                EXPORTER_METRICS.scrape_cache_hits[&Facade::Vise].inc();
+                return scrape.body.clone();
+            }
+        }
+
+        let started_at = Instant::now();
+        let body = self.scrape(MetricsFilter::default()).await;
+        *last_scrape = Some(CachedScrape {
+            started_at,
+            finished_at: Instant::now(),
+            body: body.clone(),
+        });
+        body
+    }
+
+    async fn scrape(&self, filter: MetricsFilter) -> String {
         let latency = EXPORTER_METRICS.scrape_latency[&Facade::Vise].start();
         let registry = Arc::clone(&self.registry);
         let format = self.format;
@@ -45,7 +163,9 @@ impl MetricsExporterInner {
         // blocking interface for collectors.
         let buffer = tokio::task::spawn_blocking(move || {
             let mut buffer = String::with_capacity(1_024);
-            registry.encode(&mut buffer, format).unwrap();
+            registry
+                .encode_filtered(&mut buffer, format, &filter)
+                .unwrap();
             // ^ `unwrap()` is safe; writing to a string never fails.
             buffer
         })
@@ -63,7 +183,8 @@ impl MetricsExporterInner {
         buffer
     }
 
-    async fn render(&self) -> Response<String> {
+    /// Renders metrics selected by the `filter`. Filtered scrapes bypass the scrape cache.
+    async fn render(&self, filter: &MetricsFilter) -> Response<String> {
         let content_type = if matches!(self.format, Format::Prometheus) {
             Format::PROMETHEUS_CONTENT_TYPE
         } else {
@@ -72,11 +193,62 @@ impl MetricsExporterInner {
         Response::builder()
             .status(StatusCode::OK)
             .header(header::CONTENT_TYPE, content_type)
-            .body(self.render_body().await)
+            .body(if filter.is_empty() {
+                self.render_body().await
+            } else {
+                self.scrape(filter.clone()).await
+            })
             .unwrap()
     }
 }
 
+/// Parses a scrape-time [`MetricsFilter`] from the query of a scrape request. Supported parameters
+/// are `name` (a glob pattern for metric names), `crate` and `module`. Each parameter may be specified
+/// multiple times, optionally with the `[]` suffix (e.g., `name[]=my_app_*`). Other parameters (e.g., cache busters
+/// or parameters added by proxies) are ignored.
+fn parse_filter(query: &str) -> Result<MetricsFilter, String> {
+    let mut filter = MetricsFilter::default();
+    for param in query.split('&').filter(|param| !param.is_empty()) {
+        let (raw_key, value) = param.split_once('=').unwrap_or((param, ""));
+        let key = percent_decode(raw_key).unwrap_or_default();
+        let key = key.strip_suffix("[]").unwrap_or(&key);
+        if !matches!(key, "name" | "crate" | "module") {
+            tracing::debug!(param = raw_key, "Ignoring unsupported query parameter");
+            continue;
+        }
+        let value = percent_decode(value)?;
+        filter = match key {
+            "name" => filter.with_name(value),
+            "crate" => filter.with_crate(value),
+            _ => filter.with_module(value),
+        };
+    }
+    Ok(filter)
+}
+
+/// Decodes a percent-encoded query component (`+` is decoded as a space).
+fn percent_decode(encoded: &str) -> Result<String, String> {
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut iter = encoded.bytes();
+    while let Some(byte) = iter.next() {
+        bytes.push(match byte {
+            b'+' => b' ',
+            b'%' => {
+                let hex_digit =
+                    |byte: Option<u8>| byte.and_then(|byte| char::from(byte).to_digit(16));
+                let (Some(high), Some(low)) = (hex_digit(iter.next()), hex_digit(iter.next()))
+                else {
+                    return Err(format!("Invalid percent encoding in `{encoded}`"));
+                };
+                // `unwrap()` is safe: two hex digits always fit into a byte
+                u8::try_from(high * 16 + low).unwrap()
+            }
+            _ => byte,
+        });
+    }
+    String::from_utf8(bytes).map_err(|_| format!("Invalid UTF-8 in `{encoded}`"))
+}
+
 /// Metrics exporter to Prometheus.
 ///
 /// An exporter scrapes metrics from a [`Registry`]. A [`Default`] exporter will use the registry
@@ -118,6 +290,9 @@ impl<'a> MetricsExporter<'a> {
             inner: MetricsExporterInner {
                 registry,
                 format: Format::OpenMetricsForPrometheus,
+                expose_group_switches: false,
+                scrape_cache_ttl: None,
+                last_scrape: Arc::default(),
             },
             shutdown_future: Box::pin(future::pending()),
         }
@@ -160,6 +335,29 @@ impl<'a> MetricsExporter<'a> {
         self
     }
 
+    /// Exposes the `/groups` admin endpoint allowing to enable or disable metric groups at runtime
+    /// (see [`GroupSwitch`](vise::GroupSwitch)). The endpoint is not exposed by default.
+    ///
+    /// The endpoint should only be exposed if the exporter server is not reachable by untrusted parties.
+    #[must_use]
+    pub fn with_group_switches(mut self) -> Self {
+        self.inner.expose_group_switches = true;
+        self
+    }
+
+    /// Enables caching scraped metrics for the specified `ttl`. Scrapes requested within `ttl` after the start
+    /// of the last performed scrape will return its result without re-encoding metrics. This is useful
+    /// if metrics are scraped by multiple parties (e.g., several Prometheus replicas), and encoding is expensive.
+    ///
+    /// Regardless of this setting, concurrent scrapes are coalesced: a scrape requested while another scrape
+    /// is in progress will wait for it and reuse its result. Scrapes served from the cache are counted
+    /// in the `vise_exporter_scrape_cache_hits` metric.
+    #[must_use]
+    pub fn with_scrape_cache(mut self, ttl: Duration) -> Self {
+        self.inner.scrape_cache_ttl = Some(ttl);
+        self
+    }
+
     /// Configures graceful shutdown for the exporter server.
     #[must_use]
     pub fn with_graceful_shutdown<F>(mut self, shutdown: F) -> Self
@@ -174,7 +372,15 @@ impl<'a> MetricsExporter<'a> {
     ///
     /// The server will expose the following endpoints:
     ///
-    /// - `GET` on any path: serves the metrics in the text format configured using [`Self::with_format()`]
+    /// - `GET` on any path: serves the metrics in the text format configured using [`Self::with_format()`].
+    ///   Metrics can be selected using query parameters: `name` (a metric name pattern with `*` and `?` wildcards),
+    ///   `crate` and `module` (crate name / module path the metric group is defined in). Each parameter
+    ///   may be repeated, optionally with the `[]` suffix, e.g. `?name[]=my_app_*&name[]=my_lib_*`.
+    ///   Other query parameters are ignored. See [`MetricsFilter`] for details.
+    /// - If [enabled](Self::with_group_switches()), `GET /groups` lists registered metric groups (including
+    ///   nested ones) together with their state, and `POST /groups/{crate_name}/{module_path}::{name}/enable`
+    ///   / `.../disable` enables or disables the specified group. Disabling a group also disables groups
+    ///   nested in it.
     ///
     /// # Errors
     ///
@@ -208,7 +414,13 @@ impl<'a> MetricsExporter<'a> {
                 tokio::spawn(async move {
                     let conn = http1::Builder::new().serve_connection(
                         io,
-                        service_fn(|_| async { Ok::<_, Infallible>(inner.render().await) }),
+                        service_fn(|request: Request<Incoming>| {
+                            let inner = &inner;
+                            async move {
+                                let response = inner.handle(request.method(), request.uri()).await;
+                                Ok::<_, Infallible>(response)
+                            }
+                        }),
                     );
                     tokio::pin!(conn);
 
@@ -305,6 +517,14 @@ impl<'a> MetricsExporter<'a> {
     }
 }
 
+fn plain_response(status: StatusCode, body: String) -> Response<String> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(body)
+        .unwrap()
+}
+
 async fn report_erroneous_response(endpoint: Uri, response: Response<Incoming>) {
     let status = response.status();
 
@@ -28,6 +28,12 @@ impl fmt::Display for Label {
     }
 }
 
+#[derive(Debug, Metrics)]
+struct DebugMetrics {
+    /// Debug counter.
+    counter: Counter,
+}
+
 #[derive(Debug, Metrics)]
 #[metrics(prefix = "modern")]
 struct TestMetrics {
@@ -35,6 +41,9 @@ struct TestMetrics {
     counter: Counter,
     /// Gauge with a label defined using the modern approach.
     gauge: Family<Label, Gauge<f64>>,
+    /// Nested debug metrics.
+    #[metrics(prefix = "debug")]
+    debug: DebugMetrics,
 }
 
 #[vise::register]
@@ -46,10 +55,213 @@ async fn basic_exporter_workflow() {
     let exporter = MetricsExporter::default();
     report_metrics();
 
-    let response = exporter.inner.render().await.into_body();
+    let response = exporter
+        .inner
+        .render(&MetricsFilter::default())
+        .await
+        .into_body();
     assert_scraped_payload_is_valid(&response);
 }
 
+#[tokio::test]
+async fn switching_groups_via_admin_endpoint() {
+    let _guard = TEST_MUTEX.lock().await;
+    let exporter = MetricsExporter::default().with_group_switches();
+    report_metrics();
+    let group_path = format!("vise_exporter/{}::TestMetrics", module_path!());
+    let nested_group_path = format!("vise_exporter/{}::DebugMetrics", module_path!());
+
+    let response = exporter
+        .inner
+        .handle(&Method::GET, &Uri::from_static("/groups"))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    for path in [&group_path, &nested_group_path] {
+        let expected_line = format!("{path} enabled");
+        assert!(
+            response.body().lines().any(|line| line == expected_line),
+            "{response:?}"
+        );
+    }
+
+    let disable_nested_uri: Uri = format!("/groups/{nested_group_path}/disable")
+        .parse()
+        .unwrap();
+    let response = exporter
+        .inner
+        .handle(&Method::POST, &disable_nested_uri)
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let payload = exporter
+        .inner
+        .render(&MetricsFilter::default())
+        .await
+        .into_body();
+    assert!(!payload.contains("modern_debug_counter"), "{payload}");
+    assert_scraped_payload_is_valid(&payload);
+    let enable_nested_uri: Uri = format!("/groups/{nested_group_path}/enable")
+        .parse()
+        .unwrap();
+    let response = exporter
+        .inner
+        .handle(&Method::POST, &enable_nested_uri)
+        .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let disable_uri: Uri = format!("/groups/{group_path}/disable").parse().unwrap();
+    let response = exporter.inner.handle(&Method::GET, &disable_uri).await;
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    let response = exporter.inner.handle(&Method::POST, &disable_uri).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let payload = exporter
+        .inner
+        .render(&MetricsFilter::default())
+        .await
+        .into_body();
+    assert!(!payload.contains("modern_counter"), "{payload}");
+
+    let enable_uri: Uri = format!("/groups/{group_path}/enable").parse().unwrap();
+    let response = exporter.inner.handle(&Method::POST, &enable_uri).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let payload = exporter
+        .inner
+        .render(&MetricsFilter::default())
+        .await
+        .into_body();
+    assert_scraped_payload_is_valid(&payload);
+
+    let payload = exporter
+        .inner
+        .render(&MetricsFilter::default())
+        .await
+        .into_body();
+    assert!(payload.contains("modern_debug_counter"), "{payload}");
+
+    // Groups must be addressed together with the crate name.
+    let unqualified_uri: Uri = format!("/groups/{}::TestMetrics/disable", module_path!())
+        .parse()
+        .unwrap();
+    let response = exporter.inner.handle(&Method::POST, &unqualified_uri).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let missing_uri = Uri::from_static("/groups/missing/missing::Metrics/disable");
+    let response = exporter.inner.handle(&Method::POST, &missing_uri).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn concurrent_scrapes_are_coalesced() {
+    let _guard = TEST_MUTEX.lock().await;
+    let exporter = MetricsExporter::default();
+    report_metrics();
+    let cache_hits = &EXPORTER_METRICS.scrape_cache_hits[&Facade::Vise];
+    let initial_cache_hits = cache_hits.get();
+
+    let (first_payload, second_payload) =
+        tokio::join!(exporter.inner.render_body(), exporter.inner.render_body());
+    assert_eq!(first_payload, second_payload);
+    assert_scraped_payload_is_valid(&first_payload);
+    assert_eq!(cache_hits.get(), initial_cache_hits + 1);
+
+    // Sequential scrapes shouldn't be coalesced if the cache is disabled.
+    exporter.inner.render_body().await;
+    assert_eq!(cache_hits.get(), initial_cache_hits + 1);
+}
+
+#[tokio::test]
+async fn caching_scrapes() {
+    let _guard = TEST_MUTEX.lock().await;
+    let exporter = MetricsExporter::default().with_scrape_cache(Duration::from_secs(3_600));
+    report_metrics();
+    let cache_hits = &EXPORTER_METRICS.scrape_cache_hits[&Facade::Vise];
+    let initial_cache_hits = cache_hits.get();
+
+    let payload = exporter.inner.render_body().await;
+    assert_scraped_payload_is_valid(&payload);
+    TEST_METRICS.counter.inc();
+    let cached_payload = exporter.inner.render_body().await;
+    assert_eq!(cached_payload, payload);
+    assert_eq!(cache_hits.get(), initial_cache_hits + 1);
+
+    let exporter = MetricsExporter::default().with_scrape_cache(Duration::ZERO);
+    let payload = exporter.inner.render_body().await;
+    TEST_METRICS.counter.inc();
+    let new_payload = exporter.inner.render_body().await;
+    assert_ne!(new_payload, payload);
+    assert_eq!(cache_hits.get(), initial_cache_hits + 1);
+}
+
+#[tokio::test]
+async fn filtering_metrics_via_query() {
+    let _guard = TEST_MUTEX.lock().await;
+    let exporter = MetricsExporter::default().with_scrape_cache(Duration::from_secs(3_600));
+    report_metrics();
+    // Populate the scrape cache; filtered scrapes must not use it.
+    exporter.inner.render_body().await;
+
+    let uri = Uri::from_static("/metrics?name[]=modern_gauge&name%5B%5D=other_*");
+    let response = exporter.inner.handle(&Method::GET, &uri).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let payload = response.into_body();
+    assert!(
+        payload.contains("modern_gauge{label=\"value\"} 42.0"),
+        "{payload}"
+    );
+    assert!(!payload.contains("modern_counter"), "{payload}");
+    assert!(payload.ends_with("# EOF\n"), "{payload}");
+
+    let uri = format!("/metrics?crate=vise_exporter&module={}", module_path!());
+    let response = exporter
+        .inner
+        .handle(&Method::GET, &uri.parse().unwrap())
+        .await;
+    let payload = response.into_body();
+    assert_scraped_payload_is_valid(&payload);
+    assert!(!payload.contains("vise_exporter_scrape"), "{payload}");
+
+    let uri = Uri::from_static("/metrics?crate=other");
+    let response = exporter.inner.handle(&Method::GET, &uri).await;
+    assert_eq!(response.into_body(), "# EOF\n");
+
+    for query in ["name=%5", "name=%zz"] {
+        let uri: Uri = format!("/metrics?{query}").parse().unwrap();
+        let response = exporter.inner.handle(&Method::GET, &uri).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{query}");
+    }
+}
+
+#[tokio::test]
+async fn ignoring_unknown_query_params() {
+    let _guard = TEST_MUTEX.lock().await;
+    let exporter = MetricsExporter::default().with_scrape_cache(Duration::from_secs(3_600));
+    report_metrics();
+    let full_payload = exporter.inner.render_body().await;
+
+    for query in ["foo=bar", "_=1700000000&foo", "%zz=1"] {
+        let uri: Uri = format!("/metrics?{query}").parse().unwrap();
+        let response = exporter.inner.handle(&Method::GET, &uri).await;
+        assert_eq!(response.status(), StatusCode::OK, "{query}");
+        assert_eq!(response.into_body(), full_payload, "{query}");
+    }
+}
+
+#[test]
+fn parsing_filter_query() {
+    assert_eq!(percent_decode("my_app_*").unwrap(), "my_app_*");
+    assert_eq!(percent_decode("a%3A%3Ab+c").unwrap(), "a::b c");
+    percent_decode("%").unwrap_err();
+    percent_decode("%f").unwrap_err();
+    percent_decode("%ff").unwrap_err(); // not valid UTF-8
+
+    assert!(parse_filter("").unwrap().is_empty());
+    assert!(!parse_filter("module=my_app%3A%3Ametrics")
+        .unwrap()
+        .is_empty());
+    assert!(parse_filter("foo=bar").unwrap().is_empty());
+    assert!(!parse_filter("name=x&foo=bar").unwrap().is_empty());
+    let err = parse_filter("name=%zz&foo=bar").unwrap_err();
+    assert!(err.contains("`%zz`"), "{err}");
+}
+
 fn report_metrics() {
     TEST_METRICS.counter.inc();
     TEST_METRICS.gauge[&Label("value")].set(42.0);
@@ -4,6 +4,21 @@
 //! running a web server or pushing to the Prometheus push gateway. An exporter should only be initialized
 //! in applications, not libraries.
 //!
+//! # Crate features
+//!
+//! ## `runtime-metrics`
+//!
+//! *(Off by default)*
+//!
+//! Enables [`RuntimeMetrics`] exporting metrics of a `tokio` runtime.
+//!
+//! ## `process-metrics`
+//!
+//! *(Off by default; only available on Linux)*
+//!
+//! Enables [`ProcessMetrics`] exporting conventional `process_*` metrics (CPU time, memory usage,
+//! file descriptors etc.) read from `procfs`.
+//!
 //! # Examples
 //!
 //! Running a pull-based exporter with graceful shutdown:
@@ -52,8 +67,21 @@
 
 mod exporter;
 mod metrics;
+#[cfg(all(feature = "process-metrics", target_os = "linux"))]
+mod process;
+#[cfg(feature = "runtime-metrics")]
+mod runtime;
 
 pub use crate::exporter::{MetricsExporter, MetricsServer};
+#[cfg(all(feature = "process-metrics", target_os = "linux"))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(all(feature = "process-metrics", target_os = "linux")))
+)]
+pub use crate::process::{FdLimitMetrics, ProcessMetrics};
+#[cfg(feature = "runtime-metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "runtime-metrics")))]
+pub use crate::runtime::RuntimeMetrics;
 
 #[cfg(doctest)]
 doc_comment::doctest!("../README.md");
@@ -2,7 +2,9 @@
 
 use std::{fmt, time::Duration};
 
-use vise::{Buckets, EncodeLabelSet, EncodeLabelValue, Family, Global, Histogram, Metrics, Unit};
+use vise::{
+    Buckets, Counter, EncodeLabelSet, EncodeLabelValue, Family, Global, Histogram, Metrics, Unit,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
 #[metrics(label = "facade")]
@@ -29,6 +31,9 @@ pub(crate) struct ExporterMetrics {
     /// Size of all metrics using a certain façade.
     #[metrics(buckets = BYTE_BUCKETS, unit = Unit::Bytes)]
     pub scraped_size: Family<Facade, Histogram<usize>>,
+    /// Number of scrapes served from the cache, either because they coincided with another scrape
+    /// or because the cached result was fresh enough.
+    pub scrape_cache_hits: Family<Facade, Counter>,
 }
 
 // Due to the recursive nature of the metrics definition, using a collector is problematic.
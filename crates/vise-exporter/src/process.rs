@@ -0,0 +1,276 @@
+//! Metrics for the current process, read from the Linux `procfs`.
+
+use std::{fs, io, path::Path, sync::atomic::AtomicU64};
+
+use vise::{BeforeScrapeError, Collector, Counter, Gauge, Metrics, Unit};
+
+/// Number of clock ticks per second used in `procfs`. This is the `USER_HZ` constant, which is fixed
+/// to 100 for the userspace ABI on all supported architectures.
+const CLOCK_TICKS_PER_SEC: f64 = 100.0;
+const KIB: u64 = 1_024;
+
+/// Process metrics with conventional names (`process_cpu_seconds_total`, `process_resident_memory_bytes` etc.)
+/// read from `/proc/self`.
+///
+/// # Examples
+///
+/// ```
+/// use vise_exporter::ProcessMetrics;
+///
+/// ProcessMetrics::register_collector().expect("process metrics are already collected");
+/// // Start the exporter etc.
+/// ```
+#[derive(Debug, Metrics)]
+#[metrics(prefix = "process")]
+pub struct ProcessMetrics {
+    /// Total user and system CPU time spent by the process.
+    #[metrics(unit = Unit::Seconds)]
+    pub cpu: Counter<f64, AtomicU64>,
+    /// Resident memory size of the process.
+    #[metrics(unit = Unit::Bytes)]
+    pub resident_memory: Gauge<u64>,
+    /// Virtual memory size of the process.
+    #[metrics(unit = Unit::Bytes)]
+    pub virtual_memory: Gauge<u64>,
+    /// Number of open file descriptors.
+    pub open_fds: Gauge<u64>,
+    /// Limit on the number of open file descriptors. Not reported if unlimited.
+    #[metrics(flatten)]
+    pub fd_limit: Option<FdLimitMetrics>,
+    /// Start time of the process since Unix epoch.
+    #[metrics(unit = Unit::Seconds)]
+    pub start_time: Gauge<f64>,
+    /// Number of OS threads in the process.
+    pub threads: Gauge<u64>,
+}
+
+/// Limit on the number of open file descriptors for the process. Part of [`ProcessMetrics`].
+#[derive(Debug, Metrics)]
+#[metrics(prefix = "process")]
+pub struct FdLimitMetrics {
+    /// Maximum number of open file descriptors.
+    pub max_fds: Gauge<u64>,
+}
+
+impl ProcessMetrics {
+    /// Reads metrics for the current process.
+    ///
+    /// # Errors
+    ///
+    /// Returns I/O errors reading `procfs` files, or an [`io::ErrorKind::InvalidData`] error if the files
+    /// have an unexpected format.
+    pub fn read() -> io::Result<Self> {
+        Self::read_from(Path::new("/proc"))
+    }
+
+    #[allow(clippy::cast_precision_loss)] // Values are far from exceeding the `f64` mantissa
+    fn read_from(procfs: &Path) -> io::Result<Self> {
+        let mut this = Self::default();
+        let process_dir = procfs.join("self");
+
+        let stat = ProcessStat::parse(&fs::read_to_string(process_dir.join("stat"))?)?;
+        this.cpu.inc_by(stat.cpu_ticks as f64 / CLOCK_TICKS_PER_SEC);
+        this.virtual_memory.set(stat.virtual_memory);
+        this.threads.set(stat.threads);
+        let boot_time = parse_boot_time(&fs::read_to_string(procfs.join("stat"))?)?;
+        this.start_time
+            .set(boot_time as f64 + stat.start_ticks as f64 / CLOCK_TICKS_PER_SEC);
+
+        let status = fs::read_to_string(process_dir.join("status"))?;
+        this.resident_memory
+            .set(parse_status_field(&status, "VmRSS")? * KIB);
+
+        let open_fds = fs::read_dir(process_dir.join("fd"))?.count();
+        // Reading the directory opens a file descriptor, which shouldn't be counted.
+        this.open_fds.set(open_fds.saturating_sub(1) as u64);
+        let limits = fs::read_to_string(process_dir.join("limits"))?;
+        if let Some(max_fds) = parse_max_fds(&limits)? {
+            let fd_limit = FdLimitMetrics::default();
+            fd_limit.max_fds.set(max_fds);
+            this.fd_limit = Some(fd_limit);
+        }
+        Ok(this)
+    }
+
+    /// Registers a collector reading process metrics on each scrape. Metrics are registered
+    /// in [`METRICS_REGISTRATIONS`](vise::METRICS_REGISTRATIONS), so they will be exported
+    /// by [`MetricsExporter::default()`](crate::MetricsExporter::default()). If reading metrics fails,
+    /// the error is logged, and no metrics are exported.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the collector is already registered.
+    pub fn register_collector() -> Result<(), BeforeScrapeError> {
+        PROCESS_COLLECTOR.before_scrape(|| match Self::read() {
+            Ok(metrics) => Some(metrics),
+            Err(err) => {
+                tracing::warn!(%err, "Failed reading process metrics");
+                None
+            }
+        })
+    }
+}
+
+#[vise::register]
+static PROCESS_COLLECTOR: Collector<Option<ProcessMetrics>> = Collector::new();
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Relevant fields from `/proc/self/stat`.
+#[derive(Debug, PartialEq)]
+struct ProcessStat {
+    cpu_ticks: u64,
+    threads: u64,
+    start_ticks: u64,
+    virtual_memory: u64,
+}
+
+impl ProcessStat {
+    fn parse(raw: &str) -> io::Result<Self> {
+        // The executable name (the 2nd field) is wrapped in parentheses and may contain whitespace,
+        // so we skip it altogether. The remaining fields start from the process state (the 3rd field).
+        let (_, fields) = raw
+            .rsplit_once(')')
+            .ok_or_else(|| invalid_data("no executable name in process stat"))?;
+        let fields: Vec<_> = fields.split_whitespace().collect();
+        let field = |idx: usize| -> io::Result<u64> {
+            let field = fields
+                .get(idx - 3)
+                .ok_or_else(|| invalid_data("too few fields in process stat"))?;
+            field
+                .parse()
+                .map_err(|_| invalid_data("invalid integer field in process stat"))
+        };
+
+        Ok(Self {
+            cpu_ticks: field(14)? + field(15)?,
+            threads: field(20)?,
+            start_ticks: field(22)?,
+            virtual_memory: field(23)?,
+        })
+    }
+}
+
+fn parse_boot_time(system_stat: &str) -> io::Result<u64> {
+    let boot_time = system_stat
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .ok_or_else(|| invalid_data("no boot time in system stat"))?;
+    boot_time
+        .trim()
+        .parse()
+        .map_err(|_| invalid_data("invalid boot time in system stat"))
+}
+
+/// Parses a field in kilobytes from `/proc/self/status`.
+fn parse_status_field(status: &str, name: &str) -> io::Result<u64> {
+    let value = status
+        .lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+        .ok_or_else(|| invalid_data("missing field in process status"))?;
+    let value = value.trim().trim_end_matches("kB").trim_end();
+    value
+        .parse()
+        .map_err(|_| invalid_data("invalid field in process status"))
+}
+
+/// Parses the soft limit on open files from `/proc/self/limits`. Returns `None` if the limit is absent.
+fn parse_max_fds(limits: &str) -> io::Result<Option<u64>> {
+    let Some(limit) = limits
+        .lines()
+        .find_map(|line| line.strip_prefix("Max open files"))
+    else {
+        return Ok(None);
+    };
+    match limit.split_whitespace().next() {
+        Some("unlimited") => Ok(None),
+        Some(limit) => limit
+            .parse()
+            .map(Some)
+            .map_err(|_| invalid_data("invalid max open files limit")),
+        None => Err(invalid_data("missing max open files limit")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vise::{Format, Registry};
+
+    use super::*;
+
+    #[test]
+    fn parsing_process_stat() {
+        let raw =
+            "29348 (tokio runtime) R 29344 29348 29344 0 -1 4194304 82 0 0 0 150 50 0 0 20 0 \
+                   4 0 466426 2703360 306 18446744073709551615 94822960496640";
+        let stat = ProcessStat::parse(raw).unwrap();
+        assert_eq!(
+            stat,
+            ProcessStat {
+                cpu_ticks: 200,
+                threads: 4,
+                start_ticks: 466_426,
+                virtual_memory: 2_703_360,
+            }
+        );
+
+        let err = ProcessStat::parse("29348 (cat) R 29344").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn parsing_other_procfs_files() {
+        let system_stat = "cpu  1 2 3 4\nintr 100\nbtime 1792327594\nprocesses 3";
+        assert_eq!(parse_boot_time(system_stat).unwrap(), 1_792_327_594);
+
+        let status = "Name:\tcat\nVmSize:\t    3340 kB\nVmRSS:\t    1776 kB\nThreads:\t1\n";
+        assert_eq!(parse_status_field(status, "VmRSS").unwrap(), 1_776);
+        assert_eq!(parse_status_field(status, "VmSize").unwrap(), 3_340);
+        parse_status_field(status, "VmSwap").unwrap_err();
+
+        let limits = "Limit                     Soft Limit           Hard Limit           Units     \n\
+                      Max open files            1024                 20000                files     \n";
+        assert_eq!(parse_max_fds(limits).unwrap(), Some(1_024));
+        let limits =
+            "Max open files            unlimited            unlimited            files     \n";
+        assert_eq!(parse_max_fds(limits).unwrap(), None);
+    }
+
+    #[test]
+    fn reading_process_metrics() {
+        let metrics = ProcessMetrics::read().unwrap();
+        assert!(metrics.resident_memory.get() > 0);
+        assert!(metrics.virtual_memory.get() >= metrics.resident_memory.get());
+        assert!(metrics.open_fds.get() > 0);
+        assert!(metrics.threads.get() > 0);
+        assert!(metrics.start_time.get() > 0.0);
+
+        let mut registry = Registry::empty();
+        registry.register_metrics(&metrics);
+        let mut buffer = String::new();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        let metric_names = [
+            "process_cpu_seconds_total",
+            "process_resident_memory_bytes",
+            "process_virtual_memory_bytes",
+            "process_open_fds",
+            "process_start_time_seconds",
+            "process_threads",
+        ];
+        for name in metric_names {
+            assert!(
+                buffer
+                    .lines()
+                    .any(|line| line.starts_with(&format!("{name} "))),
+                "{buffer}"
+            );
+        }
+
+        let has_max_fds = buffer
+            .lines()
+            .any(|line| line.starts_with("process_max_fds "));
+        assert_eq!(has_max_fds, metrics.fd_limit.is_some(), "{buffer}");
+    }
+}
@@ -0,0 +1,123 @@
+//! Metrics for the `tokio` runtime.
+
+use std::sync::atomic::AtomicU64;
+
+use tokio::runtime::Handle;
+use vise::{BeforeScrapeError, Collector, Counter, Gauge, LabeledFamily, Metrics, Unit};
+
+/// Metrics of a `tokio` runtime, based on [`tokio::runtime::RuntimeMetrics`].
+///
+/// Some metrics (`blocking_threads` and `worker_steals`) are only available if the app is compiled
+/// with `--cfg tokio_unstable` in `RUSTFLAGS`, since they rely on unstable `tokio` APIs. Without this flag,
+/// the corresponding fields are absent, and the metrics are not exported.
+///
+/// [`Self::register_collector()`] monitors a single runtime, and metrics do not have a label
+/// distinguishing runtimes. To monitor several runtimes, take snapshots with [`Self::new()`] in separate
+/// [`Collector`]s, and register each collector in its own [`Registry`](vise::Registry) via
+/// [`Registry::register_collector()`](vise::Registry::register_collector()).
+///
+/// # Examples
+///
+/// ```
+/// use vise_exporter::RuntimeMetrics;
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     RuntimeMetrics::register_collector(tokio::runtime::Handle::current())
+///         .expect("runtime metrics are already collected");
+///     // Start the exporter etc.
+/// }
+/// ```
+#[derive(Debug, Metrics)]
+#[metrics(prefix = "tokio")]
+pub struct RuntimeMetrics {
+    /// Number of worker threads used by the runtime.
+    pub workers: Gauge<usize>,
+    /// Number of alive tasks in the runtime.
+    pub alive_tasks: Gauge<usize>,
+    /// Number of tasks currently scheduled in the runtime's global queue.
+    pub global_queue_depth: Gauge<usize>,
+    /// Number of additional threads spawned by the runtime for blocking operations.
+    /// Only available with `--cfg tokio_unstable`.
+    #[cfg(tokio_unstable)]
+    pub blocking_threads: Gauge<usize>,
+    /// Total time a worker thread has been busy.
+    #[metrics(unit = Unit::Seconds, labels = ["worker"])]
+    pub worker_busy_duration: LabeledFamily<usize, Counter<f64, AtomicU64>>,
+    /// Number of tasks a worker thread has stolen from other workers.
+    /// Only available with `--cfg tokio_unstable`.
+    #[cfg(tokio_unstable)]
+    #[metrics(labels = ["worker"])]
+    pub worker_steals: LabeledFamily<usize, Counter>,
+}
+
+impl RuntimeMetrics {
+    /// Takes a snapshot of metrics for the runtime with the specified handle.
+    pub fn new(handle: &Handle) -> Self {
+        let runtime_metrics = handle.metrics();
+        let this = Self::default();
+        this.workers.set(runtime_metrics.num_workers());
+        this.alive_tasks.set(runtime_metrics.num_alive_tasks());
+        this.global_queue_depth
+            .set(runtime_metrics.global_queue_depth());
+        #[cfg(tokio_unstable)]
+        this.blocking_threads
+            .set(runtime_metrics.num_blocking_threads());
+
+        for worker in 0..runtime_metrics.num_workers() {
+            let busy_duration = runtime_metrics.worker_total_busy_duration(worker);
+            this.worker_busy_duration[&worker].inc_by(busy_duration.as_secs_f64());
+            #[cfg(tokio_unstable)]
+            this.worker_steals[&worker].inc_by(runtime_metrics.worker_steal_count(worker));
+        }
+        this
+    }
+
+    /// Registers a collector taking a snapshot of runtime metrics on each scrape. Metrics are registered
+    /// in [`METRICS_REGISTRATIONS`](vise::METRICS_REGISTRATIONS), so they will be exported
+    /// by [`MetricsExporter::default()`](crate::MetricsExporter::default()).
+    ///
+    /// Only one runtime can be monitored this way; see the type-level docs for monitoring several runtimes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the collector is already registered (e.g., for another runtime).
+    pub fn register_collector(handle: Handle) -> Result<(), BeforeScrapeError> {
+        RUNTIME_COLLECTOR.before_scrape(move || Self::new(&handle))
+    }
+}
+
+#[vise::register]
+static RUNTIME_COLLECTOR: Collector<RuntimeMetrics> = Collector::new();
+
+#[cfg(test)]
+mod tests {
+    use vise::{Format, Registry};
+
+    use super::*;
+
+    #[test]
+    fn collecting_runtime_metrics() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let metrics = runtime.block_on(async {
+            let _task = tokio::spawn(std::future::pending::<()>());
+            RuntimeMetrics::new(&Handle::current())
+        });
+        assert_eq!(metrics.workers.get(), 1);
+        assert_eq!(metrics.alive_tasks.get(), 1);
+        assert!(metrics.worker_busy_duration.contains(&0));
+
+        let mut registry = Registry::empty();
+        registry.register_metrics(&metrics);
+        let mut buffer = String::new();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        let lines: Vec<_> = buffer.lines().collect();
+        assert!(lines.contains(&"tokio_workers 1"), "{lines:#?}");
+        assert!(lines.contains(&"tokio_alive_tasks 1"), "{lines:#?}");
+        assert!(lines.iter().any(
+            |line| line.starts_with(r#"tokio_worker_busy_duration_seconds_total{worker="0"}"#)
+        ));
+    }
+}
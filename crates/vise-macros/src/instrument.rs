@@ -0,0 +1,393 @@
+//! `instrument` attribute macro.
+
+use std::fmt;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{quote, quote_spanned};
+use syn::{
+    meta::ParseNestedMeta,
+    parse::Parser,
+    spanned::Spanned,
+    visit_mut::{self, VisitMut},
+    Expr, Ident, Item, ItemFn, Path, ReturnType, Type,
+};
+
+/// Kind of the function output determining how call outcomes are classified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputKind {
+    Result,
+    Option,
+    Other,
+}
+
+impl OutputKind {
+    fn new(output: &ReturnType) -> Self {
+        let ReturnType::Type(_, ty) = output else {
+            return Self::Other;
+        };
+        let Type::Path(ty) = ty.as_ref() else {
+            return Self::Other;
+        };
+        let Some(last_segment) = ty.path.segments.last() else {
+            return Self::Other;
+        };
+        if last_segment.ident == "Option" {
+            Self::Option
+        } else if last_segment.ident == "Result" {
+            Self::Result
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// Arguments of the `instrument` attribute.
+#[derive(Default)]
+struct InstrumentAttrs {
+    cr: Option<Path>,
+    metrics: Option<Expr>,
+    latency: Option<Ident>,
+    errors: Option<Ident>,
+}
+
+impl InstrumentAttrs {
+    fn parse_meta(&mut self, meta: &ParseNestedMeta<'_>) -> syn::Result<()> {
+        if meta.path.is_ident("crate") {
+            self.cr = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("metrics") {
+            self.metrics = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("latency") {
+            self.latency = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("errors") {
+            self.errors = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error(
+                "Unsupported attribute; only `metrics`, `latency` and `errors` attributes \
+                 are supported (see `vise` crate docs for details)",
+            ))
+        }
+    }
+
+    fn path_to_crate(&self) -> proc_macro2::TokenStream {
+        if let Some(cr) = &self.cr {
+            quote!(#cr)
+        } else {
+            quote!(vise)
+        }
+    }
+}
+
+struct Instrumentation {
+    cr: proc_macro2::TokenStream,
+    metrics: Expr,
+    latency: Option<Ident>,
+    errors: Option<Ident>,
+    output_kind: OutputKind,
+}
+
+impl fmt::Debug for Instrumentation {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Instrumentation")
+            .field("latency", &self.latency)
+            .field("errors", &self.errors)
+            .field("output_kind", &self.output_kind)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Instrumentation {
+    fn new(attrs: InstrumentAttrs, item: &ItemFn) -> syn::Result<Self> {
+        let cr = attrs.path_to_crate();
+        let Some(metrics) = attrs.metrics else {
+            let message = "`metrics` attribute must be specified";
+            return Err(syn::Error::new_spanned(&item.sig, message));
+        };
+        if attrs.latency.is_none() && attrs.errors.is_none() {
+            let message = "At least one of `latency` and `errors` attributes must be specified";
+            return Err(syn::Error::new_spanned(&item.sig, message));
+        }
+
+        let output_kind = OutputKind::new(&item.sig.output);
+        if let Some(errors) = &attrs.errors {
+            if output_kind == OutputKind::Other {
+                let message = "`errors` attribute can only be used for functions returning \
+                               `Result` or `Option`";
+                return Err(syn::Error::new(errors.span(), message));
+            }
+        }
+
+        Ok(Self {
+            cr,
+            metrics,
+            latency: attrs.latency,
+            errors: attrs.errors,
+            output_kind,
+        })
+    }
+
+    /// Generates an expression classifying `__vise_output` and recording an error if necessary.
+    /// If `unknown_output` is set, the output is an `Option` that is `None` if the function exited
+    /// without the output being observed.
+    fn classify_outcome(&self, unknown_output: bool) -> proc_macro2::TokenStream {
+        let cr = &self.cr;
+        let record_error = |err: proc_macro2::TokenStream| {
+            self.errors.as_ref().map(|errors| {
+                quote_spanned! {errors.span()=>
+                    #cr::_private::RecordError::record_error(&__vise_metrics.#errors, #err);
+                }
+            })
+        };
+        let record_unknown_error = self.errors.as_ref().map(|errors| {
+            quote_spanned! {errors.span()=>
+                #cr::_private::record_unknown_error(&__vise_metrics.#errors, __vise_output);
+            }
+        });
+
+        let (ok, err, outcome, record_err) = match self.output_kind {
+            OutputKind::Result => (
+                quote!(::core::result::Result::Ok(_)),
+                quote!(::core::result::Result::Err(err)),
+                quote!(#cr::Outcome::Error),
+                record_error(quote!(err)),
+            ),
+            OutputKind::Option => (
+                quote!(::core::option::Option::Some(_)),
+                quote!(::core::option::Option::None),
+                quote!(#cr::Outcome::None),
+                record_error(quote!(&())),
+            ),
+            OutputKind::Other => return quote!(#cr::Outcome::Ok),
+        };
+
+        if unknown_output {
+            // The only way to exit the function without the output being observed (other than panicking,
+            // which is not recorded) is returning from a macro, e.g. `anyhow::bail!`.
+            quote! {
+                match __vise_output {
+                    ::core::option::Option::Some(#ok) => #cr::Outcome::Ok,
+                    ::core::option::Option::Some(#err) => {
+                        #record_err
+                        #outcome
+                    }
+                    ::core::option::Option::None => {
+                        #record_unknown_error
+                        #outcome
+                    }
+                }
+            }
+        } else {
+            quote! {
+                match &__vise_output {
+                    #ok => #cr::Outcome::Ok,
+                    #err => {
+                        #record_err
+                        #outcome
+                    }
+                }
+            }
+        }
+    }
+
+    fn observe_latency(&self) -> Option<proc_macro2::TokenStream> {
+        let cr = &self.cr;
+        self.latency.as_ref().map(|latency| {
+            quote_spanned! {latency.span()=>
+                #cr::_private::ObserveLatency::observe_latency(
+                    &__vise_metrics.#latency,
+                    __vise_latency,
+                    __vise_outcome,
+                );
+            }
+        })
+    }
+
+    fn instrument(&self, mut item: ItemFn) -> proc_macro2::TokenStream {
+        *item.block = if item.sig.asyncness.is_some() {
+            self.instrument_async(&item)
+        } else {
+            self.instrument_sync(&mut item)
+        };
+        quote!(#item)
+    }
+
+    fn instrument_async(&self, item: &ItemFn) -> syn::Block {
+        let metrics = &self.metrics;
+        let stmts = &item.block.stmts;
+
+        // Helps type inference (e.g., for `?` operators) in the wrapped body. This isn't possible
+        // for `impl Trait` return types.
+        let fake_return = match &item.sig.output {
+            ReturnType::Type(_, ty) if !matches!(ty.as_ref(), Type::ImplTrait(_)) => Some(quote! {
+                #[allow(unreachable_code, clippy::diverging_sub_expression)]
+                if false {
+                    let __vise_fake_return: #ty = loop {};
+                    return __vise_fake_return;
+                }
+            }),
+            _ => None,
+        };
+        let classify_outcome = self.classify_outcome(false);
+        let observe_latency = self.observe_latency();
+
+        let span = item.block.span();
+        syn::parse_quote_spanned! {span=>
+            {
+                let __vise_start = ::std::time::Instant::now();
+                let __vise_output = async move { #fake_return #(#stmts)* }.await;
+                let __vise_latency = __vise_start.elapsed();
+                let __vise_metrics = &#metrics;
+                #[allow(unused_variables)]
+                let __vise_outcome = #classify_outcome;
+                #observe_latency
+                __vise_output
+            }
+        }
+    }
+
+    /// Sync functions are instrumented with a guard, so that the function body is executed in place
+    /// (i.e., with the same borrowing semantics). `return` expressions and `?` operators in the body
+    /// are rewritten to pass the returned value to the guard.
+    fn instrument_sync(&self, item: &mut ItemFn) -> syn::Block {
+        let cr = &self.cr;
+        let metrics = &self.metrics;
+        let output_ty = match &item.sig.output {
+            ReturnType::Default => quote!(()),
+            ReturnType::Type(_, ty) if matches!(ty.as_ref(), Type::ImplTrait(_)) => quote!(_),
+            ReturnType::Type(_, ty) => quote!(#ty),
+        };
+
+        let mut rewriter = ReturnRewriter {
+            output_kind: self.output_kind,
+        };
+        rewriter.visit_block_mut(&mut item.block);
+        let stmts = &item.block.stmts;
+
+        let classify_outcome = self.classify_outcome(true);
+        let observe_latency = self.observe_latency();
+
+        let span = item.block.span();
+        syn::parse_quote_spanned! {span=>
+            {
+                let __vise_guard = #cr::_private::CallGuard::<#output_ty, _>::new(
+                    |__vise_output, __vise_latency| {
+                        let __vise_metrics = &#metrics;
+                        #[allow(unused_variables)]
+                        let __vise_outcome = #classify_outcome;
+                        #observe_latency
+                    },
+                );
+                #[allow(unreachable_code)]
+                let __vise_output: #output_ty = { #(#stmts)* };
+                #[allow(unreachable_code)]
+                __vise_guard.finish(__vise_output)
+            }
+        }
+    }
+}
+
+/// Rewrites `return` expressions and `?` operators in a sync function body so that they pass
+/// the returned value through `__vise_guard`.
+#[derive(Debug)]
+struct ReturnRewriter {
+    output_kind: OutputKind,
+}
+
+impl VisitMut for ReturnRewriter {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        match expr {
+            // Returns in these expressions don't return from the instrumented function
+            Expr::Closure(_) | Expr::Async(_) => return,
+            _ => visit_mut::visit_expr_mut(self, expr),
+        }
+
+        match expr {
+            Expr::Return(ret) => {
+                let value = ret
+                    .expr
+                    .take()
+                    .map_or_else(|| quote!(()), |value| quote!(#value));
+                // Spans are located at the original code, but are marked as generated by the macro
+                // so that lints (e.g., from `clippy`) don't fire for the generated code.
+                let span = Span::call_site().located_at(ret.span());
+                ret.expr = Some(syn::parse_quote_spanned! {span=>
+                    __vise_guard.finish(#value)
+                });
+            }
+            Expr::Try(try_expr) => {
+                let span = Span::call_site().located_at(try_expr.question_token.span);
+                let inner = &try_expr.expr;
+                *expr = match self.output_kind {
+                    OutputKind::Result => syn::parse_quote_spanned! {span=>
+                        match #inner {
+                            ::core::result::Result::Ok(value) => value,
+                            ::core::result::Result::Err(err) => {
+                                return __vise_guard.finish(::core::result::Result::Err(
+                                    ::core::convert::From::from(err),
+                                ));
+                            }
+                        }
+                    },
+                    OutputKind::Option => syn::parse_quote_spanned! {span=>
+                        match #inner {
+                            ::core::option::Option::Some(value) => value,
+                            ::core::option::Option::None => {
+                                return __vise_guard.finish(::core::option::Option::None);
+                            }
+                        }
+                    },
+                    // `?` on a non-standard return type; leave it as is
+                    OutputKind::Other => return,
+                };
+            }
+            _ => { /* do nothing */ }
+        }
+    }
+
+    fn visit_item_mut(&mut self, _item: &mut Item) {
+        // Nested items are not a part of the instrumented function
+    }
+}
+
+pub(crate) fn impl_instrument(attrs: TokenStream, input: TokenStream) -> TokenStream {
+    let item: ItemFn = match syn::parse(input) {
+        Ok(item) => item,
+        Err(err) => return err.into_compile_error().into(),
+    };
+
+    let mut instrument_attrs = InstrumentAttrs::default();
+    let parser = syn::meta::parser(|meta| instrument_attrs.parse_meta(&meta));
+    if let Err(err) = parser.parse(attrs) {
+        return err.into_compile_error().into();
+    }
+    match Instrumentation::new(instrument_attrs, &item) {
+        Ok(instrumentation) => instrumentation.instrument(item).into(),
+        Err(err) => err.into_compile_error().into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifying_output() {
+        let outputs: [(ReturnType, OutputKind); 7] = [
+            (syn::parse_quote!(), OutputKind::Other),
+            (syn::parse_quote!(-> u64), OutputKind::Other),
+            (syn::parse_quote!(-> Option<u64>), OutputKind::Option),
+            (syn::parse_quote!(-> Result<(), Error>), OutputKind::Result),
+            (syn::parse_quote!(-> anyhow::Result<()>), OutputKind::Result),
+            (syn::parse_quote!(-> io::Result<String>), OutputKind::Result),
+            (syn::parse_quote!(-> RpcResult<String>), OutputKind::Other),
+        ];
+        for (output, expected_kind) in outputs {
+            assert_eq!(OutputKind::new(&output), expected_kind);
+        }
+    }
+}
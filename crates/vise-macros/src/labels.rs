@@ -5,10 +5,13 @@ use std::{collections::HashSet, fmt};
 use proc_macro::TokenStream;
 use quote::{quote, quote_spanned};
 use syn::{
-    Attribute, Data, DeriveInput, Expr, Field, Fields, Ident, LitStr, Path, PathArguments, Type,
+    Attribute, Data, DeriveInput, Expr, Field, Fields, Generics, Ident, LitStr, Path,
+    PathArguments, Type, WherePredicate,
 };
 
-use crate::utils::{ensure_no_generics, metrics_attribute, ParseAttribute};
+use crate::utils::{
+    ensure_only_type_params, metrics_attribute, unit_suffix, with_predicates, ParseAttribute,
+};
 
 #[derive(Debug, Clone, Copy)]
 #[allow(clippy::enum_variant_names)]
@@ -177,13 +180,24 @@ impl ParseAttribute for EnumVariantAttrs {
     }
 }
 
-#[derive(Debug)]
 struct EncodeLabelValueImpl {
     attrs: EncodeLabelAttrs,
     name: Ident,
+    generics: Generics,
     enum_variants: Option<Vec<EnumVariant>>,
 }
 
+impl fmt::Debug for EncodeLabelValueImpl {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("EncodeLabelValueImpl")
+            .field("attrs", &self.attrs)
+            .field("name", &self.name)
+            .field("enum_variants", &self.enum_variants)
+            .finish_non_exhaustive()
+    }
+}
+
 impl EncodeLabelValueImpl {
     fn new(raw: &DeriveInput) -> syn::Result<Self> {
         let attrs = Self::parse_attrs(raw, "EncodeLabelValue")?;
@@ -196,11 +210,12 @@ impl EncodeLabelValueImpl {
             attrs,
             enum_variants,
             name: raw.ident.clone(),
+            generics: raw.generics.clone(),
         })
     }
 
     fn parse_attrs(raw: &DeriveInput, derived_macro: &str) -> syn::Result<EncodeLabelAttrs> {
-        ensure_no_generics(&raw.generics, derived_macro)?;
+        ensure_only_type_params(&raw.generics, derived_macro)?;
 
         let attrs: EncodeLabelAttrs = metrics_attribute(&raw.attrs)?;
         if let Some(format) = &attrs.format {
@@ -277,8 +292,19 @@ impl EncodeLabelValueImpl {
             }
         };
 
+        // The trait required by a custom format cannot be determined, so bounds for it must be specified
+        // on the type itself.
+        let generics = if self.enum_variants.is_none() && self.attrs.format.is_none() {
+            let (_, ty_generics, _) = self.generics.split_for_impl();
+            let display_predicate: WherePredicate =
+                syn::parse_quote!(#name #ty_generics: ::core::fmt::Display);
+            with_predicates(&self.generics, [display_predicate])
+        } else {
+            self.generics.clone()
+        };
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
         quote! {
-            impl #encoding::EncodeLabelValue for #name {
+            impl #impl_generics #encoding::EncodeLabelValue for #name #ty_generics #where_clause {
                 fn encode(
                     &self,
                     encoder: &mut #encoding::LabelValueEncoder<'_>,
@@ -294,6 +320,7 @@ impl EncodeLabelValueImpl {
 struct LabelFieldAttrs {
     skip: Option<Path>,
     unit: Option<Expr>,
+    flatten: bool,
 }
 
 impl fmt::Debug for LabelFieldAttrs {
@@ -302,6 +329,7 @@ impl fmt::Debug for LabelFieldAttrs {
             .debug_struct("LabelFieldAttrs")
             .field("skip", &self.skip.as_ref().map(|_| ".."))
             .field("unit", &self.unit.as_ref().map(|_| ".."))
+            .field("flatten", &self.flatten)
             .finish()
     }
 }
@@ -316,21 +344,40 @@ impl ParseAttribute for LabelFieldAttrs {
             } else if meta.path.is_ident("unit") {
                 attrs.unit = Some(meta.value()?.parse()?);
                 Ok(())
+            } else if meta.path.is_ident("flatten") {
+                attrs.flatten = true;
+                Ok(())
             } else {
                 Err(meta.error("unsupported attribute"))
             }
         })?;
+
+        if attrs.flatten && (attrs.skip.is_some() || attrs.unit.is_some()) {
+            let message = "`flatten` attribute cannot be combined with other attributes";
+            return Err(syn::Error::new_spanned(raw, message));
+        }
         Ok(attrs)
     }
 }
 
-#[derive(Debug)]
 struct LabelField {
     name: Ident,
+    ty: Type,
     is_option: bool,
     attrs: LabelFieldAttrs,
 }
 
+impl fmt::Debug for LabelField {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("LabelField")
+            .field("name", &self.name)
+            .field("is_option", &self.is_option)
+            .field("attrs", &self.attrs)
+            .finish_non_exhaustive()
+    }
+}
+
 impl LabelField {
     fn parse(raw: &Field) -> syn::Result<Self> {
         let name = raw.ident.clone().ok_or_else(|| {
@@ -340,6 +387,7 @@ impl LabelField {
 
         Ok(Self {
             name,
+            ty: raw.ty.clone(),
             is_option: Self::detect_is_option(&raw.ty),
             attrs: metrics_attribute(&raw.attrs)?,
         })
@@ -360,6 +408,18 @@ impl LabelField {
         LitStr::new(&self.label_string(), name.span())
     }
 
+    /// Returns the label name including the unit suffix, if any. Only units specified as `Unit::*` paths
+    /// can be resolved during macro expansion; for other unit expressions, the suffix is omitted.
+    fn full_label_string(&self) -> String {
+        let label = self.label_string();
+        let suffix = self.attrs.unit.as_ref().and_then(unit_suffix);
+        if let Some(suffix) = suffix {
+            format!("{label}_{suffix}")
+        } else {
+            label
+        }
+    }
+
     fn detect_is_option(ty: &Type) -> bool {
         let Type::Path(ty) = ty else {
             return false;
@@ -375,7 +435,17 @@ impl LabelField {
             )
     }
 
+    fn encode_flattened(&self, cr: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        quote_spanned! {name.span()=>
+            #cr::traits::EncodeLabelSet::encode(&self.#name, encoder)?;
+        }
+    }
+
     fn encode(&self, cr: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        if self.attrs.flatten {
+            return self.encode_flattened(cr);
+        }
         let encoding = quote!(#cr::_reexports::encoding);
 
         let name = &self.name;
@@ -421,13 +491,24 @@ impl LabelField {
     }
 }
 
-#[derive(Debug)]
 struct EncodeLabelSetImpl {
     attrs: EncodeLabelAttrs,
     name: Ident,
+    generics: Generics,
     fields: Option<Vec<LabelField>>,
 }
 
+impl fmt::Debug for EncodeLabelSetImpl {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("EncodeLabelSetImpl")
+            .field("attrs", &self.attrs)
+            .field("name", &self.name)
+            .field("fields", &self.fields)
+            .finish_non_exhaustive()
+    }
+}
+
 impl EncodeLabelSetImpl {
     fn new(raw: &DeriveInput) -> syn::Result<Self> {
         let attrs = EncodeLabelValueImpl::parse_attrs(raw, "EncodeLabelSet")?;
@@ -447,6 +528,7 @@ impl EncodeLabelSetImpl {
         Ok(Self {
             attrs,
             name,
+            generics: raw.generics.clone(),
             fields,
         })
     }
@@ -458,7 +540,8 @@ impl EncodeLabelSetImpl {
             quote_spanned!(span=> #cr::validation::assert_label_name(#label);)
         } else {
             let fields = self.fields.as_ref().unwrap();
-            let field_assertions = fields.iter().map(|field| {
+            let fields = fields.iter().filter(|field| !field.attrs.flatten);
+            let field_assertions = fields.map(|field| {
                 let label = field.label_literal();
                 let span = label.span();
                 let cr = self.attrs.path_to_crate(span);
@@ -466,11 +549,58 @@ impl EncodeLabelSetImpl {
             });
             quote!(#(#field_assertions;)*)
         };
+        // Label names from flattened sets are checked for collisions when evaluating `LABELS`. For generic types,
+        // this happens when `LABELS` is used; see `impl_set()`.
+        let labels_assertion = (self.has_flattened_fields() && self.generics.params.is_empty())
+            .then(|| {
+                let name = &self.name;
+                let cr = self.attrs.path_to_crate(name.span());
+                quote!(let _ = <#name as #cr::traits::LabelSetNames>::LABELS;)
+            });
         quote! {
-            const _: () = { #label_assertions };
+            const _: () = { #label_assertions #labels_assertion };
         }
     }
 
+    fn has_flattened_fields(&self) -> bool {
+        self.fields
+            .as_ref()
+            .is_some_and(|fields| fields.iter().any(|field| field.attrs.flatten))
+    }
+
+    /// Returns generics for the derived impl with bounds required to encode label values.
+    fn impl_generics(&self, encoding: &proc_macro2::TokenStream) -> Generics {
+        if self.generics.params.is_empty() {
+            return self.generics.clone();
+        }
+
+        let name = &self.name;
+        let (_, ty_generics, _) = self.generics.split_for_impl();
+        let self_predicate: WherePredicate =
+            syn::parse_quote!(#name #ty_generics: ::core::marker::Send + ::core::marker::Sync);
+        let value_predicates: Vec<WherePredicate> = if self.attrs.label.is_some() {
+            vec![syn::parse_quote!(#name #ty_generics: #encoding::EncodeLabelValue)]
+        } else {
+            let fields = self.fields.as_ref().unwrap();
+            fields
+                .iter()
+                .map(|field| {
+                    let ty = &field.ty;
+                    if field.attrs.flatten {
+                        let cr = self.attrs.path_to_crate(proc_macro2::Span::call_site());
+                        syn::parse_quote!(#ty: #cr::traits::EncodeLabelSet + #cr::traits::LabelSetNames)
+                    } else {
+                        syn::parse_quote!(#ty: #encoding::EncodeLabelValue)
+                    }
+                })
+                .collect()
+        };
+        with_predicates(
+            &self.generics,
+            [self_predicate].into_iter().chain(value_predicates),
+        )
+    }
+
     fn impl_set(&self) -> proc_macro2::TokenStream {
         let encode_impl = if let Some(label) = &self.attrs.label {
             let cr = self.attrs.path_to_crate(label.span());
@@ -500,13 +630,49 @@ impl EncodeLabelSetImpl {
 
         let name = &self.name;
         let cr = self.attrs.path_to_crate(proc_macro2::Span::call_site());
+        let labels = if let Some(label) = &self.attrs.label {
+            quote!(&[#label])
+        } else if self.has_flattened_fields() {
+            let fields = self.fields.as_ref().unwrap();
+            let parts = fields.iter().map(|field| {
+                if field.attrs.flatten {
+                    let ty = &field.ty;
+                    quote!(<#ty as #cr::traits::LabelSetNames>::LABELS)
+                } else {
+                    let name = field.full_label_string();
+                    quote!(&[#name])
+                }
+            });
+            quote_spanned! {name.span()=>
+                {
+                    let names: &'static #cr::validation::LabelNames =
+                        &#cr::validation::LabelNames::concat(&[#(#parts,)*]);
+                    names.as_slice()
+                }
+            }
+        } else {
+            let fields = self.fields.as_ref().unwrap();
+            let names = fields.iter().map(LabelField::full_label_string);
+            quote!(&[#(#names),*])
+        };
+        // Ensures that label names are checked for collisions for each monomorphization of a generic type.
+        let labels_assertion = (self.has_flattened_fields() && !self.generics.params.is_empty())
+            .then(|| quote!(let _ = <Self as #cr::traits::LabelSetNames>::LABELS;));
+
         let encoding = quote!(#cr::_reexports::encoding);
+        let generics = self.impl_generics(&encoding);
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
         quote! {
-            impl #cr::traits::EncodeLabelSet for #name {
+            impl #impl_generics #cr::traits::LabelSetNames for #name #ty_generics #where_clause {
+                const LABELS: &'static [&'static str] = #labels;
+            }
+
+            impl #impl_generics #cr::traits::EncodeLabelSet for #name #ty_generics #where_clause {
                 fn encode(
                     &self,
                     encoder: &mut #encoding::LabelSetEncoder<'_>,
                 ) -> ::core::fmt::Result {
+                    #labels_assertion
                     #encode_impl
                 }
             }
@@ -572,6 +738,33 @@ mod tests {
         assert!(fields[1].attrs.skip.is_some());
     }
 
+    #[test]
+    fn label_names_with_units() {
+        let input: DeriveInput = syn::parse_quote! {
+            struct InfoLabels {
+                version: &'static str,
+                #[metrics(unit = Unit::Seconds)]
+                timeout: DurationAsSecs,
+                #[metrics(unit = vise::Unit::Bytes)]
+                capacity: u64,
+                #[metrics(unit = CUSTOM_UNIT)]
+                custom: u64,
+            }
+        };
+        let label_set = EncodeLabelSetImpl::new(&input).unwrap();
+        let names: Vec<_> = label_set
+            .fields
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(LabelField::full_label_string)
+            .collect();
+        assert_eq!(
+            names,
+            ["version", "timeout_seconds", "capacity_bytes", "custom"]
+        );
+    }
+
     #[test]
     fn label_value_redefinition_error() {
         let input: DeriveInput = syn::parse_quote! {
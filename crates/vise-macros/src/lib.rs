@@ -18,6 +18,7 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 
+mod instrument;
 mod labels;
 mod metrics;
 mod register;
@@ -42,3 +43,8 @@ pub fn encode_label_set(input: TokenStream) -> TokenStream {
 pub fn register(_attrs: TokenStream, input: TokenStream) -> TokenStream {
     register::impl_register(input)
 }
+
+#[proc_macro_attribute]
+pub fn instrument(attrs: TokenStream, input: TokenStream) -> TokenStream {
+    instrument::impl_instrument(attrs, input)
+}
@@ -5,10 +5,13 @@ use std::fmt;
 use proc_macro::TokenStream;
 use quote::{quote, quote_spanned};
 use syn::{
-    spanned::Spanned, Attribute, Data, DeriveInput, Expr, Field, Ident, Lit, LitStr, Path, Type,
+    spanned::Spanned, Attribute, Data, DeriveInput, Expr, Field, Generics, Ident, Lit, LitStr,
+    Path, Type, WherePredicate,
 };
 
-use crate::utils::{ensure_no_generics, metrics_attribute, ParseAttribute};
+use crate::utils::{
+    ensure_only_type_params, metrics_attribute, unit_suffix, with_predicates, ParseAttribute,
+};
 
 /// Struct-level `#[metrics(..)]` attributes.
 #[derive(Default)]
@@ -57,11 +60,63 @@ impl ParseAttribute for MetricsAttrs {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+enum Stability {
+    Alpha,
+    Stable,
+}
+
+impl Stability {
+    fn parse(raw: &LitStr) -> syn::Result<Self> {
+        match raw.value().as_str() {
+            "alpha" => Ok(Self::Alpha),
+            "stable" => Ok(Self::Stable),
+            _ => Err(syn::Error::new(
+                raw.span(),
+                "Unsupported stability level; expected \"alpha\" or \"stable\"",
+            )),
+        }
+    }
+
+    fn variant(self) -> Ident {
+        let name = match self {
+            Self::Alpha => "Alpha",
+            Self::Stable => "Stable",
+        };
+        Ident::new(name, proc_macro2::Span::call_site())
+    }
+}
+
 #[derive(Default)]
 struct MetricsFieldAttrs {
     buckets: Option<Expr>,
     unit: Option<Expr>,
     labels: Option<Expr>,
+    stability: Option<Stability>,
+    deprecated_name: Option<LitStr>,
+    rename: Option<LitStr>,
+    help: Option<Expr>,
+    flatten: bool,
+    prefix: Option<LitStr>,
+    skip: bool,
+    sharded: bool,
+}
+
+impl MetricsFieldAttrs {
+    fn is_nested(&self) -> bool {
+        self.flatten || self.prefix.is_some()
+    }
+
+    fn has_metric_attrs(&self) -> bool {
+        self.buckets.is_some()
+            || self.unit.is_some()
+            || self.labels.is_some()
+            || self.stability.is_some()
+            || self.deprecated_name.is_some()
+            || self.rename.is_some()
+            || self.help.is_some()
+            || self.sharded
+    }
 }
 
 impl fmt::Debug for MetricsFieldAttrs {
@@ -71,6 +126,17 @@ impl fmt::Debug for MetricsFieldAttrs {
             .field("buckets", &self.buckets.as_ref().map(|_| ".."))
             .field("unit", &self.unit.as_ref().map(|_| ".."))
             .field("labels", &self.labels.as_ref().map(|_| ".."))
+            .field("stability", &self.stability)
+            .field(
+                "deprecated_name",
+                &self.deprecated_name.as_ref().map(LitStr::value),
+            )
+            .field("rename", &self.rename.as_ref().map(LitStr::value))
+            .field("help", &self.help.as_ref().map(|_| ".."))
+            .field("flatten", &self.flatten)
+            .field("prefix", &self.prefix.as_ref().map(LitStr::value))
+            .field("skip", &self.skip)
+            .field("sharded", &self.sharded)
             .finish()
     }
 }
@@ -88,13 +154,53 @@ impl ParseAttribute for MetricsFieldAttrs {
             } else if meta.path.is_ident("labels") {
                 attrs.labels = Some(meta.value()?.parse()?);
                 Ok(())
+            } else if meta.path.is_ident("stability") {
+                attrs.stability = Some(Stability::parse(&meta.value()?.parse()?)?);
+                Ok(())
+            } else if meta.path.is_ident("deprecated_name") {
+                attrs.deprecated_name = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("rename") {
+                attrs.rename = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("help") {
+                attrs.help = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("flatten") {
+                attrs.flatten = true;
+                Ok(())
+            } else if meta.path.is_ident("prefix") {
+                attrs.prefix = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("skip") {
+                attrs.skip = true;
+                Ok(())
+            } else if meta.path.is_ident("sharded") {
+                attrs.sharded = true;
+                Ok(())
             } else {
                 Err(meta.error(
-                    "Unsupported attribute; only `buckets`, `unit` and `labels` attributes are supported \
+                    "Unsupported attribute; only `buckets`, `unit`, `labels`, `stability`, `deprecated_name`, \
+                     `rename`, `help`, `flatten`, `prefix`, `skip` and `sharded` attributes are supported \
                      (see `vise` crate docs for details)"
                 ))
             }
         })?;
+
+        if attrs.skip && (attrs.is_nested() || attrs.has_metric_attrs()) {
+            let message = "`skip` attribute cannot be combined with other attributes";
+            return Err(syn::Error::new_spanned(raw, message));
+        }
+
+        if attrs.flatten && attrs.prefix.is_some() {
+            let message = "`flatten` and `prefix` attributes are mutually exclusive";
+            return Err(syn::Error::new_spanned(raw, message));
+        }
+        if attrs.is_nested() && attrs.has_metric_attrs() {
+            let message = "`flatten` and `prefix` attributes cannot be combined with attributes \
+                           applicable to metrics (`buckets`, `unit`, etc.)";
+            return Err(syn::Error::new_spanned(raw, message));
+        }
         Ok(attrs)
     }
 }
@@ -124,7 +230,7 @@ impl MetricsField {
             syn::Error::new_spanned(raw, message)
         })?;
         let ty = raw.ty.clone();
-        let attrs = metrics_attribute(&raw.attrs)?;
+        let mut attrs: MetricsFieldAttrs = metrics_attribute(&raw.attrs)?;
 
         let doc_lines = raw.attrs.iter().filter_map(|attr| {
             if attr.meta.path().is_ident("doc") {
@@ -141,8 +247,31 @@ impl MetricsField {
             }
         });
 
+        let docs = if let Some(Expr::Lit(help)) = &attrs.help {
+            let Lit::Str(help) = &help.lit else {
+                let message =
+                    "`help` must be a string or an expression evaluating to `&'static str`";
+                return Err(syn::Error::new_spanned(help, message));
+            };
+            // String help is processed in the same way as doc comments.
+            let docs = Self::normalize_docs([help.value()]);
+            attrs.help = None;
+            docs
+        } else {
+            Self::normalize_docs(doc_lines)
+        };
+
+        Ok(Self {
+            attrs,
+            name,
+            ty,
+            docs,
+        })
+    }
+
+    fn normalize_docs(lines: impl IntoIterator<Item = String>) -> String {
         let mut docs = String::new();
-        for line in doc_lines {
+        for line in lines {
             let line = line.trim();
             if !line.is_empty() {
                 if !docs.is_empty() {
@@ -155,13 +284,43 @@ impl MetricsField {
             // Remove the trailing punctuation since it'll be inserted automatically by the `Registry`.
             docs.pop();
         }
+        docs
+    }
 
-        Ok(Self {
-            attrs,
-            name,
-            ty,
-            docs,
-        })
+    /// Returns the metric name without the prefix.
+    fn metric_name(&self) -> String {
+        self.attrs
+            .rename
+            .as_ref()
+            .map_or_else(|| self.name.to_string(), LitStr::value)
+    }
+
+    /// Returns the metric help, which is either a processed doc comment, or an expression
+    /// from the `help` attribute.
+    fn help(&self) -> proc_macro2::TokenStream {
+        if let Some(help) = &self.attrs.help {
+            quote!(#help)
+        } else {
+            let docs = &self.docs;
+            quote!(#docs)
+        }
+    }
+
+    fn initialize_with_default(&self) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        let span = self.ty.span();
+        quote_spanned! {span=>
+            #name: ::core::default::Default::default()
+        }
+    }
+
+    fn initialize_nested(&self, cr: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        let ty = &self.ty;
+        let span = ty.span();
+        quote_spanned! {span=>
+            #name: <#ty as #cr::Metrics>::nested_default(__switch)
+        }
     }
 
     fn initialize_default(&self, cr: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
@@ -174,20 +333,93 @@ impl MetricsField {
         if let Some(labels) = &self.attrs.labels {
             builder = quote_spanned!(span=> #builder.with_labels(#labels));
         }
+        if self.attrs.sharded {
+            builder = quote_spanned!(span=> #builder.with_sharding());
+        }
+        builder = quote_spanned!(span=> #builder.with_switch(__switch));
 
         quote_spanned! {span=>
             #name: #cr::BuildMetric::build(#builder)
         }
     }
 
-    fn visit(&self, prefix: Option<&str>) -> proc_macro2::TokenStream {
-        let name = &self.name;
-        let name_str = if let Some(prefix) = prefix {
+    fn prefixed_name(name: &str, prefix: Option<&str>) -> String {
+        if let Some(prefix) = prefix {
             format!("{prefix}_{name}")
         } else {
-            name.to_string()
+            name.to_owned()
+        }
+    }
+
+    fn stability(&self, cr: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let variant = self.attrs.stability.unwrap_or(Stability::Stable).variant();
+        quote!(#cr::descriptors::Stability::#variant)
+    }
+
+    /// Returns help for the deprecated metric name.
+    fn deprecated_docs(&self, name_str: &str) -> String {
+        let suffix = self.attrs.unit.as_ref().and_then(unit_suffix);
+        let full_name = if let Some(suffix) = suffix {
+            format!("{name_str}_{suffix}")
+        } else {
+            name_str.to_owned()
         };
-        let docs = &self.docs;
+        if self.docs.is_empty() {
+            format!("Deprecated name of `{full_name}`")
+        } else {
+            format!("{} (deprecated name of `{full_name}`)", self.docs)
+        }
+    }
+
+    /// Returns the full prefix for metrics in a nested group, or `None` if the group is flattened.
+    fn nested_prefix(&self, prefix: Option<&str>) -> Option<String> {
+        let nested_prefix = self.attrs.prefix.as_ref()?.value();
+        Some(Self::prefixed_name(&nested_prefix, prefix))
+    }
+
+    fn visit_nested(
+        &self,
+        prefix: Option<&str>,
+        cr: &proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        if let Some(nested_prefix) = self.nested_prefix(prefix) {
+            quote! {
+                #cr::Metrics::visit_metrics(
+                    &self.#name,
+                    &mut #cr::_private::PrefixedVisitor::new(#nested_prefix, visitor),
+                )
+            }
+        } else {
+            quote!(#cr::Metrics::visit_metrics(&self.#name, visitor))
+        }
+    }
+
+    fn describe_nested(
+        &self,
+        prefix: Option<&str>,
+        cr: &proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let ty = &self.ty;
+        let nested_prefix = if let Some(nested_prefix) = self.nested_prefix(prefix) {
+            quote!(::core::option::Option::Some(#nested_prefix))
+        } else {
+            quote!(::core::option::Option::None)
+        };
+        quote! {
+            #cr::_private::describe_nested_group::<#ty>(prefix, #nested_prefix, descriptors)
+        }
+    }
+
+    fn visit(
+        &self,
+        prefix: Option<&str>,
+        cr: &proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        let name_str = Self::prefixed_name(&self.metric_name(), prefix);
+        let help = self.help();
+        let stability = self.stability(cr);
 
         let unit = if let Some(unit) = &self.attrs.unit {
             quote!(::core::option::Option::Some(#unit))
@@ -195,13 +427,37 @@ impl MetricsField {
             quote!(::core::option::Option::None)
         };
 
+        let deprecated_visit = self.attrs.deprecated_name.as_ref().map(|deprecated_name| {
+            let deprecated_name = Self::prefixed_name(&deprecated_name.value(), prefix);
+            // Custom help expressions cannot be amended, so they are reused as is.
+            let deprecated_help = if self.attrs.help.is_some() {
+                self.help()
+            } else {
+                let deprecated_docs = self.deprecated_docs(&name_str);
+                quote!(#deprecated_docs)
+            };
+            quote! {
+                visitor.visit_metric(
+                    ::std::borrow::Cow::Borrowed(#deprecated_name),
+                    #deprecated_help,
+                    #unit,
+                    #stability,
+                    true,
+                    ::std::boxed::Box::new(::core::clone::Clone::clone(&self.#name)),
+                );
+            }
+        });
+
         quote! {
             visitor.visit_metric(
-                #name_str,
-                #docs,
+                ::std::borrow::Cow::Borrowed(#name_str),
+                #help,
                 #unit,
+                #stability,
+                false,
                 ::std::boxed::Box::new(::core::clone::Clone::clone(&self.#name)),
             );
+            #deprecated_visit
         }
     }
 
@@ -211,41 +467,79 @@ impl MetricsField {
         cr: &proc_macro2::TokenStream,
     ) -> proc_macro2::TokenStream {
         let name = &self.name;
-        let name_str = if let Some(prefix) = prefix {
-            format!("{prefix}_{name}")
+        let name_str = Self::prefixed_name(&self.metric_name(), prefix);
+        let help = self.help();
+        let ty = &self.ty;
+        let stability = self.stability(cr);
+        let deprecated_name = if let Some(deprecated_name) = &self.attrs.deprecated_name {
+            let deprecated_name = Self::prefixed_name(&deprecated_name.value(), prefix);
+            quote!(::core::option::Option::Some(#deprecated_name))
         } else {
-            name.to_string()
+            quote!(::core::option::Option::None)
         };
-        let docs = &self.docs;
-        let ty = &self.ty;
         let unit = if let Some(unit) = &self.attrs.unit {
             quote!(::core::option::Option::Some(#unit))
         } else {
             quote!(::core::option::Option::None)
         };
+        let labels = if let Some(labels) = &self.attrs.labels {
+            quote!(&#labels)
+        } else {
+            quote!({
+                use #cr::_private::LabelNamesFallback as _;
+                #cr::_private::LabelNamesProbe::<#ty>::LABELS
+            })
+        };
+        let buckets = if let Some(buckets) = &self.attrs.buckets {
+            // Mirrors the builder call in `initialize_default()`, so that a type error in the buckets expression
+            // is reported only once.
+            let span = self.ty.span();
+            quote_spanned! {span=>
+                ::core::option::Option::Some(#cr::descriptors::BucketsDescriptor::new(
+                    || #cr::MetricBuilder::new().with_buckets(#buckets).into_buckets(),
+                ))
+            }
+        } else {
+            quote!(::core::option::Option::None)
+        };
 
         quote! {
             #cr::descriptors::MetricDescriptor {
                 name: #name_str,
                 field_name: ::core::stringify!(#name),
                 metric_type: <#ty as #cr::_reexports::TypedMetric>::TYPE,
-                help: #docs,
+                help: #help,
                 unit: #unit,
+                labels: #labels,
+                buckets: #buckets,
+                stability: #stability,
+                deprecated_name: #deprecated_name,
             }
         }
     }
 }
 
-#[derive(Debug)]
 struct MetricsImpl {
     attrs: MetricsAttrs,
     name: Ident,
+    generics: Generics,
     fields: Vec<MetricsField>,
 }
 
+impl fmt::Debug for MetricsImpl {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("MetricsImpl")
+            .field("attrs", &self.attrs)
+            .field("name", &self.name)
+            .field("fields", &self.fields)
+            .finish_non_exhaustive()
+    }
+}
+
 impl MetricsImpl {
     fn new(input: &DeriveInput) -> syn::Result<Self> {
-        ensure_no_generics(&input.generics, "Metrics")?;
+        ensure_only_type_params(&input.generics, "Metrics")?;
         let Data::Struct(data) = &input.data else {
             let message = "#[derive(Metrics)] can only be placed on structs";
             return Err(syn::Error::new_spanned(input, message));
@@ -258,17 +552,65 @@ impl MetricsImpl {
         Ok(Self {
             attrs,
             name,
+            generics: input.generics.clone(),
             fields,
         })
     }
 
-    fn initialize(&self) -> proc_macro2::TokenStream {
+    fn is_generic(&self) -> bool {
+        !self.generics.params.is_empty()
+    }
+
+    /// Returns generics for the derived impls.
+    ///
+    /// Field types are not added to the where clause since that would prevent normalizing associated types
+    /// (e.g., `BuildMetric::Builder`). Instead, bounds on type params declared for the struct must be sufficient
+    /// for field types to be metrics.
+    fn impl_generics(&self) -> Generics {
+        if !self.is_generic() {
+            return self.generics.clone();
+        }
+
+        let name = &self.name;
+        let (_, ty_generics, _) = self.generics.split_for_impl();
+        let self_predicate: WherePredicate = syn::parse_quote! {
+            #name #ty_generics: 'static + ::core::marker::Send + ::core::marker::Sync
+        };
+        with_predicates(&self.generics, [self_predicate])
+    }
+
+    /// Generates initialization of the group. `parent_switch` is an expression evaluating to the optional
+    /// runtime switch of the enclosing group.
+    fn initialize(&self, parent_switch: &proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let mut needs_switch = false;
         let fields = self.fields.iter().map(|field| {
+            if field.attrs.skip {
+                return field.initialize_with_default();
+            }
+            needs_switch = true;
             let cr = self.attrs.path_to_crate(field.ty.span());
-            field.initialize_default(&cr)
+            if field.attrs.is_nested() {
+                field.initialize_nested(&cr)
+            } else {
+                field.initialize_default(&cr)
+            }
+        });
+        let fields: Vec<_> = fields.collect();
+
+        // The runtime switch is shared by all metrics in the group (and all monomorphizations of a generic group,
+        // since it's keyed by the group name), so it's only looked up once. Metrics in the group are then switched
+        // by the combination of this switch and the switch of the enclosing group (if any).
+        let switch = needs_switch.then(|| {
+            let cr = self.attrs.path_to_crate(proc_macro2::Span::call_site());
+            quote! {
+                static __SWITCH: ::std::sync::OnceLock<&'static #cr::GroupSwitch> =
+                    ::std::sync::OnceLock::new();
+                let __switch = *__SWITCH.get_or_init(|| <Self as #cr::Metrics>::DESCRIPTOR.switch());
+                let __switch = #cr::_private::group_switch(__switch, #parent_switch);
+            }
         });
-
         quote! {
+            #switch
             Self {
                 #(#fields,)*
             }
@@ -281,20 +623,46 @@ impl MetricsImpl {
             let cr = self.attrs.path_to_crate(span);
             quote_spanned!(span=> #cr::validation::assert_metric_prefix(#prefix);)
         });
-        let field_assertions = self.fields.iter().map(|field| {
+        let field_assertions = self.fields.iter().filter(|field| !field.attrs.skip);
+        let field_assertions = field_assertions.map(|field| {
             let field_ty = &field.ty;
             let span = field_ty.span();
             let cr = self.attrs.path_to_crate(span);
-            let type_assertion = quote_spanned! {span=>
-                { struct _AssertIsMetric where #field_ty: #cr::BuildMetric; }
-            };
+            if field.attrs.is_nested() {
+                let type_assertion = (!self.is_generic()).then(|| {
+                    quote_spanned! {span=>
+                        { struct _AssertIsMetrics where #field_ty: #cr::Metrics; }
+                    }
+                });
+                let prefix_assertion = field.attrs.prefix.as_ref().map(|prefix| {
+                    let span = prefix.span();
+                    let cr = self.attrs.path_to_crate(span);
+                    quote_spanned!(span=> #cr::validation::assert_metric_prefix(#prefix);)
+                });
+                return quote!(#type_assertion #prefix_assertion);
+            }
 
-            let field_name = LitStr::new(&field.name.to_string(), field.name.span());
+            let type_assertion = (!self.is_generic()).then(|| {
+                quote_spanned! {span=>
+                    { struct _AssertIsMetric where #field_ty: #cr::BuildMetric; }
+                }
+            });
+
+            let field_name = field
+                .attrs
+                .rename
+                .clone()
+                .unwrap_or_else(|| LitStr::new(&field.name.to_string(), field.name.span()));
             let span = field_name.span();
             let cr = self.attrs.path_to_crate(span);
             let name_assertion =
                 quote_spanned!(span=> #cr::validation::assert_metric_name(#field_name););
-            quote!(#type_assertion #name_assertion)
+            let deprecated_name_assertion = field.attrs.deprecated_name.as_ref().map(|name| {
+                let span = name.span();
+                let cr = self.attrs.path_to_crate(span);
+                quote_spanned!(span=> #cr::validation::assert_metric_name(#name);)
+            });
+            quote!(#type_assertion #name_assertion #deprecated_name_assertion)
         });
         let label_assertions = self.fields.iter().filter_map(|field| {
             let labels = field.attrs.labels.as_ref()?;
@@ -321,8 +689,45 @@ impl MetricsImpl {
             .as_ref()
             .map_or_else(String::new, LitStr::value);
         let prefix = (!prefix.is_empty()).then_some(prefix.as_str());
-        let visit_fields = self.fields.iter().map(|field| field.visit(prefix));
-        let describe_fields = self.fields.iter().map(|field| field.describe(prefix, &cr));
+        let fields: Vec<_> = self
+            .fields
+            .iter()
+            .filter(|field| !field.attrs.skip)
+            .collect();
+        let visit_fields = fields.iter().map(|field| {
+            if field.attrs.is_nested() {
+                field.visit_nested(prefix, &cr)
+            } else {
+                field.visit(prefix, &cr)
+            }
+        });
+        let (nested_fields, metric_fields): (Vec<&MetricsField>, Vec<_>) =
+            fields.iter().partition(|field| field.attrs.is_nested());
+        let describe_fields = metric_fields
+            .iter()
+            .map(|field| field.describe(prefix, &cr));
+        let describe_nested = (!nested_fields.is_empty()).then(|| {
+            let describe_fields = nested_fields
+                .iter()
+                .map(|field| field.describe_nested(prefix, &cr));
+            quote! {
+                fn describe_nested(
+                    prefix: ::core::option::Option<&str>,
+                    descriptors: &mut #cr::_private::NestedDescriptors,
+                ) {
+                    #(#describe_fields;)*
+                }
+            }
+        });
+        let nested_default = (!nested_fields.is_empty() || !metric_fields.is_empty()).then(|| {
+            let initialization =
+                self.initialize(&quote!(::core::option::Option::Some(parent_switch)));
+            quote! {
+                fn nested_default(parent_switch: &'static #cr::GroupSwitch) -> Self {
+                    #initialization
+                }
+            }
+        });
 
         let descriptor = quote_spanned! {name.span()=>
             #cr::descriptors::MetricGroupDescriptor {
@@ -331,17 +736,24 @@ impl MetricsImpl {
                 module_path: ::core::module_path!(),
                 name: ::core::stringify!(#name),
                 line: ::core::line!(),
+                labels: &[],
                 metrics: &[#(#describe_fields,)*],
+                nested_groups: &[],
             }
         };
 
+        let generics = self.impl_generics();
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
         quote! {
-            impl #cr::Metrics for #name {
+            impl #impl_generics #cr::Metrics for #name #ty_generics #where_clause {
                 const DESCRIPTOR: #cr::descriptors::MetricGroupDescriptor = #descriptor;
 
                 fn visit_metrics(&self, visitor: &mut dyn #cr::MetricsVisitor) {
                     #(#visit_fields;)*
                 }
+
+                #describe_nested
+                #nested_default
             }
         }
     }
@@ -349,9 +761,11 @@ impl MetricsImpl {
     fn derive_traits(&self) -> proc_macro2::TokenStream {
         let name = &self.name;
         let validation = self.validate();
-        let initialization = self.initialize();
+        let initialization = self.initialize(&quote!(::core::option::Option::None));
+        let generics = self.impl_generics();
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
         let default_impl = quote! {
-            impl ::core::default::Default for #name {
+            impl #impl_generics ::core::default::Default for #name #ty_generics #where_clause {
                 fn default() -> Self {
                     #initialization
                 }
@@ -1,6 +1,6 @@
 //! Utils shared among multiple derive macros.
 
-use syn::{Attribute, Generics};
+use syn::{Attribute, Expr, GenericParam, Generics, WherePredicate};
 
 pub(crate) trait ParseAttribute: Sized {
     fn parse(raw: &Attribute) -> syn::Result<Self>;
@@ -16,11 +16,46 @@ where
     attrs.map_or_else(|| Ok(T::default()), T::parse)
 }
 
-pub(crate) fn ensure_no_generics(generics: &Generics, derived_macro: &str) -> syn::Result<()> {
-    if generics.params.is_empty() {
-        Ok(())
-    } else {
-        let message = format!("Generics are not supported for `derive({derived_macro})` macro");
-        Err(syn::Error::new_spanned(generics, message))
+pub(crate) fn ensure_only_type_params(generics: &Generics, derived_macro: &str) -> syn::Result<()> {
+    for param in &generics.params {
+        if !matches!(param, GenericParam::Type(_)) {
+            let message = format!(
+                "Lifetimes and const params are not supported for `derive({derived_macro})` macro; \
+                 only type params are supported"
+            );
+            return Err(syn::Error::new_spanned(param, message));
+        }
     }
+    Ok(())
+}
+
+/// Returns a copy of `generics` with the where clause extended with the specified `predicates`.
+pub(crate) fn with_predicates(
+    generics: &Generics,
+    predicates: impl IntoIterator<Item = WherePredicate>,
+) -> Generics {
+    let mut generics = generics.clone();
+    generics.make_where_clause().predicates.extend(predicates);
+    generics
+}
+
+/// Resolves the metric name suffix for a unit specified as a `Unit::Variant` path. Returns `None`
+/// for other expressions since they cannot be evaluated during macro expansion.
+pub(crate) fn unit_suffix(unit: &Expr) -> Option<&'static str> {
+    let Expr::Path(path) = unit else {
+        return None;
+    };
+    let variant = &path.path.segments.last()?.ident;
+    Some(match variant.to_string().as_str() {
+        "Amperes" => "amperes",
+        "Bytes" => "bytes",
+        "Celsius" => "celsius",
+        "Grams" => "grams",
+        "Joules" => "joules",
+        "Meters" => "meters",
+        "Ratios" => "ratios",
+        "Seconds" => "seconds",
+        "Volts" => "volts",
+        _ => return None,
+    })
 }
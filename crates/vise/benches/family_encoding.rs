@@ -0,0 +1,85 @@
+//! Benchmarks encoding large metric families.
+//!
+//! Run with `cargo bench -p vise --bench family_encoding`. When run as a test (e.g., via `cargo test --all-targets`),
+//! benchmarks use small families to check that they work.
+
+use std::{
+    env,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use vise::{
+    Buckets, Counter, EncodeLabelSet, Format, Histogram, LabeledFamily, Metrics, MetricsFamily,
+    Registry,
+};
+
+#[derive(Debug, Metrics)]
+#[metrics(prefix = "bench")]
+struct CounterMetrics {
+    #[metrics(labels = ["method", "shard"])]
+    requests: LabeledFamily<(String, u64), Counter, 2>,
+}
+
+#[derive(Debug, Metrics)]
+#[metrics(prefix = "bench")]
+struct HistogramMetrics {
+    #[metrics(buckets = Buckets::LATENCIES, labels = ["method", "shard"])]
+    latencies: LabeledFamily<(String, u64), Histogram<Duration>, 2>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+struct GroupLabels {
+    method: String,
+    shard: u64,
+}
+
+#[derive(Debug, Metrics)]
+#[metrics(prefix = "bench_group")]
+struct GroupMetrics {
+    requests: Counter,
+}
+
+/// Encodes `registry` `iterations` times and returns the mean time per encoding.
+fn encode(registry: &Registry, iterations: u32) -> Duration {
+    let mut buffer = String::new();
+    registry.encode(&mut buffer, Format::OpenMetrics).unwrap(); // warm-up
+
+    let started_at = Instant::now();
+    for _ in 0..iterations {
+        buffer.clear();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        black_box(&buffer);
+    }
+    started_at.elapsed() / iterations
+}
+
+fn main() {
+    let is_bench = env::args().any(|arg| arg == "--bench");
+    let (series_count, iterations) = if is_bench { (50_000, 20) } else { (100, 1) };
+    let labels = (0..series_count).map(|i| (format!("method_{}", i % 100), i));
+
+    let counter_metrics = CounterMetrics::default();
+    let histogram_metrics = HistogramMetrics::default();
+    let group_metrics = MetricsFamily::<GroupLabels, GroupMetrics>::new();
+    for (method, shard) in labels {
+        counter_metrics.requests[&(method.clone(), shard)].inc();
+        histogram_metrics.latencies[&(method.clone(), shard)].observe(Duration::from_millis(shard));
+        group_metrics[&GroupLabels { method, shard }].requests.inc();
+    }
+
+    let mut registry = Registry::empty();
+    registry.register_metrics(&counter_metrics);
+    let elapsed = encode(&registry, iterations);
+    println!("Family of counters, {series_count} series:      {elapsed:>10.2?} / scrape");
+
+    let mut registry = Registry::empty();
+    registry.register_metrics(&histogram_metrics);
+    let elapsed = encode(&registry, iterations);
+    println!("Family of histograms, {series_count} series:    {elapsed:>10.2?} / scrape");
+
+    let mut registry = Registry::empty();
+    registry.register_metrics(&group_metrics);
+    let elapsed = encode(&registry, iterations);
+    println!("MetricsFamily of counters, {series_count} series: {elapsed:>10.2?} / scrape");
+}
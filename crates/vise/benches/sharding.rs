@@ -0,0 +1,103 @@
+//! Benchmarks comparing contention for regular and sharded metrics updated from multiple threads.
+//!
+//! Run with `cargo bench -p vise --bench sharding`. The number of threads defaults to the available parallelism
+//! and can be overridden with the `BENCH_THREADS` env var. When run as a test (e.g., via `cargo test --all-targets`),
+//! benchmarks use a small number of iterations to check that they work.
+
+use std::{
+    env,
+    hint::black_box,
+    num::NonZeroUsize,
+    sync::Barrier,
+    thread,
+    time::{Duration, Instant},
+};
+
+use vise::{Buckets, Counter, Gauge, Histogram, Metrics};
+
+#[derive(Debug, Metrics)]
+#[metrics(prefix = "bench")]
+struct RegularMetrics {
+    counter: Counter,
+    gauge: Gauge,
+    #[metrics(buckets = Buckets::LATENCIES)]
+    histogram: Histogram<Duration>,
+}
+
+#[derive(Debug, Metrics)]
+#[metrics(prefix = "bench_sharded")]
+struct ShardedMetrics {
+    #[metrics(sharded)]
+    counter: Counter,
+    #[metrics(sharded)]
+    gauge: Gauge,
+    #[metrics(buckets = Buckets::LATENCIES, sharded)]
+    histogram: Histogram<Duration>,
+}
+
+/// Runs `update` in `thread_count` threads, `iterations` times per thread, and returns the mean time per update.
+fn run(thread_count: usize, iterations: u32, update: impl Fn(u32) + Sync) -> Duration {
+    let barrier = Barrier::new(thread_count + 1);
+    let started_at = thread::scope(|scope| {
+        for _ in 0..thread_count {
+            scope.spawn(|| {
+                barrier.wait();
+                for i in 0..iterations {
+                    update(black_box(i));
+                }
+            });
+        }
+        barrier.wait();
+        Instant::now()
+    });
+    let total_updates = u32::try_from(thread_count).unwrap() * iterations;
+    started_at.elapsed() / total_updates
+}
+
+fn bench(name: &str, thread_count: usize, iterations: u32, update: impl Fn(u32) + Sync) {
+    run(thread_count, iterations / 10, &update); // warm-up
+    let per_update = run(thread_count, iterations, &update);
+    println!("{name:<40} {per_update:>10.2?} / update");
+}
+
+fn main() {
+    let is_bench = env::args().any(|arg| arg == "--bench");
+    let iterations = if is_bench { 1_000_000 } else { 100 };
+    let thread_count = env::var("BENCH_THREADS").map_or_else(
+        |_| thread::available_parallelism().map_or(4, NonZeroUsize::get),
+        |threads| threads.parse().expect("invalid `BENCH_THREADS`"),
+    );
+    println!("Updating metrics from {thread_count} threads, {iterations} updates per thread");
+
+    let regular = RegularMetrics::default();
+    let sharded = ShardedMetrics::default();
+
+    bench("Counter::inc", thread_count, iterations, |_| {
+        regular.counter.inc();
+    });
+    bench("Counter::inc (sharded)", thread_count, iterations, |_| {
+        sharded.counter.inc();
+    });
+    bench("Gauge::inc_by", thread_count, iterations, |i| {
+        regular.gauge.inc_by(i.into());
+    });
+    bench("Gauge::inc_by (sharded)", thread_count, iterations, |i| {
+        sharded.gauge.inc_by(i.into());
+    });
+    bench("Histogram::observe", thread_count, iterations, |i| {
+        regular.histogram.observe(Duration::from_micros(i.into()));
+    });
+    bench(
+        "Histogram::observe (sharded)",
+        thread_count,
+        iterations,
+        |i| {
+            sharded.histogram.observe(Duration::from_micros(i.into()));
+        },
+    );
+
+    let total_updates = u64::try_from(thread_count).unwrap() * u64::from(iterations);
+    assert!(regular.counter.get() >= total_updates);
+    assert_eq!(regular.counter.get(), sharded.counter.get());
+    assert_eq!(regular.gauge.get(), sharded.gauge.get());
+}
@@ -0,0 +1,28 @@
+//! Build script capturing build information for the `build_info!` macro.
+
+use std::{env, process::Command};
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // `TARGET` and `PROFILE` are only available to build scripts, so they are re-exported for the library.
+    // Since the library is compiled together with the binary, they apply to the binary as well.
+    for var in ["TARGET", "PROFILE"] {
+        if let Ok(value) = env::var(var) {
+            println!("cargo:rustc-env=VISE_BUILD_{var}={value}");
+        }
+    }
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned());
+    let version = Command::new(rustc).arg("--version").output();
+    let version = version
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    // The output has the `rustc 1.79.0 (129f3b996 2024-06-10)` format.
+    if let Some(version) = version
+        .as_deref()
+        .and_then(|version| version.trim().strip_prefix("rustc "))
+    {
+        println!("cargo:rustc-env=VISE_BUILD_RUSTC_VERSION={version}");
+    }
+}
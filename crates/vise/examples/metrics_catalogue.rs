@@ -0,0 +1,47 @@
+//! Prints the catalogue of all metrics registered in an app.
+//!
+//! The same logic can be embedded into an app binary (e.g., behind a command-line flag)
+//! to generate metrics reference docs in CI. Run as
+//!
+//! ```text
+//! cargo run -p vise --example metrics_catalogue -- [json|markdown]
+//! ```
+
+use std::{env, process, time::Duration};
+
+use vise::{
+    Buckets, CatalogueFormat, Counter, Global, Histogram, Metrics, MetricsCollection, Unit,
+};
+
+#[derive(Debug, Metrics)]
+#[metrics(prefix = "example")]
+struct ExampleMetrics {
+    /// Number of processed requests.
+    requests: Counter,
+    /// Latency of processing a single request.
+    #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
+    request_latency: Histogram<Duration>,
+}
+
+#[vise::register]
+static METRICS: Global<ExampleMetrics> = Global::new();
+
+fn main() {
+    let format = match env::args().nth(1).as_deref() {
+        None | Some("markdown") => CatalogueFormat::Markdown,
+        Some("json") => CatalogueFormat::Json,
+        Some(other) => {
+            eprintln!("Unknown catalogue format `{other}`; expected `json` or `markdown`");
+            process::exit(1);
+        }
+    };
+
+    // Lazy collection doesn't initialize `Global` metrics; descriptors are still collected.
+    let registry = MetricsCollection::lazy().collect();
+    let mut catalogue = String::new();
+    registry
+        .descriptors()
+        .encode_catalogue(&mut catalogue, format)
+        .unwrap();
+    println!("{catalogue}");
+}
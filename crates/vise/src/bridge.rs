@@ -0,0 +1,455 @@
+//! Bridge for the [`metrics`] crate façade.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    error, fmt,
+    sync::{Arc, RwLock},
+};
+
+use metrics::{CounterFn, GaugeFn, HistogramFn, Key, KeyName, Metadata, SharedString};
+use prometheus_client::{
+    encoding::{EncodeMetric, MetricEncoder},
+    metrics::{MetricType, TypedMetric},
+};
+
+use crate::{
+    builder::BuildMetric,
+    descriptors::{MetricGroupDescriptor, Stability},
+    encoding::{EncodeGroupedMetric, GroupedMetric, LabelSetWrapper},
+    names::{counter_name, glob_matches, sanitize_label_name, sanitize_metric_name, unit_for_name},
+    registry::MetricsVisitor,
+    traits::EncodeLabelSet,
+    wrappers::{Counter, Family, Gauge, Histogram},
+    Buckets, Collector, MetricBuilder, Metrics, Unit,
+};
+
+type Labels = Vec<(String, String)>;
+
+/// Error returned by [`MetricsRecorder::install()`].
+#[derive(Debug)]
+pub struct InstallRecorderError(());
+
+impl fmt::Display for InstallRecorderError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .write_str("Cannot install `metrics` recorder: a global recorder is already installed")
+    }
+}
+
+impl error::Error for InstallRecorderError {}
+
+/// Family of dynamically created metrics. Unlike [`Family`], encodes a metric without labels
+/// without an empty label set (i.e., as `name 1` instead of `name{} 1`).
+struct DynamicFamily<M: BuildMetric>(Family<Labels, M>);
+
+impl<M> fmt::Debug for DynamicFamily<M>
+where
+    M: BuildMetric + fmt::Debug,
+    M::Builder: fmt::Debug,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, formatter)
+    }
+}
+
+impl<M: BuildMetric> Clone for DynamicFamily<M> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<M> EncodeMetric for DynamicFamily<M>
+where
+    M: BuildMetric + EncodeMetric + TypedMetric,
+{
+    fn encode(&self, mut encoder: MetricEncoder<'_>) -> fmt::Result {
+        let mut unlabeled_metric = None;
+        for (labels, metric) in self.0.to_entries() {
+            if labels.is_empty() {
+                unlabeled_metric = Some(metric);
+            } else {
+                metric.encode(encoder.encode_family(&LabelSetWrapper(labels))?)?;
+            }
+        }
+        // Encoding an unlabeled metric consumes the encoder, so it's performed last.
+        if let Some(metric) = unlabeled_metric {
+            metric.encode(encoder)?;
+        }
+        Ok(())
+    }
+
+    fn metric_type(&self) -> MetricType {
+        M::TYPE
+    }
+}
+
+impl<M> EncodeGroupedMetric for DynamicFamily<M>
+where
+    M: BuildMetric + EncodeMetric + TypedMetric,
+{
+    fn encode_grouped(
+        &self,
+        group_labels: &dyn EncodeLabelSet,
+        encoder: &mut MetricEncoder<'_>,
+    ) -> fmt::Result {
+        self.0.encode_grouped(group_labels, encoder)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum DynamicMetric {
+    Counter(DynamicFamily<Counter>),
+    Gauge(DynamicFamily<Gauge<f64>>),
+    Histogram(DynamicFamily<Histogram<f64>>),
+}
+
+impl DynamicMetric {
+    fn boxed(&self) -> Box<dyn GroupedMetric> {
+        match self {
+            Self::Counter(family) => Box::new(family.clone()),
+            Self::Gauge(family) => Box::new(family.clone()),
+            Self::Histogram(family) => Box::new(family.clone()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Description {
+    help: &'static str,
+    unit: Option<metrics::Unit>,
+}
+
+/// Recorder for the [`metrics`] crate façade storing metrics in `vise` [`Family`]s.
+///
+/// Metrics reported via `metrics` macros (`counter!`, `gauge!`, `histogram!`) are created dynamically
+/// on the first use. Once the recorder is [installed](Self::install()), these metrics are exported together
+/// with other metrics collected from [`METRICS_REGISTRATIONS`](crate::METRICS_REGISTRATIONS), e.g. by
+/// [`Registry::encode()`](crate::Registry::encode()). Alternatively, the recorder can be registered in a
+/// [`Registry`](crate::Registry) manually, e.g. via a [`Collector`].
+///
+/// # Naming
+///
+/// - Metric and label names are sanitized to conform to Prometheus conventions (e.g., dots
+///   are replaced with underscores).
+/// - The `_total` suffix is stripped from counter names since it's added during encoding.
+/// - [`Seconds`](metrics::Unit::Seconds) and [`Bytes`](metrics::Unit::Bytes) units from metric descriptions
+///   are exported as Prometheus units, unless the metric name already ends with the unit suffix.
+///   Other units are ignored.
+/// - If the same name is used for metrics of different types, only the first registered metric is recorded;
+///   other metrics are no-ops.
+///
+/// # Examples
+///
+/// ```
+/// use vise::{Buckets, MetricsRecorder};
+///
+/// MetricsRecorder::default()
+///     .with_buckets("*_latency", Buckets::LATENCIES)
+///     .with_buckets("*_size", Buckets::exponential(1.0..=1_024.0, 4.0))
+///     .install()
+///     .expect("`metrics` recorder is already installed");
+///
+/// // Metrics reported by dependencies are now exported by `vise`.
+/// metrics::counter!("http.requests", "method" => "GET").increment(1);
+/// metrics::histogram!("http.latency").record(0.025);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MetricsRecorder {
+    buckets: Arc<Vec<(String, Buckets)>>,
+    descriptions: Arc<RwLock<HashMap<String, Description>>>,
+    metrics: Arc<RwLock<BTreeMap<String, DynamicMetric>>>,
+}
+
+impl MetricsRecorder {
+    /// Configures buckets for histograms with the names matching the specified pattern. The pattern
+    /// is matched against the sanitized metric name; it may contain `*` wildcards matching any sequence
+    /// of chars and `?` wildcards matching a single char (same as for [`MetricsFilter`](crate::MetricsFilter)).
+    /// Patterns are checked in the order they are added. If no pattern matches,
+    /// [`Buckets::LATENCIES`] are used.
+    #[must_use]
+    pub fn with_buckets(mut self, pattern: &str, buckets: Buckets) -> Self {
+        Arc::make_mut(&mut self.buckets).push((pattern.to_owned(), buckets));
+        self
+    }
+
+    /// Installs this recorder as the global `metrics` recorder, and registers it in
+    /// [`METRICS_REGISTRATIONS`](crate::METRICS_REGISTRATIONS).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a global `metrics` recorder is already installed.
+    pub fn install(self) -> Result<(), InstallRecorderError> {
+        metrics::set_global_recorder(self.clone()).map_err(|_| InstallRecorderError(()))?;
+        // The collector is only initialized here, after the global recorder is installed, so this cannot fail.
+        RECORDER_COLLECTOR.before_scrape(move || self.clone()).ok();
+        Ok(())
+    }
+
+    fn histogram_buckets(&self, name: &str) -> Buckets {
+        self.buckets
+            .iter()
+            .find_map(|(pattern, buckets)| glob_matches(pattern, name).then_some(*buckets))
+            .unwrap_or(Buckets::LATENCIES)
+    }
+
+    fn describe(&self, name: &str, unit: Option<metrics::Unit>, description: &SharedString) {
+        let name = sanitize_metric_name(name);
+        let mut descriptions = self
+            .descriptions
+            .write()
+            .expect("descriptions are poisoned");
+        let help = match descriptions.get(&name) {
+            Some(prev) if prev.help == &**description => prev.help,
+            // Leaking is fine since the number of described metrics is bounded.
+            _ => Box::leak(description.to_string().into_boxed_str()),
+        };
+        descriptions.insert(name, Description { help, unit });
+    }
+
+    fn get_or_create(&self, name: String, create: impl FnOnce() -> DynamicMetric) -> DynamicMetric {
+        if let Some(metric) = self
+            .metrics
+            .read()
+            .expect("metrics are poisoned")
+            .get(&name)
+        {
+            return metric.clone();
+        }
+        let mut metrics = self.metrics.write().expect("metrics are poisoned");
+        metrics.entry(name).or_insert_with(create).clone()
+    }
+}
+
+impl metrics::Recorder for MetricsRecorder {
+    fn describe_counter(
+        &self,
+        key: KeyName,
+        unit: Option<metrics::Unit>,
+        description: SharedString,
+    ) {
+        self.describe(counter_name(key.as_str()), unit, &description);
+    }
+
+    fn describe_gauge(&self, key: KeyName, unit: Option<metrics::Unit>, description: SharedString) {
+        self.describe(key.as_str(), unit, &description);
+    }
+
+    fn describe_histogram(
+        &self,
+        key: KeyName,
+        unit: Option<metrics::Unit>,
+        description: SharedString,
+    ) {
+        self.describe(key.as_str(), unit, &description);
+    }
+
+    fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> metrics::Counter {
+        let name = sanitize_metric_name(counter_name(key.name()));
+        let metric = self.get_or_create(name, || {
+            DynamicMetric::Counter(DynamicFamily(Family::new(MetricBuilder::new(), ())))
+        });
+        match metric {
+            DynamicMetric::Counter(family) => {
+                let counter = family.0[&labels(key)].clone();
+                metrics::Counter::from_arc(Arc::new(CounterHandle(counter)))
+            }
+            _ => metrics::Counter::noop(),
+        }
+    }
+
+    fn register_gauge(&self, key: &Key, _metadata: &Metadata<'_>) -> metrics::Gauge {
+        let name = sanitize_metric_name(key.name());
+        let metric = self.get_or_create(name, || {
+            DynamicMetric::Gauge(DynamicFamily(Family::new(MetricBuilder::new(), ())))
+        });
+        match metric {
+            DynamicMetric::Gauge(family) => {
+                let gauge = family.0[&labels(key)].clone();
+                metrics::Gauge::from_arc(Arc::new(GaugeHandle(gauge)))
+            }
+            _ => metrics::Gauge::noop(),
+        }
+    }
+
+    fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> metrics::Histogram {
+        let name = sanitize_metric_name(key.name());
+        let buckets = self.histogram_buckets(&name);
+        let metric = self.get_or_create(name, || {
+            let builder = MetricBuilder::new().with_buckets(buckets);
+            DynamicMetric::Histogram(DynamicFamily(Family::new(builder, ())))
+        });
+        match metric {
+            DynamicMetric::Histogram(family) => {
+                let histogram = family.0[&labels(key)].clone();
+                metrics::Histogram::from_arc(Arc::new(HistogramHandle(histogram)))
+            }
+            _ => metrics::Histogram::noop(),
+        }
+    }
+}
+
+impl Metrics for MetricsRecorder {
+    const DESCRIPTOR: MetricGroupDescriptor = MetricGroupDescriptor {
+        crate_name: "vise",
+        crate_version: env!("CARGO_PKG_VERSION"),
+        module_path: module_path!(),
+        name: "MetricsRecorder",
+        line: line!(),
+        labels: &[],
+        metrics: &[],
+        nested_groups: &[],
+    };
+
+    fn visit_metrics(&self, visitor: &mut dyn MetricsVisitor) {
+        let descriptions = self.descriptions.read().expect("descriptions are poisoned");
+        let metrics = self.metrics.read().expect("metrics are poisoned");
+        for (name, metric) in metrics.iter() {
+            let description = descriptions.get(name);
+            let help = description.map_or("", |description| description.help);
+            let unit = description
+                .and_then(|description| description.unit)
+                .and_then(|unit| convert_unit(unit, name));
+            visitor.visit_metric(
+                Cow::Owned(name.clone()),
+                help,
+                unit,
+                Stability::Stable,
+                false,
+                metric.boxed(),
+            );
+        }
+    }
+}
+
+#[crate::register]
+#[metrics(crate = crate)]
+static RECORDER_COLLECTOR: Collector<MetricsRecorder> = Collector::new();
+
+#[derive(Debug)]
+struct CounterHandle(Counter);
+
+impl CounterFn for CounterHandle {
+    fn increment(&self, value: u64) {
+        self.0.inc_by(value);
+    }
+
+    /// Best-effort implementation; concurrent calls may overshoot the target value.
+    fn absolute(&self, value: u64) {
+        let current = self.0.get();
+        if value > current {
+            self.0.inc_by(value - current);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct GaugeHandle(Gauge<f64>);
+
+impl GaugeFn for GaugeHandle {
+    fn increment(&self, value: f64) {
+        self.0.inc_by(value);
+    }
+
+    fn decrement(&self, value: f64) {
+        self.0.dec_by(value);
+    }
+
+    fn set(&self, value: f64) {
+        self.0.set(value);
+    }
+}
+
+#[derive(Debug)]
+struct HistogramHandle(Histogram<f64>);
+
+impl HistogramFn for HistogramHandle {
+    fn record(&self, value: f64) {
+        self.0.observe(value);
+    }
+}
+
+fn labels(key: &Key) -> Labels {
+    let mut labels: Labels = key
+        .labels()
+        .map(|label| {
+            (
+                sanitize_label_name(label.key()).into_owned(),
+                label.value().to_owned(),
+            )
+        })
+        .collect();
+    // Ensure that the same labels in a different order map to the same label set.
+    labels.sort_unstable_by(|(key, _), (other_key, _)| key.cmp(other_key));
+    labels
+}
+
+fn convert_unit(unit: metrics::Unit, name: &str) -> Option<Unit> {
+    let unit = match unit {
+        metrics::Unit::Seconds => Unit::Seconds,
+        metrics::Unit::Bytes => Unit::Bytes,
+        _ => return None,
+    };
+    unit_for_name(unit, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Format, Registry};
+
+    #[test]
+    fn recording_metrics() {
+        let recorder = MetricsRecorder::default()
+            .with_buckets("*_size", Buckets::values(&[10.0, 100.0, 1_000.0]));
+        metrics::with_local_recorder(&recorder, || {
+            metrics::describe_counter!("http.requests", "Number of HTTP requests");
+            metrics::counter!("http.requests", "method" => "GET").increment(2);
+            metrics::counter!("http.requests", "method" => "POST").increment(1);
+            metrics::counter!("http.requests", "method" => "GET").increment(1);
+            metrics::counter!("restarts_total").absolute(5);
+            metrics::counter!("rpc.calls", "method" => "get", "status" => "ok").increment(1);
+            metrics::counter!("rpc.calls", "status" => "ok", "method" => "get").increment(1);
+
+            metrics::describe_gauge!("queue.len", metrics::Unit::Count, "Queue length");
+            let gauge = metrics::gauge!("queue.len");
+            gauge.set(10.0);
+            gauge.decrement(3.0);
+
+            metrics::describe_histogram!("http.latency", metrics::Unit::Seconds, "HTTP latency");
+            metrics::histogram!("http.latency").record(0.02);
+            metrics::histogram!("http.response.size").record(512.0);
+
+            // Conflicting metric type; should be ignored.
+            metrics::gauge!("http.requests").set(100.0);
+        });
+
+        let mut registry = Registry::empty();
+        registry.register_metrics(&recorder);
+        let mut buffer = String::new();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        let lines: Vec<_> = buffer.lines().collect();
+
+        let expected_lines = [
+            "# HELP http_requests Number of HTTP requests.",
+            "# TYPE http_requests counter",
+            r#"http_requests_total{method="GET"} 3"#,
+            r#"http_requests_total{method="POST"} 1"#,
+            "restarts_total 5",
+            r#"rpc_calls_total{method="get",status="ok"} 2"#,
+            "# HELP queue_len Queue length.",
+            "queue_len 7.0",
+            "# UNIT http_latency_seconds seconds",
+            "http_latency_seconds_count 1",
+            r#"http_response_size_bucket{le="1000.0"} 1"#,
+        ];
+        for line in expected_lines {
+            assert!(lines.contains(&line), "{line}: {lines:#?}");
+        }
+        assert!(!lines.contains(&"http_requests 100.0"), "{lines:#?}");
+        let rpc_lines = lines
+            .iter()
+            .filter(|line| line.starts_with("rpc_calls_total"));
+        assert_eq!(rpc_lines.count(), 1, "{lines:#?}");
+    }
+}
@@ -0,0 +1,165 @@
+//! Build information metric.
+
+use crate::EncodeLabelSet;
+
+/// Build information about a binary. Used as labels for the [`Info`](crate::Info) metric defined
+/// by the [`build_info!`](crate::build_info) macro.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+#[metrics(crate = crate)]
+#[non_exhaustive]
+pub struct BuildInfo {
+    /// Name of the package (`CARGO_PKG_NAME`).
+    pub package: &'static str,
+    /// Version of the package (`CARGO_PKG_VERSION`).
+    pub version: &'static str,
+    /// Git commit the binary was built from, if provided via an environment variable during compilation.
+    pub git_commit: Option<&'static str>,
+    /// Target triple, e.g. `x86_64-unknown-linux-gnu`. Omitted if `vise` is built without Cargo.
+    pub target: Option<&'static str>,
+    /// Target CPU architecture, e.g. `x86_64`.
+    pub target_arch: &'static str,
+    /// Target operating system, e.g. `linux`.
+    pub target_os: &'static str,
+    /// Version of the Rust compiler, e.g. `1.79.0 (129f3b996 2024-06-10)`. Omitted if `vise` is built without Cargo.
+    pub rustc_version: Option<&'static str>,
+    /// Build profile (`debug` or `release`) as reported by Cargo; custom profiles are reported as the profile
+    /// they inherit from. If `vise` is built without Cargo, the profile is `debug` if debug assertions
+    /// are enabled, and `release` otherwise.
+    pub profile: &'static str,
+}
+
+impl BuildInfo {
+    #[doc(hidden)] // only used by the `build_info!` macro
+    pub const fn new(
+        package: &'static str,
+        version: &'static str,
+        git_commit: Option<&'static str>,
+        is_debug: bool,
+    ) -> Self {
+        // `TARGET`, `PROFILE` and the compiler version are captured by the build script of this crate.
+        let profile = match option_env!("VISE_BUILD_PROFILE") {
+            Some(profile) => profile,
+            None if is_debug => "debug",
+            None => "release",
+        };
+        Self {
+            package,
+            version,
+            git_commit,
+            target: option_env!("VISE_BUILD_TARGET"),
+            target_arch: std::env::consts::ARCH,
+            target_os: std::env::consts::OS,
+            rustc_version: option_env!("VISE_BUILD_RUSTC_VERSION"),
+            profile,
+        }
+    }
+}
+
+/// Defines a `build_info` [`Info`](crate::Info) metric with [`BuildInfo`] labels and registers it
+/// in [`METRICS_REGISTRATIONS`](crate::METRICS_REGISTRATIONS). (Per OpenMetrics conventions, the metric
+/// is defined with the `build` name, and the `_info` suffix is added during encoding.)
+///
+/// Package info is captured when compiling the crate in which the macro is invoked, so the macro should be called
+/// in the binary crate (and only once per binary). The target, compiler version and build profile are captured
+/// when compiling `vise`, which is built together with the binary.
+///
+/// # Arguments
+///
+/// - `prefix`: prefix for the metric name, e.g., `prefix = "my_app"` results in the `my_app_build_info`
+///   metric. If not specified, the metric is named `build_info`.
+/// - `git_commit`: name of the environment variable with the git commit, read during compilation
+///   (e.g., set by a build script). If not specified, the `GIT_COMMIT` variable is used.
+///   If the variable is not set, the `git_commit` label is omitted.
+///
+/// # Examples
+///
+/// ```
+/// vise::build_info!(prefix = "my_app");
+///
+/// // With a custom env variable for the git commit:
+/// # mod custom {
+/// vise::build_info!(prefix = "other_app", git_commit = "VERGEN_GIT_SHA");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! build_info {
+    () => {
+        $crate::build_info!(@impl []; "GIT_COMMIT");
+    };
+    (prefix = $prefix:literal $(,)?) => {
+        $crate::build_info!(@impl [$prefix]; "GIT_COMMIT");
+    };
+    (git_commit = $git_commit:literal $(,)?) => {
+        $crate::build_info!(@impl []; $git_commit);
+    };
+    (prefix = $prefix:literal, git_commit = $git_commit:literal $(,)?) => {
+        $crate::build_info!(@impl [$prefix]; $git_commit);
+    };
+
+    (@impl [$($prefix:literal)?]; $git_commit:literal) => {
+        const _: () = {
+            #[derive(Debug, $crate::Metrics)]
+            #[metrics(crate = $crate $(, prefix = $prefix)?)]
+            struct BuildInfoMetrics {
+                /// Information about the binary build.
+                build: $crate::Info<$crate::BuildInfo>,
+            }
+
+            static BUILD_INFO: $crate::Global<BuildInfoMetrics> = $crate::Global::new();
+
+            #[$crate::_reexports::ctor]
+            fn register_build_info() {
+                let info = $crate::BuildInfo::new(
+                    ::core::env!("CARGO_PKG_NAME"),
+                    ::core::env!("CARGO_PKG_VERSION"),
+                    ::core::option_env!($git_commit),
+                    ::core::cfg!(debug_assertions),
+                );
+                BUILD_INFO.build.set(info).ok();
+                $crate::METRICS_REGISTRATIONS.push(&BUILD_INFO);
+            }
+        };
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Format, MetricsCollection};
+
+    crate::build_info!(prefix = "test");
+
+    #[test]
+    fn build_info_is_registered() {
+        let registry = MetricsCollection::default()
+            .filter(|group| group.name == "BuildInfoMetrics")
+            .collect();
+        let descriptors = registry.descriptors();
+        let descriptor = descriptors.metric("test_build").unwrap();
+        assert_eq!(descriptor.metric.field_name, "build");
+
+        let mut buffer = String::new();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        let info_line = buffer
+            .lines()
+            .find(|line| line.starts_with("test_build_info{"))
+            .unwrap();
+        let expected_labels = [
+            r#"package="vise""#.to_owned(),
+            format!(r#"version="{}""#, env!("CARGO_PKG_VERSION")),
+            format!(r#"target_os="{}""#, std::env::consts::OS),
+            format!(r#"target="{}""#, env!("VISE_BUILD_TARGET")),
+            "rustc_version=\"".to_owned(),
+            format!(
+                r#"profile="{}""#,
+                if cfg!(debug_assertions) {
+                    "debug"
+                } else {
+                    "release"
+                }
+            ),
+        ];
+        for label in expected_labels {
+            assert!(info_line.contains(&label), "{info_line}");
+        }
+    }
+}
@@ -1,10 +1,11 @@
-use std::hash::Hash;
+use std::{hash::Hash, marker::PhantomData};
 
-use prometheus_client::{encoding::EncodeMetric, metrics::counter::Counter};
+use prometheus_client::encoding::EncodeMetric;
 
 use crate::{
-    traits::{EncodeLabelSet, GaugeValue, HistogramValue},
-    wrappers::{Family, Gauge, Histogram, Info},
+    switch::GroupSwitch,
+    traits::{EncodeLabelSet, GaugeValue, HistogramValue, LabelSetNames, MapLabelNames},
+    wrappers::{Counter, Family, Gauge, Histogram, Info},
     Buckets, Metrics,
 };
 
@@ -15,6 +16,8 @@ use crate::{
 pub struct MetricBuilder<B = (), L = ()> {
     buckets: B,
     labels: L,
+    switch: Option<&'static GroupSwitch>,
+    sharded: bool,
 }
 
 impl Default for MetricBuilder {
@@ -29,6 +32,8 @@ impl MetricBuilder {
         Self {
             buckets: (),
             labels: (),
+            switch: None,
+            sharded: false,
         }
     }
 }
@@ -39,16 +44,57 @@ impl<L> MetricBuilder<(), L> {
         MetricBuilder {
             buckets: buckets.into(),
             labels: self.labels,
+            switch: self.switch,
+            sharded: self.sharded,
         }
     }
 }
 
+impl<L> MetricBuilder<Buckets, L> {
+    #[doc(hidden)] // only used by the proc macros
+    pub fn into_buckets(self) -> Buckets {
+        self.buckets
+    }
+}
+
 impl<B> MetricBuilder<B> {
     /// Configures labels for this builder.
     pub fn with_labels<L>(self, labels: L) -> MetricBuilder<B, L> {
         MetricBuilder {
             buckets: self.buckets,
             labels,
+            switch: self.switch,
+            sharded: self.sharded,
+        }
+    }
+}
+
+impl<B, L> MetricBuilder<B, L> {
+    /// Configures a [runtime switch](GroupSwitch) for this builder. If the switch is turned off,
+    /// updating built metrics is a no-op, and they are not encoded (see [`GroupSwitch`] docs for details).
+    ///
+    /// The [`Metrics`](macro@crate::Metrics) derive macro configures the switch of the metrics group automatically.
+    #[must_use]
+    pub fn with_switch(self, switch: &'static GroupSwitch) -> Self {
+        Self {
+            switch: Some(switch),
+            ..self
+        }
+    }
+
+    /// Configures built [`Counter`]s, [`Gauge`]s and [`Histogram`]s (including ones in a [`Family`])
+    /// to be sharded. A sharded metric is split into cache-padded shards, one per thread slot (the number
+    /// of slots is based on the available parallelism). Each thread updates its own shard, and shards
+    /// are aggregated on reads and encoding. This eliminates contention for metrics updated from many threads
+    /// at the cost of increased memory usage and slower reads.
+    ///
+    /// Sharding is ignored for other metric types. The [`Metrics`](macro@crate::Metrics) derive macro
+    /// enables sharding for fields with the `#[metrics(sharded)]` attribute.
+    #[must_use]
+    pub fn with_sharding(self) -> Self {
+        Self {
+            sharded: true,
+            ..self
         }
     }
 }
@@ -58,26 +104,31 @@ pub trait BuildMetric: 'static + Sized /*+ EncodeMetric + TypedMetric*/ {
     /// Metric builder used to construct a metric.
     type Builder: Copy;
 
+    /// Names of labels for the metric known from its type (e.g., label names of a [`Family`]).
+    /// Used in [metric descriptors](crate::descriptors::MetricDescriptor::labels).
+    const LABELS: &'static [&'static str] = &[];
+
     /// Creates a metric given its builder.
     fn build(builder: Self::Builder) -> Self;
 }
 
 impl<N, A> BuildMetric for Counter<N, A>
 where
-    Counter<N, A>: 'static + EncodeMetric + Default,
+    Counter<N, A>: 'static + EncodeMetric,
+    A: Default,
 {
     type Builder = MetricBuilder;
 
-    fn build(_builder: Self::Builder) -> Self {
-        Self::default()
+    fn build(builder: Self::Builder) -> Self {
+        Self::new(builder.switch, builder.sharded)
     }
 }
 
 impl<V: GaugeValue> BuildMetric for Gauge<V> {
     type Builder = MetricBuilder;
 
-    fn build(_builder: Self::Builder) -> Self {
-        Self::default()
+    fn build(builder: Self::Builder) -> Self {
+        Self::new(builder.switch, builder.sharded)
     }
 }
 
@@ -85,15 +136,15 @@ impl<V: HistogramValue> BuildMetric for Histogram<V> {
     type Builder = MetricBuilder<Buckets>;
 
     fn build(builder: Self::Builder) -> Self {
-        Histogram::new(builder.buckets)
+        Histogram::new(builder.buckets, builder.switch, builder.sharded)
     }
 }
 
 impl<S: 'static + EncodeLabelSet> BuildMetric for Info<S> {
     type Builder = MetricBuilder;
 
-    fn build(_builder: Self::Builder) -> Self {
-        Self::default()
+    fn build(builder: Self::Builder) -> Self {
+        Self::new(builder.switch)
     }
 }
 
@@ -102,20 +153,45 @@ where
     S: 'static + Clone + Eq + Hash,
     M: BuildMetric<Builder = MetricBuilder<B, ()>>,
     B: Copy,
-    L: 'static + Copy,
+    L: 'static + MapLabelNames<S>,
     Family<S, M, L>: EncodeMetric,
 {
     type Builder = MetricBuilder<B, L>;
 
+    const LABELS: &'static [&'static str] = L::LABELS;
+
     fn build(builder: Self::Builder) -> Self {
         let item_builder = MetricBuilder {
             buckets: builder.buckets,
             labels: (),
+            switch: builder.switch,
+            sharded: builder.sharded,
         };
-        Family::new(item_builder, builder.labels)
+        Family::new(item_builder, builder.labels).with_switch(builder.switch)
     }
 }
 
+/// Resolves label names of a metric in the [`Metrics`] derive macro. For a [`Family`] with a label set
+/// implementing [`LabelSetNames`], names are taken from the label set; otherwise, [`BuildMetric::LABELS`]
+/// are used. This allows using manually implemented [`EncodeLabelSet`]s in families.
+#[doc(hidden)] // only used by the proc macro
+#[derive(Debug)]
+pub struct LabelNamesProbe<T>(PhantomData<T>);
+
+// Inherent associated items take precedence over trait ones, but only if the impl bounds are satisfied.
+impl<S: LabelSetNames, M: BuildMetric> LabelNamesProbe<Family<S, M>> {
+    pub const LABELS: &'static [&'static str] = S::LABELS;
+}
+
+#[doc(hidden)] // only used by the proc macro
+pub trait LabelNamesFallback {
+    const LABELS: &'static [&'static str];
+}
+
+impl<T: BuildMetric> LabelNamesFallback for LabelNamesProbe<T> {
+    const LABELS: &'static [&'static str] = T::LABELS;
+}
+
 impl<M: Metrics + Default> BuildMetric for M {
     type Builder = ();
 
@@ -0,0 +1,330 @@
+//! Export of metric descriptors as a human- or machine-readable catalogue.
+
+use std::fmt;
+
+use crate::{
+    descriptors::{MetricDescriptor, MetricGroupDescriptor, Stability},
+    registry::RegisteredDescriptors,
+};
+
+/// Format of a metrics catalogue produced by [`RegisteredDescriptors::encode_catalogue()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CatalogueFormat {
+    /// JSON object with the `groups` array. Each group contains metadata from [`MetricGroupDescriptor`]
+    /// and the `metrics` array with metadata from [`MetricDescriptor`]s.
+    Json,
+    /// Markdown document with a section and a metrics table per metric group. Useful to generate
+    /// reference docs.
+    Markdown,
+}
+
+impl RegisteredDescriptors {
+    /// Encodes descriptors of all registered metrics as a catalogue in the specified format.
+    ///
+    /// Groups are sorted by the crate name, module path and group name, so that the output is
+    /// stable across builds and can be diffed.
+    ///
+    /// # Errors
+    ///
+    /// Proxies formatting errors of the provided `writer`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vise::{CatalogueFormat, Counter, Metrics, Registry};
+    ///
+    /// #[derive(Debug, Metrics)]
+    /// #[metrics(prefix = "my_app")]
+    /// struct AppMetrics {
+    ///     /// Number of processed requests.
+    ///     requests: Counter,
+    /// }
+    ///
+    /// let mut registry = Registry::empty();
+    /// registry.register_metrics(&AppMetrics::default());
+    /// let mut catalogue = String::new();
+    /// registry
+    ///     .descriptors()
+    ///     .encode_catalogue(&mut catalogue, CatalogueFormat::Markdown)?;
+    /// assert!(catalogue.contains("| `my_app_requests` | counter |  |  | Number of processed requests |"));
+    /// # Ok::<_, std::fmt::Error>(())
+    /// ```
+    pub fn encode_catalogue<W: fmt::Write>(
+        &self,
+        writer: &mut W,
+        format: CatalogueFormat,
+    ) -> fmt::Result {
+        let mut groups: Vec<_> = self.groups().collect();
+        groups.sort_by_key(|group| (group.crate_name, group.module_path, group.name, group.line));
+        match format {
+            CatalogueFormat::Json => encode_json(writer, &groups),
+            CatalogueFormat::Markdown => encode_markdown(writer, &groups),
+        }
+    }
+}
+
+fn encode_json(writer: &mut impl fmt::Write, groups: &[&MetricGroupDescriptor]) -> fmt::Result {
+    writer.write_str("{\"groups\":[")?;
+    for (i, group) in groups.iter().enumerate() {
+        if i > 0 {
+            writer.write_char(',')?;
+        }
+        write!(
+            writer,
+            "{{\"crate_name\":{crate_name},\"crate_version\":{crate_version},\
+             \"module_path\":{module_path},\"name\":{name},\"line\":{line},\"labels\":{labels},\
+             \"metrics\":[",
+            crate_name = JsonStr(group.crate_name),
+            crate_version = JsonStr(group.crate_version),
+            module_path = JsonStr(group.module_path),
+            name = JsonStr(group.name),
+            line = group.line,
+            labels = JsonStrArray(group.labels)
+        )?;
+        for (j, metric) in group.metrics.iter().enumerate() {
+            if j > 0 {
+                writer.write_char(',')?;
+            }
+            encode_json_metric(writer, metric)?;
+        }
+        writer.write_str("]}")?;
+    }
+    writer.write_str("]}")
+}
+
+fn encode_json_metric(writer: &mut impl fmt::Write, metric: &MetricDescriptor) -> fmt::Result {
+    write!(
+        writer,
+        "{{\"name\":{name},\"field_name\":{field_name},\"type\":{ty},\"unit\":",
+        name = JsonStr(&metric.full_name()),
+        field_name = JsonStr(metric.field_name),
+        ty = JsonStr(metric.metric_type.as_str())
+    )?;
+    if let Some(unit) = &metric.unit {
+        write!(writer, "{}", JsonStr(unit.as_str()))?;
+    } else {
+        writer.write_str("null")?;
+    }
+    write!(
+        writer,
+        ",\"help\":{help},\"labels\":{labels},\"buckets\":",
+        help = JsonStr(metric.help),
+        labels = JsonStrArray(metric.labels)
+    )?;
+    if let Some(buckets) = &metric.buckets {
+        writer.write_char('[')?;
+        for (i, bound) in buckets.bounds().into_iter().enumerate() {
+            if i > 0 {
+                writer.write_char(',')?;
+            }
+            if bound.is_finite() {
+                write!(writer, "{bound}")?;
+            } else {
+                writer.write_str("null")?;
+            }
+        }
+        writer.write_char(']')?;
+    } else {
+        writer.write_str("null")?;
+    }
+
+    write!(
+        writer,
+        ",\"stability\":{stability},\"deprecated_name\":",
+        stability = JsonStr(metric.stability.as_str())
+    )?;
+    if let Some(name) = metric.full_deprecated_name() {
+        write!(writer, "{}}}", JsonStr(&name))
+    } else {
+        writer.write_str("null}")
+    }
+}
+
+fn encode_markdown(writer: &mut impl fmt::Write, groups: &[&MetricGroupDescriptor]) -> fmt::Result {
+    writer.write_str("# Metrics\n")?;
+    for group in groups {
+        writeln!(
+            writer,
+            "\n## `{module_path}::{name}`\n\n\
+             Defined in crate `{crate_name}` {crate_version} (line {line}).\n",
+            module_path = group.module_path,
+            name = group.name,
+            crate_name = group.crate_name,
+            crate_version = group.crate_version,
+            line = group.line
+        )?;
+        writer.write_str("| Name | Type | Unit | Labels | Description |\n")?;
+        writer.write_str("|:-----|:-----|:-----|:-------|:------------|\n")?;
+        for metric in group.metrics {
+            let labels: Vec<_> = group
+                .labels
+                .iter()
+                .chain(metric.labels)
+                .map(|label| format!("`{label}`"))
+                .collect();
+            writeln!(
+                writer,
+                "| `{name}` | {ty} | {unit} | {labels} | {help} |",
+                name = metric.full_name(),
+                ty = metric.metric_type.as_str(),
+                unit = metric.unit.as_ref().map_or("", |unit| unit.as_str()),
+                labels = labels.join(", "),
+                help = MarkdownHelp(metric)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Displays a string as a quoted JSON string literal.
+struct JsonStr<'a>(&'a str);
+
+impl fmt::Display for JsonStr<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use fmt::Write as _;
+
+        formatter.write_char('"')?;
+        for ch in self.0.chars() {
+            match ch {
+                '"' => formatter.write_str("\\\"")?,
+                '\\' => formatter.write_str("\\\\")?,
+                '\n' => formatter.write_str("\\n")?,
+                '\r' => formatter.write_str("\\r")?,
+                '\t' => formatter.write_str("\\t")?,
+                ch if ch.is_control() => write!(formatter, "\\u{:04x}", u32::from(ch))?,
+                ch => formatter.write_char(ch)?,
+            }
+        }
+        formatter.write_char('"')
+    }
+}
+
+/// Displays a slice of strings as a JSON array.
+struct JsonStrArray<'a>(&'a [&'a str]);
+
+impl fmt::Display for JsonStrArray<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use fmt::Write as _;
+
+        formatter.write_char('[')?;
+        for (i, item) in self.0.iter().enumerate() {
+            if i > 0 {
+                formatter.write_char(',')?;
+            }
+            write!(formatter, "{}", JsonStr(item))?;
+        }
+        formatter.write_char(']')
+    }
+}
+
+/// Displays metric help together with its stability and deprecation notes in a Markdown table cell.
+struct MarkdownHelp<'a>(&'a MetricDescriptor);
+
+impl fmt::Display for MarkdownHelp<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use fmt::Write as _;
+
+        let metric = self.0;
+        let mut notes = vec![];
+        if metric.stability == Stability::Alpha {
+            notes.push("*Alpha.*".to_owned());
+        }
+        if let Some(name) = metric.full_deprecated_name() {
+            notes.push(format!("Deprecated name: `{name}`."));
+        }
+
+        write!(formatter, "{}", MarkdownCell(metric.help))?;
+        let mut needs_space = !metric.help.is_empty();
+        for note in notes {
+            if needs_space {
+                formatter.write_char(' ')?;
+            }
+            formatter.write_str(&note)?;
+            needs_space = true;
+        }
+        Ok(())
+    }
+}
+
+/// Displays a string so that it can be placed in a Markdown table cell.
+struct MarkdownCell<'a>(&'a str);
+
+impl fmt::Display for MarkdownCell<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use fmt::Write as _;
+
+        for ch in self.0.chars() {
+            match ch {
+                '|' => formatter.write_str("\\|")?,
+                '\n' => formatter.write_char(' ')?,
+                ch => formatter.write_char(ch)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::TestMetrics, Registry};
+
+    #[test]
+    fn escaping_json_strings() {
+        let escaped = JsonStr("Multi-line\n\"quoted\" \\ text\u{1}").to_string();
+        assert_eq!(escaped, r#""Multi-line\n\"quoted\" \\ text\u0001""#);
+    }
+
+    #[test]
+    fn encoding_json_catalogue() {
+        let mut registry = Registry::empty();
+        registry.register_metrics(&TestMetrics::default());
+        let mut buffer = String::new();
+        registry
+            .descriptors()
+            .encode_catalogue(&mut buffer, CatalogueFormat::Json)
+            .unwrap();
+
+        assert!(buffer.starts_with(r#"{"groups":[{"crate_name":"vise","crate_version":"#));
+        let expected_fragments = [
+            r#""module_path":"vise::tests","name":"TestMetrics""#,
+            r#","labels":[],"metrics":["#,
+            r#"{"name":"test_counter","field_name":"counter","type":"counter","unit":null,"help":"Test counter","labels":[],"buckets":null,"stability":"stable","deprecated_name":null}"#,
+            r#"{"name":"test_gauge_bytes","field_name":"gauge","type":"gauge","unit":"bytes","help":"","labels":[],"buckets":null,"stability":"stable","deprecated_name":null}"#,
+            r#""name":"test_histogram","field_name":"histogram","type":"histogram","unit":null,"help":"Histogram with inline bucket specification","labels":[],"buckets":[0.001,0.002,0.005,0.01,0.1],"stability":"stable""#,
+            r#""name":"test_histograms_with_buckets","#,
+            r#""labels":["method"],"buckets":[0.1,0.2,0.3,0.4,0.5,0.6,0.7,0.8,0.9],"stability""#,
+        ];
+        for fragment in expected_fragments {
+            assert!(buffer.contains(fragment), "{buffer}");
+        }
+        assert!(buffer.ends_with("]}]}"), "{buffer}");
+    }
+
+    #[test]
+    fn encoding_markdown_catalogue() {
+        let mut registry = Registry::empty();
+        registry.register_metrics(&TestMetrics::default());
+        let mut buffer = String::new();
+        registry
+            .descriptors()
+            .encode_catalogue(&mut buffer, CatalogueFormat::Markdown)
+            .unwrap();
+        let lines: Vec<_> = buffer.lines().collect();
+
+        let expected_lines = [
+            "# Metrics",
+            "## `vise::tests::TestMetrics`",
+            "| `test_counter` | counter |  |  | Test counter |",
+            "| `test_gauge_bytes` | gauge | bytes |  |  |",
+            "| `test_family_of_gauges` | gauge |  | `method` | Test family of gauges |",
+            "| `test_family_of_histograms_seconds` | histogram | seconds | `method` | A family of histograms \
+             with a multiline description. Note that we use a type alias to properly propagate \
+             bucket configuration |",
+        ];
+        for line in expected_lines {
+            assert!(lines.contains(&line), "{lines:#?}");
+        }
+    }
+}
@@ -5,7 +5,8 @@ use prometheus_client::{collector::Collector as CollectorTrait, encoding::Descri
 
 use crate::{
     descriptors::MetricGroupDescriptor,
-    registry::{CollectToRegistry, MetricsEncoder, Registry},
+    registry::{CollectToRegistry, GroupContext, MetricsEncoder, RegistrationError, Registry},
+    switch::GroupSwitch,
     Metrics,
 };
 
@@ -80,15 +81,27 @@ impl<M: Metrics> Collector<M> {
     }
 }
 
+impl<M: Metrics> Collector<M> {
+    fn encode_with_context(
+        &self,
+        encoder: DescriptorEncoder<'_>,
+        context: &GroupContext,
+    ) -> fmt::Result {
+        let Some(hook) = self.inner.get() else {
+            return Ok(());
+        };
+        // The hook is only invoked if the group is selected by the filter.
+        let Some(mut visitor) = MetricsEncoder::new(encoder, context) else {
+            return Ok(());
+        };
+        hook().visit_metrics(&mut visitor);
+        visitor.check()
+    }
+}
+
 impl<M: Metrics> CollectorTrait for &'static Collector<M> {
     fn encode(&self, encoder: DescriptorEncoder<'_>) -> fmt::Result {
-        if let Some(hook) = self.inner.get() {
-            let mut visitor = MetricsEncoder::from(encoder);
-            hook().visit_metrics(&mut visitor);
-            visitor.check()
-        } else {
-            Ok(())
-        }
+        self.encode_with_context(encoder, &GroupContext::new(&M::DESCRIPTOR))
     }
 }
 
@@ -97,14 +110,58 @@ impl<M: Metrics> CollectToRegistry for Collector<M> {
         &M::DESCRIPTOR
     }
 
-    fn collect_to_registry(&'static self, registry: &mut Registry) {
-        registry.register_collector(self);
+    fn collect_to_registry(
+        &'static self,
+        registry: &mut Registry,
+    ) -> Result<(), RegistrationError> {
+        registry.try_register_collector(self)
+    }
+}
+
+/// [`Collector`] registered in a [`Registry`] together with the group context (e.g., the registry's visibility filter).
+pub(crate) struct RegisteredCollector<M: Metrics> {
+    collector: &'static Collector<M>,
+    switch: &'static GroupSwitch,
+    context: GroupContext,
+}
+
+impl<M: Metrics> fmt::Debug for RegisteredCollector<M> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("RegisteredCollector")
+            .field("collector", self.collector)
+            .field("switch", self.switch)
+            .field("context", &self.context)
+            .finish()
+    }
+}
+
+impl<M: Metrics> RegisteredCollector<M> {
+    pub(crate) fn new(collector: &'static Collector<M>, context: GroupContext) -> Self {
+        Self {
+            collector,
+            switch: M::DESCRIPTOR.switch(),
+            context,
+        }
+    }
+}
+
+impl<M: Metrics> CollectorTrait for RegisteredCollector<M> {
+    fn encode(&self, encoder: DescriptorEncoder<'_>) -> fmt::Result {
+        if !self.switch.is_enabled() {
+            return Ok(());
+        }
+        self.collector.encode_with_context(encoder, &self.context)
     }
 }
 
 /// Lazy collector of `Global` metrics. Only exports metrics once they are initialized;
 /// does not initialize metrics on its own.
-pub(crate) struct LazyGlobalCollector<M: Metrics>(&'static Lazy<M>);
+pub(crate) struct LazyGlobalCollector<M: Metrics> {
+    metrics: &'static Lazy<M>,
+    switch: &'static GroupSwitch,
+    context: GroupContext,
+}
 
 impl<M: Metrics> fmt::Debug for LazyGlobalCollector<M> {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -115,20 +172,28 @@ impl<M: Metrics> fmt::Debug for LazyGlobalCollector<M> {
 }
 
 impl<M: Metrics> LazyGlobalCollector<M> {
-    pub(crate) fn new(metrics: &'static Lazy<M>) -> Self {
-        Self(metrics)
+    pub(crate) fn new(metrics: &'static Lazy<M>, context: GroupContext) -> Self {
+        Self {
+            metrics,
+            switch: M::DESCRIPTOR.switch(),
+            context,
+        }
     }
 }
 
 impl<M: Metrics> CollectorTrait for LazyGlobalCollector<M> {
     fn encode(&self, encoder: DescriptorEncoder<'_>) -> fmt::Result {
-        if let Some(metrics) = Lazy::get(self.0) {
-            let mut visitor = MetricsEncoder::from(encoder);
-            metrics.visit_metrics(&mut visitor);
-            visitor.check()
-        } else {
-            Ok(())
+        if !self.switch.is_enabled() {
+            return Ok(());
         }
+        let Some(metrics) = Lazy::get(self.metrics) else {
+            return Ok(());
+        };
+        let Some(mut visitor) = MetricsEncoder::new(encoder, &self.context) else {
+            return Ok(());
+        };
+        metrics.visit_metrics(&mut visitor);
+        visitor.check()
     }
 }
 
@@ -1,9 +1,67 @@
 //! Metric descriptors.
 
+use std::fmt;
+
 use prometheus_client::{metrics::MetricType, registry::Unit};
 
+use crate::Buckets;
+
+/// Descriptor of [`Buckets`] for a [`Histogram`](crate::Histogram) or a [`Family`](crate::Family) of histograms.
+///
+/// Buckets are evaluated lazily since they can be specified using non-constant expressions.
+#[derive(Clone, Copy)]
+pub struct BucketsDescriptor(fn() -> Buckets);
+
+impl fmt::Debug for BucketsDescriptor {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_tuple("BucketsDescriptor")
+            .field(&self.bounds())
+            .finish()
+    }
+}
+
+impl BucketsDescriptor {
+    #[doc(hidden)] // only used by the proc macros
+    pub const fn new(buckets_fn: fn() -> Buckets) -> Self {
+        Self(buckets_fn)
+    }
+
+    /// Returns the described buckets.
+    pub fn buckets(&self) -> Buckets {
+        (self.0)()
+    }
+
+    /// Returns upper bounds of the described buckets in the increasing order. The implicit `+Inf` bucket
+    /// is not included.
+    pub fn bounds(&self) -> Vec<f64> {
+        self.buckets().iter().collect()
+    }
+}
+
+/// Stability level of a metric.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Stability {
+    /// Experimental metric that can be changed or removed without a migration path.
+    Alpha,
+    /// Stable metric. This is the default stability level.
+    #[default]
+    Stable,
+}
+
+impl Stability {
+    /// Returns the string presentation of this level as used in the `stability` attribute.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Alpha => "alpha",
+            Self::Stable => "stable",
+        }
+    }
+}
+
 /// Descriptor for a single metric.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MetricDescriptor {
     /// Name of the metric **excluding** the unit suffix.
     pub name: &'static str,
@@ -15,7 +73,20 @@ pub struct MetricDescriptor {
     pub unit: Option<Unit>,
     /// Help for the metrics exported to Prometheus.
     pub help: &'static str,
-    // TODO: labels?
+    /// Names of labels for a [`Family`](crate::Family) or [`LabeledFamily`](crate::LabeledFamily)
+    /// in the order they are encoded. Empty for other metric types, or if label names cannot be determined
+    /// statically (e.g., if a label set is a `Vec`).
+    ///
+    /// Does not include [group labels](MetricGroupDescriptor::labels); use [`FullMetricDescriptor::label_names()`]
+    /// to get all labels.
+    pub labels: &'static [&'static str],
+    /// Buckets for a [`Histogram`](crate::Histogram) or a [`Family`](crate::Family) of histograms.
+    pub buckets: Option<BucketsDescriptor>,
+    /// Stability level of the metric.
+    pub stability: Stability,
+    /// Deprecated name of the metric **excluding** the unit suffix. If set, the metric is additionally
+    /// exported under this name.
+    pub deprecated_name: Option<&'static str>,
 }
 
 impl MetricDescriptor {
@@ -26,6 +97,28 @@ impl MetricDescriptor {
             self.name.to_owned()
         }
     }
+
+    /// Returns the deprecated name including the unit suffix.
+    pub(crate) fn full_deprecated_name(&self) -> Option<String> {
+        let name = self.deprecated_name?;
+        Some(if let Some(unit) = &self.unit {
+            format!("{name}_{}", unit.as_str())
+        } else {
+            name.to_owned()
+        })
+    }
+
+    /// Creates a copy of this descriptor with the name and the deprecated name (if any) prefixed by `prefix`.
+    /// Prefixed names are leaked.
+    pub(crate) fn leak_with_prefix(&self, prefix: &str) -> Self {
+        let prefixed =
+            |name: &str| -> &'static str { Box::leak(format!("{prefix}_{name}").into_boxed_str()) };
+        Self {
+            name: prefixed(self.name),
+            deprecated_name: self.deprecated_name.map(prefixed),
+            ..self.clone()
+        }
+    }
 }
 
 /// Descriptor for a group of metrics (i.e., a struct implementing [`Metrics`](crate::Metrics)).
@@ -41,8 +134,43 @@ pub struct MetricGroupDescriptor {
     pub name: &'static str,
     /// Source code line on which the group is defined.
     pub line: u32,
-    /// Descriptors of all metrics defined in the group.
+    /// Names of labels shared by all metrics in the group, such as labels of a [`MetricsFamily`](crate::MetricsFamily).
+    /// These labels are encoded before the labels of a specific metric.
+    pub labels: &'static [&'static str],
+    /// Descriptors of all metrics defined in the group. For a group obtained from [`Metrics::DESCRIPTOR`](crate::Metrics::DESCRIPTOR),
+    /// this excludes metrics in nested groups (i.e., fields with the `flatten` or `prefix` attribute); these metrics
+    /// are merged into the group descriptor on registration.
     pub metrics: &'static [MetricDescriptor],
+    /// Descriptors of groups nested into this group, including transitively nested ones. Similar to [`Self::metrics`],
+    /// this is empty for a group obtained from [`Metrics::DESCRIPTOR`](crate::Metrics::DESCRIPTOR) and is filled in
+    /// on registration. Nested groups have their own [runtime switches](crate::GroupSwitch).
+    pub nested_groups: &'static [&'static MetricGroupDescriptor],
+}
+
+impl MetricGroupDescriptor {
+    /// Creates a copy of this descriptor with metrics from nested groups appended to [`Self::metrics`],
+    /// and the nested groups themselves set as [`Self::nested_groups`]. The copy is leaked, which is fine
+    /// since it is only created once per group type.
+    pub(crate) fn leak_with_nested(
+        &self,
+        metrics: Vec<MetricDescriptor>,
+        nested_groups: Vec<&'static Self>,
+    ) -> &'static Self {
+        let metrics: Vec<_> = self.metrics.iter().cloned().chain(metrics).collect();
+        Box::leak(Box::new(Self {
+            metrics: Box::leak(metrics.into_boxed_slice()),
+            nested_groups: Box::leak(nested_groups.into_boxed_slice()),
+            ..*self
+        }))
+    }
+
+    /// Checks whether this and `other` descriptors correspond to the same group (i.e., have the same
+    /// crate name, module path and name, similar to [`GroupSwitch`](crate::GroupSwitch)es).
+    pub(crate) fn is_same_group(&self, other: &Self) -> bool {
+        self.crate_name == other.crate_name
+            && self.module_path == other.module_path
+            && self.name == other.name
+    }
 }
 
 /// A metric descriptor together with a descriptor for a group in which the metric is defined.
@@ -61,6 +189,11 @@ impl FullMetricDescriptor {
     ) -> Self {
         Self { group, metric }
     }
+
+    /// Iterates over names of all labels for the metric, including group labels.
+    pub fn label_names(&self) -> impl Iterator<Item = &'static str> {
+        self.group.labels.iter().chain(self.metric.labels).copied()
+    }
 }
 
 #[cfg(test)]
@@ -70,7 +203,20 @@ mod tests {
     use assert_matches::assert_matches;
 
     use super::*;
-    use crate::{metrics::Metrics, tests::TestMetrics};
+    use crate::{
+        tests::TestMetrics, traits::LabelSetNames, CollectToRegistry, Counter, DurationAsSecs,
+        EncodeLabelSet, EncodeLabelValue, LabeledFamily, Metrics, MetricsFamily, Registry,
+    };
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
+    #[metrics(crate = crate, label = "method")]
+    struct Method(&'static str);
+
+    impl fmt::Display for Method {
+        fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str(self.0)
+        }
+    }
 
     #[test]
     fn describing_metrics() {
@@ -97,5 +243,63 @@ mod tests {
             histogram_descriptor.help,
             "Histogram with inline bucket specification"
         );
+        assert!(histogram_descriptor.labels.is_empty());
+        let buckets = histogram_descriptor.buckets.unwrap();
+        assert_eq!(buckets.bounds(), [0.001, 0.002, 0.005, 0.01, 0.1]);
+
+        assert_eq!(metric_descriptors["family_of_gauges"].labels, ["method"]);
+        assert!(metric_descriptors["family_of_gauges"].buckets.is_none());
+        let family_descriptor = metric_descriptors["family_of_histograms"];
+        assert_eq!(family_descriptor.labels, ["method"]);
+        assert_eq!(
+            family_descriptor.buckets.unwrap().bounds(),
+            [0.001, 0.005, 0.025, 0.1, 0.25, 1.0, 5.0, 30.0, 120.0]
+        );
+        assert!(metric_descriptors["package_metadata"].labels.is_empty());
+    }
+
+    #[test]
+    fn describing_label_sets() {
+        #[derive(Debug, EncodeLabelSet)]
+        #[metrics(crate = crate)]
+        struct InfoLabels {
+            version: &'static str,
+            #[metrics(unit = Unit::Seconds)]
+            timeout: DurationAsSecs,
+            commit: Option<&'static str>,
+        }
+
+        assert_eq!(InfoLabels::LABELS, ["version", "timeout_seconds", "commit"]);
+        assert_eq!(Method::LABELS, ["method"]);
+        assert_eq!(<&Method>::LABELS, ["method"]);
+        assert!(<Vec<(&str, &str)>>::LABELS.is_empty());
+    }
+
+    #[test]
+    fn describing_metrics_family() {
+        #[derive(Debug, Metrics)]
+        #[metrics(crate = crate, prefix = "grouped")]
+        struct GroupedMetrics {
+            #[metrics(labels = ["code"])]
+            return_codes: LabeledFamily<u16, Counter>,
+        }
+
+        type Group = MetricsFamily<Method, GroupedMetrics>;
+
+        static GROUP: Group = MetricsFamily::new();
+
+        let descriptor = <Group as Metrics>::DESCRIPTOR;
+        assert_eq!(descriptor.name, "GroupedMetrics");
+        assert_eq!(descriptor.labels, ["method"]);
+        assert!(GroupedMetrics::DESCRIPTOR.labels.is_empty());
+
+        let mut registry = Registry::empty();
+        GROUP.collect_to_registry(&mut registry).unwrap();
+        let full_descriptor = registry
+            .descriptors()
+            .metric("grouped_return_codes")
+            .unwrap();
+        let label_names: Vec<_> = full_descriptor.label_names().collect();
+        assert_eq!(label_names, ["method", "code"]);
     }
 }
@@ -1,12 +1,15 @@
-use std::{collections::HashMap, fmt, sync::Arc};
+use std::{borrow::Cow, collections::HashMap, fmt, sync::Arc, time::Duration};
 
 use prometheus_client::{
-    encoding::{EncodeMetric, LabelSetEncoder, MetricEncoder},
+    encoding::{
+        EncodeLabelKey, EncodeLabelValue, EncodeMetric, LabelKeyEncoder, LabelSetEncoder,
+        LabelValueEncoder, MetricEncoder,
+    },
     metrics::MetricType,
     registry::{Metric, Unit},
 };
 
-use crate::{traits::EncodeLabelSet, MetricsVisitor};
+use crate::{descriptors::Stability, traits::EncodeLabelSet, MetricsVisitor};
 
 /// Wraps a label set so that it can be used in the `prometheus_client` library.
 #[derive(Debug)]
@@ -18,6 +21,45 @@ impl<S: EncodeLabelSet> prometheus_client::encoding::EncodeLabelSet for LabelSet
     }
 }
 
+/// Label with a unit suffix implementing [`EncodeLabelKey`].
+#[doc(hidden)] // used in proc macros only
+#[derive(Debug)]
+pub struct LabelWithUnit {
+    name: &'static str,
+    unit: Unit,
+}
+
+impl LabelWithUnit {
+    pub const fn new(name: &'static str, unit: Unit) -> Self {
+        Self { name, unit }
+    }
+}
+
+impl EncodeLabelKey for LabelWithUnit {
+    fn encode(&self, encoder: &mut LabelKeyEncoder<'_>) -> fmt::Result {
+        use std::fmt::Write as _;
+
+        write!(encoder, "{}_{}", self.name, self.unit.as_str())
+    }
+}
+
+/// Wraps a [`Duration`] so that it can be used as a label value, which will be set to the fractional
+/// number of seconds in the duration, i.e. [`Duration::as_secs_f64()`]. Mostly useful for [`Info`](crate::Info) metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DurationAsSecs(pub Duration);
+
+impl From<Duration> for DurationAsSecs {
+    fn from(duration: Duration) -> Self {
+        Self(duration)
+    }
+}
+
+impl EncodeLabelValue for DurationAsSecs {
+    fn encode(&self, encoder: &mut LabelValueEncoder) -> fmt::Result {
+        EncodeLabelValue::encode(&self.0.as_secs_f64(), encoder)
+    }
+}
+
 #[derive(Default)]
 struct LabeledMetric(Vec<(Arc<dyn EncodeLabelSet>, Box<dyn GroupedMetric>)>);
 
@@ -59,12 +101,18 @@ impl EncodeGroupedMetric for LabeledMetric {
         }
         Ok(())
     }
+
+    fn is_enabled(&self) -> bool {
+        self.0.iter().any(|(_, metric)| metric.is_enabled())
+    }
 }
 
 #[derive(Debug)]
 struct MetricsGroup {
     help: &'static str,
     unit: Option<Unit>,
+    stability: Stability,
+    is_deprecated: bool,
     instances: LabeledMetric,
 }
 
@@ -73,7 +121,7 @@ struct MetricsGroup {
 #[derive(Default)]
 pub(crate) struct LabelGroups {
     labels: Option<Arc<dyn EncodeLabelSet>>,
-    metrics_by_name: HashMap<&'static str, MetricsGroup>,
+    metrics_by_name: HashMap<Cow<'static, str>, MetricsGroup>,
 }
 
 impl fmt::Debug for LabelGroups {
@@ -88,9 +136,11 @@ impl fmt::Debug for LabelGroups {
 impl MetricsVisitor for LabelGroups {
     fn visit_metric(
         &mut self,
-        name: &'static str,
+        name: Cow<'static, str>,
         help: &'static str,
         unit: Option<Unit>,
+        stability: Stability,
+        is_deprecated: bool,
         metric: Box<dyn GroupedMetric>,
     ) {
         let metric_entry = self
@@ -99,6 +149,8 @@ impl MetricsVisitor for LabelGroups {
             .or_insert_with(|| MetricsGroup {
                 help,
                 unit,
+                stability,
+                is_deprecated,
                 instances: LabeledMetric::default(),
             });
         let current_labels = self
@@ -120,6 +172,8 @@ impl LabelGroups {
                 name,
                 grouped_metric.help,
                 grouped_metric.unit,
+                grouped_metric.stability,
+                grouped_metric.is_deprecated,
                 Box::new(grouped_metric.instances),
             );
         }
@@ -172,6 +226,13 @@ pub trait EncodeGroupedMetric: EncodeMetric {
         let labels = LabelSetWrapper(labels);
         self.encode(encoder.encode_family(&labels)?)
     }
+
+    /// Checks whether the metric is enabled by its [runtime switch](crate::GroupSwitch). Disabled metrics
+    /// are not encoded.
+    #[doc(hidden)] // implementation detail
+    fn is_enabled(&self) -> bool {
+        true
+    }
 }
 
 /// [`EncodeGroupedMetric`] with additional constraints, such as `Send`, `Sync` and `'static` lifetime.
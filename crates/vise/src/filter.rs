@@ -0,0 +1,197 @@
+//! Scrape-time filtering of metrics.
+
+use std::{cell::RefCell, sync::Arc};
+
+use crate::{descriptors::MetricGroupDescriptor, names::glob_matches};
+
+thread_local! {
+    /// Filter applied by the [`Registry::encode_filtered()`](crate::Registry::encode_filtered()) call
+    /// in progress on the current thread, if any. Collectors are invoked by `prometheus_client`, which doesn't allow
+    /// passing custom state to them, so the filter is handed over to them via this thread-local;
+    /// after that, it's passed explicitly in the [`MetricsEncoder`](crate::registry::MetricsEncoder).
+    static ACTIVE_FILTER: RefCell<Option<MetricsFilter>> = const { RefCell::new(None) };
+}
+
+/// Filter selecting metrics to be [encoded](crate::Registry::encode_filtered()) from a [`Registry`](crate::Registry).
+///
+/// Unlike [`MetricsCollection::filter()`](crate::MetricsCollection::filter()), which is applied once when
+/// collecting metrics into a registry, this filter is applied on each scrape, e.g. to debug a single subsystem
+/// without downloading all metrics. A filter can select metrics by the following criteria:
+///
+/// - [Name patterns](Self::with_name()) matched against the full exported metric names
+/// - [Crate names](Self::with_crate()) and [module paths](Self::with_module()) from the [`MetricGroupDescriptor`]
+///   of the group containing the metric
+///
+/// A metric is selected if it satisfies all configured criteria. A criterion with several values
+/// is satisfied if any of the values matches. An empty filter selects all metrics.
+///
+/// # Examples
+///
+/// ```
+/// use vise::{Counter, Format, Metrics, MetricsFilter, Registry};
+///
+/// #[derive(Debug, Metrics)]
+/// #[metrics(prefix = "my_app")]
+/// struct AppMetrics {
+///     requests: Counter,
+///     errors: Counter,
+/// }
+///
+/// let mut registry = Registry::empty();
+/// registry.register_metrics(&AppMetrics::default());
+///
+/// let filter = MetricsFilter::default().with_name("my_app_req*");
+/// let mut buffer = String::new();
+/// registry.encode_filtered(&mut buffer, Format::OpenMetrics, &filter)?;
+/// assert!(buffer.contains("my_app_requests_total 0"));
+/// assert!(!buffer.contains("my_app_errors"));
+/// # Ok::<_, std::fmt::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MetricsFilter {
+    // Shared, so that the filter can be cheaply cloned when encoding metrics.
+    inner: Arc<FilterCriteria>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct FilterCriteria {
+    names: Vec<String>,
+    crates: Vec<String>,
+    modules: Vec<String>,
+}
+
+impl MetricsFilter {
+    /// Adds a pattern for full metric names (i.e., names reported to Prometheus, excluding suffixes
+    /// like `_total` or `_bucket`). In the pattern, `*` matches any sequence of chars, and `?` matches
+    /// a single char.
+    #[must_use]
+    pub fn with_name(mut self, pattern: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.inner).names.push(pattern.into());
+        self
+    }
+
+    /// Adds a crate name that metric groups may be [defined in](MetricGroupDescriptor::crate_name).
+    /// Note that crate names use underscores rather than hyphens (e.g., `my_crate`).
+    #[must_use]
+    pub fn with_crate(mut self, crate_name: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.inner)
+            .crates
+            .push(crate_name.into());
+        self
+    }
+
+    /// Adds a module path that metric groups may be [defined in](MetricGroupDescriptor::module_path),
+    /// e.g. `my_app::metrics`. Groups defined in submodules of the module are selected as well.
+    #[must_use]
+    pub fn with_module(mut self, module_path: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.inner)
+            .modules
+            .push(module_path.into());
+        self
+    }
+
+    /// Checks whether this filter selects all metrics.
+    pub fn is_empty(&self) -> bool {
+        let criteria = &*self.inner;
+        criteria.names.is_empty() && criteria.crates.is_empty() && criteria.modules.is_empty()
+    }
+
+    /// Checks whether metrics in the specified group may be selected by this filter.
+    pub fn matches_group(&self, group: &MetricGroupDescriptor) -> bool {
+        let criteria = &*self.inner;
+        let crate_matches = criteria.crates.is_empty()
+            || criteria.crates.iter().any(|name| name == group.crate_name);
+        let module_matches = criteria.modules.is_empty()
+            || criteria.modules.iter().any(|module| {
+                group
+                    .module_path
+                    .strip_prefix(module.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            });
+        crate_matches && module_matches
+    }
+
+    /// Checks whether the specified full metric name is selected by this filter.
+    pub fn matches_name(&self, name: &str) -> bool {
+        let names = &self.inner.names;
+        names.is_empty() || names.iter().any(|pattern| glob_matches(pattern, name))
+    }
+
+    /// Checks whether a metric with the specified name and prefix added by the registry is selected by this filter.
+    pub(crate) fn matches_prefixed_name(&self, prefix: Option<&str>, name: &str) -> bool {
+        if self.inner.names.is_empty() {
+            return true;
+        }
+        match prefix {
+            Some(prefix) => self.matches_name(&format!("{prefix}_{name}")),
+            None => self.matches_name(name),
+        }
+    }
+}
+
+/// Sets the active filter for the current thread until dropped. An empty filter is set as well, so that
+/// a nested encoding (e.g., from a [`Collector`](crate::Collector) hook) doesn't inherit the outer filter.
+pub(crate) struct ActiveFilterGuard {
+    prev_filter: Option<MetricsFilter>,
+}
+
+impl ActiveFilterGuard {
+    pub(crate) fn new(filter: &MetricsFilter) -> Self {
+        let filter = (!filter.is_empty()).then(|| filter.clone());
+        let prev_filter = ACTIVE_FILTER.with(|active| active.replace(filter));
+        Self { prev_filter }
+    }
+}
+
+impl Drop for ActiveFilterGuard {
+    fn drop(&mut self) {
+        ACTIVE_FILTER.with(|active| *active.borrow_mut() = self.prev_filter.take());
+    }
+}
+
+/// Returns the filter active on the current thread, if any.
+pub(crate) fn active_filter() -> Option<MetricsFilter> {
+    ACTIVE_FILTER.with(|active| active.borrow().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching_groups() {
+        const GROUP: MetricGroupDescriptor = MetricGroupDescriptor {
+            crate_name: "my_app",
+            crate_version: "0.1.0",
+            module_path: "my_app::api::metrics",
+            name: "ApiMetrics",
+            line: 1,
+            labels: &[],
+            metrics: &[],
+            nested_groups: &[],
+        };
+
+        assert!(MetricsFilter::default().matches_group(&GROUP));
+        assert!(MetricsFilter::default()
+            .with_crate("other")
+            .with_crate("my_app")
+            .matches_group(&GROUP));
+        assert!(!MetricsFilter::default()
+            .with_crate("other")
+            .matches_group(&GROUP));
+
+        for module in ["my_app", "my_app::api", "my_app::api::metrics"] {
+            let filter = MetricsFilter::default().with_module(module);
+            assert!(filter.matches_group(&GROUP), "{module}");
+        }
+        for module in ["my_ap", "my_app::ap", "other"] {
+            let filter = MetricsFilter::default().with_module(module);
+            assert!(!filter.matches_group(&GROUP), "{module}");
+        }
+
+        let filter = MetricsFilter::default()
+            .with_crate("my_app")
+            .with_module("my_app::other");
+        assert!(!filter.matches_group(&GROUP));
+    }
+}
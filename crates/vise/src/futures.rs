@@ -0,0 +1,318 @@
+//! Instrumentation for futures and streams.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+
+use crate::{Buckets, Counter, Histogram, Metrics, Unit};
+
+/// Buckets for the time spent polling a future. Unlike wall latency, polling normally takes
+/// microseconds to milliseconds.
+const POLL_BUCKETS: Buckets = Buckets::exponential(1e-6..=1.0, 10.0);
+
+/// Metrics for [instrumented](InstrumentFuture) futures.
+///
+/// Normally, this group is nested into another metrics group using the `prefix` field attribute
+/// so that metric names are distinguishable; see the [`InstrumentFuture`] docs for an example.
+#[derive(Debug, Metrics)]
+#[metrics(crate = crate)]
+pub struct FutureMetrics {
+    /// Wall-clock latency from the first poll until completion.
+    #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
+    pub latency: Histogram<Duration>,
+    /// Total time spent inside `poll` until completion.
+    #[metrics(buckets = POLL_BUCKETS, unit = Unit::Seconds)]
+    pub poll_latency: Histogram<Duration>,
+    /// Number of polls.
+    pub polls: Counter,
+    /// Number of futures dropped before completion. Futures that were never polled are not counted.
+    pub cancellations: Counter,
+}
+
+/// Metrics for [instrumented](InstrumentStream) streams. Besides [`FutureMetrics`] (which are recorded
+/// for the entire stream lifetime, i.e., until the stream is exhausted), counts produced items.
+#[derive(Debug, Metrics)]
+#[metrics(crate = crate)]
+pub struct StreamMetrics {
+    /// Metrics for the stream lifetime.
+    #[metrics(flatten)]
+    pub lifetime: FutureMetrics,
+    /// Number of items produced by streams.
+    pub items: Counter,
+}
+
+/// Polling state shared by instrumented futures and streams.
+#[derive(Debug)]
+struct PollState<'a> {
+    metrics: &'a FutureMetrics,
+    started_at: Option<Instant>,
+    busy_time: Duration,
+    is_completed: bool,
+}
+
+impl<'a> PollState<'a> {
+    fn new(metrics: &'a FutureMetrics) -> Self {
+        Self {
+            metrics,
+            started_at: None,
+            busy_time: Duration::ZERO,
+            is_completed: false,
+        }
+    }
+
+    fn poll<T>(&mut self, poll: impl FnOnce() -> Poll<T>) -> Poll<T> {
+        let poll_start = Instant::now();
+        self.started_at.get_or_insert(poll_start);
+        let output = poll();
+        self.busy_time += poll_start.elapsed();
+        self.metrics.polls.inc();
+        output
+    }
+
+    fn complete(&mut self) {
+        if self.is_completed {
+            return;
+        }
+        self.is_completed = true;
+        if let Some(started_at) = self.started_at {
+            self.metrics.latency.observe(started_at.elapsed());
+        }
+        self.metrics.poll_latency.observe(self.busy_time);
+    }
+}
+
+impl Drop for PollState<'_> {
+    fn drop(&mut self) {
+        // A future that was never polled didn't start executing, so it's not considered cancelled.
+        if !self.is_completed && self.started_at.is_some() {
+            self.metrics.cancellations.inc();
+        }
+    }
+}
+
+/// Extension trait instrumenting [`Future`]s with [`FutureMetrics`].
+///
+/// Unlike [`LatencyObserver`](crate::LatencyObserver), an instrumented future doesn't lose information
+/// if it's dropped before completion; such futures are counted as cancellations (unless they were never polled).
+///
+/// # Examples
+///
+/// ```
+/// use vise::{FutureMetrics, Global, InstrumentFuture, Metrics};
+///
+/// #[derive(Debug, Metrics)]
+/// #[metrics(prefix = "app")]
+/// struct AppMetrics {
+///     /// Metrics exported as `app_sync_latency_seconds`, `app_sync_polls_total` etc.
+///     #[metrics(prefix = "sync")]
+///     sync: FutureMetrics,
+/// }
+///
+/// static METRICS: Global<AppMetrics> = Global::new();
+///
+/// async fn sync() {
+///     // Do some work...
+/// }
+///
+/// # async fn test() {
+/// sync().with_metrics(&METRICS.sync).await;
+/// # }
+/// ```
+pub trait InstrumentFuture: Future + Sized {
+    /// Instruments this future with the provided metrics.
+    fn with_metrics(self, metrics: &FutureMetrics) -> InstrumentedFuture<'_, Self> {
+        InstrumentedFuture {
+            inner: self,
+            state: PollState::new(metrics),
+        }
+    }
+}
+
+impl<F: Future> InstrumentFuture for F {}
+
+pin_project! {
+    /// Future instrumented with [`FutureMetrics`]. Returned by [`InstrumentFuture::with_metrics()`].
+    #[derive(Debug)]
+    #[must_use = "futures do nothing unless polled"]
+    pub struct InstrumentedFuture<'a, F> {
+        #[pin]
+        inner: F,
+        state: PollState<'a>,
+    }
+}
+
+impl<F: Future> Future for InstrumentedFuture<'_, F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let inner = this.inner;
+        let poll = this.state.poll(|| inner.poll(cx));
+        if poll.is_ready() {
+            this.state.complete();
+        }
+        poll
+    }
+}
+
+/// Extension trait instrumenting [`Stream`]s with [`StreamMetrics`]. A stream is considered completed
+/// once it's exhausted; dropping a polled stream before that is counted as a cancellation.
+pub trait InstrumentStream: Stream + Sized {
+    /// Instruments this stream with the provided metrics.
+    fn with_metrics(self, metrics: &StreamMetrics) -> InstrumentedStream<'_, Self> {
+        InstrumentedStream {
+            inner: self,
+            items: &metrics.items,
+            state: PollState::new(&metrics.lifetime),
+        }
+    }
+}
+
+impl<S: Stream> InstrumentStream for S {}
+
+pin_project! {
+    /// Stream instrumented with [`StreamMetrics`]. Returned by [`InstrumentStream::with_metrics()`].
+    #[derive(Debug)]
+    #[must_use = "streams do nothing unless polled"]
+    pub struct InstrumentedStream<'a, S> {
+        #[pin]
+        inner: S,
+        items: &'a Counter,
+        state: PollState<'a>,
+    }
+}
+
+impl<S: Stream> Stream for InstrumentedStream<'_, S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let inner = this.inner;
+        let poll = this.state.poll(|| inner.poll_next(cx));
+        match &poll {
+            Poll::Ready(Some(_)) => {
+                this.items.inc();
+            }
+            Poll::Ready(None) => this.state.complete(),
+            Poll::Pending => { /* do nothing */ }
+        }
+        poll
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future;
+
+    use super::*;
+    use crate::{Format, Registry};
+
+    #[derive(Debug, Metrics)]
+    #[metrics(crate = crate, prefix = "test")]
+    struct TestMetrics {
+        #[metrics(prefix = "call")]
+        call: FutureMetrics,
+        #[metrics(prefix = "blocks")]
+        blocks: StreamMetrics,
+    }
+
+    #[derive(Debug)]
+    struct IterStream<I>(I);
+
+    impl<I: Iterator + Unpin> Stream for IterStream<I> {
+        type Item = I::Item;
+
+        fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<I::Item>> {
+            Poll::Ready(self.0.next())
+        }
+    }
+
+    #[tokio::test]
+    async fn instrumenting_future() {
+        let metrics = TestMetrics::default();
+        let output = async {
+            tokio::task::yield_now().await;
+            tokio::task::yield_now().await;
+            42
+        }
+        .with_metrics(&metrics.call)
+        .await;
+        assert_eq!(output, 42);
+
+        assert_eq!(metrics.call.polls.get(), 3);
+        assert_eq!(metrics.call.cancellations.get(), 0);
+
+        tokio::select! {
+            biased;
+            () = future::pending::<()>().with_metrics(&metrics.call) => unreachable!(),
+            () = future::ready(()) => { /* cancels the first future */ }
+        }
+        assert_eq!(metrics.call.polls.get(), 4);
+        assert_eq!(metrics.call.cancellations.get(), 1);
+
+        // Dropping a future that was never polled is not a cancellation.
+        drop(future::pending::<()>().with_metrics(&metrics.call));
+        assert_eq!(metrics.call.cancellations.get(), 1);
+
+        let mut registry = Registry::empty();
+        registry.register_metrics(&metrics);
+        let mut buffer = String::new();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        let lines: Vec<_> = buffer.lines().collect();
+        assert!(
+            lines.contains(&"test_call_latency_seconds_count 1"),
+            "{lines:#?}"
+        );
+        assert!(lines.contains(&"test_call_poll_latency_seconds_count 1"));
+        assert!(lines.contains(&"test_call_polls_total 4"));
+        assert!(lines.contains(&"test_call_cancellations_total 1"));
+    }
+
+    #[tokio::test]
+    async fn instrumenting_stream() {
+        let metrics = TestMetrics::default();
+        let mut stream = IterStream(1..=3).with_metrics(&metrics.blocks);
+        assert_eq!(stream.size_hint(), (0, None));
+        let mut items = vec![];
+        while let Some(item) = future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+            items.push(item);
+        }
+        assert_eq!(items, [1, 2, 3]);
+        drop(stream);
+
+        assert_eq!(metrics.blocks.items.get(), 3);
+        assert_eq!(metrics.blocks.lifetime.polls.get(), 4);
+        assert_eq!(metrics.blocks.lifetime.cancellations.get(), 0);
+
+        let mut stream = IterStream(1..).with_metrics(&metrics.blocks);
+        future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await;
+        drop(stream);
+        assert_eq!(metrics.blocks.items.get(), 4);
+        assert_eq!(metrics.blocks.lifetime.cancellations.get(), 1);
+
+        drop(IterStream(1..).with_metrics(&metrics.blocks));
+        assert_eq!(metrics.blocks.lifetime.cancellations.get(), 1);
+
+        let mut registry = Registry::empty();
+        registry.register_metrics(&metrics);
+        let mut buffer = String::new();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        let lines: Vec<_> = buffer.lines().collect();
+        assert!(
+            lines.contains(&"test_blocks_latency_seconds_count 1"),
+            "{lines:#?}"
+        );
+        assert!(lines.contains(&"test_blocks_items_total 4"));
+        assert!(lines.contains(&"test_blocks_cancellations_total 1"));
+    }
+}
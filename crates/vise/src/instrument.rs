@@ -0,0 +1,319 @@
+//! Runtime support for the [`instrument`](macro@crate::instrument) attribute macro.
+
+use std::{
+    fmt,
+    hash::Hash,
+    marker::PhantomData,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    builder::BuildMetric, wrappers::Family, Counter, EncodeLabelSet, EncodeLabelValue, Histogram,
+};
+
+/// Outcome of an instrumented function call. Can be used as a label set in a [`Family`] of latency histograms
+/// recorded by the [`instrument`](macro@crate::instrument) macro; it's encoded as the `outcome` label.
+///
+/// A call returning `Result` or `Option` is classified based on the variant of the returned value. Calls
+/// returning other types are always classified as [`Self::Ok`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
+#[metrics(crate = crate, rename_all = "snake_case", label = "outcome")]
+pub enum Outcome {
+    /// The call returned `Ok(_)` or `Some(_)`.
+    Ok,
+    /// The call returned `Err(_)`.
+    Error,
+    /// The call returned `None`.
+    None,
+}
+
+/// Guard recording metrics for an instrumented sync call. The call is finished either explicitly
+/// with the returned value, or implicitly on drop if the function exits in a way not rewritten
+/// by the macro (e.g., a `return` inside a macro invocation). In the latter case, the output is unknown.
+#[doc(hidden)] // only used by the proc macro
+#[must_use = "Guard should be finished with the function output"]
+pub struct CallGuard<T, F: FnOnce(Option<&T>, Duration)> {
+    start: Instant,
+    on_finish: Option<F>,
+    _output: PhantomData<fn(&T)>,
+}
+
+impl<T, F: FnOnce(Option<&T>, Duration)> CallGuard<T, F> {
+    pub fn new(on_finish: F) -> Self {
+        Self {
+            start: Instant::now(),
+            on_finish: Some(on_finish),
+            _output: PhantomData,
+        }
+    }
+
+    pub fn finish(mut self, output: T) -> T {
+        if let Some(on_finish) = self.on_finish.take() {
+            on_finish(Some(&output), self.start.elapsed());
+        }
+        output
+    }
+}
+
+impl<T, F: FnOnce(Option<&T>, Duration)> fmt::Debug for CallGuard<T, F> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("CallGuard")
+            .field("start", &self.start)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T, F: FnOnce(Option<&T>, Duration)> Drop for CallGuard<T, F> {
+    fn drop(&mut self) {
+        // Panicking calls are not recorded, same as for async functions.
+        if thread::panicking() {
+            return;
+        }
+        if let Some(on_finish) = self.on_finish.take() {
+            on_finish(None, self.start.elapsed());
+        }
+    }
+}
+
+/// Metric that can be used to record latency of instrumented calls.
+#[doc(hidden)] // only used by the proc macro
+pub trait ObserveLatency {
+    fn observe_latency(&self, latency: Duration, outcome: Outcome);
+}
+
+impl ObserveLatency for Histogram<Duration> {
+    fn observe_latency(&self, latency: Duration, _outcome: Outcome) {
+        self.observe(latency);
+    }
+}
+
+impl<L> ObserveLatency for Family<Outcome, Histogram<Duration>, L> {
+    fn observe_latency(&self, latency: Duration, outcome: Outcome) {
+        self[&outcome].observe(latency);
+    }
+}
+
+/// Metric that can be used to count errors in instrumented calls.
+#[doc(hidden)] // only used by the proc macro
+pub trait RecordError<E: ?Sized> {
+    fn record_error(&self, err: &E);
+
+    /// Records an error that cannot be accessed (e.g., returned from a macro).
+    fn record_unknown_error(&self);
+}
+
+impl<E: ?Sized> RecordError<E> for Counter {
+    fn record_error(&self, _err: &E) {
+        self.inc();
+    }
+
+    fn record_unknown_error(&self) {
+        self.inc();
+    }
+}
+
+/// Output of an instrumented function that can signal an error.
+#[doc(hidden)] // only used by the proc macro
+pub trait FallibleOutput {
+    type Error: ?Sized;
+}
+
+impl<T, E> FallibleOutput for Result<T, E> {
+    type Error = E;
+}
+
+impl<T> FallibleOutput for Option<T> {
+    type Error = ();
+}
+
+/// Records an error for a call with an unknown output. The output is only used for type inference.
+#[doc(hidden)] // only used by the proc macro
+pub fn record_unknown_error<O, M>(metric: &M, _output: Option<&O>)
+where
+    O: FallibleOutput,
+    M: RecordError<O::Error>,
+{
+    metric.record_unknown_error();
+}
+
+/// Uses the error as the label value.
+impl<E, L> RecordError<E> for Family<E, Counter, L>
+where
+    E: Clone + Eq + Hash,
+    Counter: BuildMetric,
+{
+    fn record_error(&self, err: &E) {
+        self[err].inc();
+    }
+
+    fn record_unknown_error(&self) {
+        // Do nothing since there's no label value for the error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Buckets, Format, Global, LabeledFamily, Metrics, Registry};
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelValue)]
+    #[metrics(crate = crate, rename_all = "snake_case")]
+    enum TestError {
+        Timeout,
+        Internal,
+    }
+
+    #[derive(Debug, Metrics)]
+    #[metrics(crate = crate, prefix = "instrumented")]
+    struct TestMetrics {
+        #[metrics(buckets = Buckets::LATENCIES)]
+        latency: Family<Outcome, Histogram<Duration>>,
+        #[metrics(buckets = Buckets::LATENCIES)]
+        plain_latency: Histogram<Duration>,
+        #[metrics(labels = ["error"])]
+        errors: LabeledFamily<TestError, Counter>,
+        missing_values: Counter,
+    }
+
+    static METRICS: Global<TestMetrics> = Global::new();
+
+    fn encode_metrics(metrics: &TestMetrics) -> Vec<String> {
+        let mut registry = Registry::empty();
+        registry.register_metrics(metrics);
+        let mut buffer = String::new();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        buffer.lines().map(str::to_owned).collect()
+    }
+
+    #[crate::instrument(crate = crate, metrics = METRICS, latency = latency, errors = errors)]
+    fn fallible(fail: bool) -> Result<u64, TestError> {
+        if fail {
+            return Err(TestError::Timeout);
+        }
+        let value: u64 = "42".parse().map_err(|_| TestError::Internal)?;
+        Ok(value)
+    }
+
+    #[crate::instrument(crate = crate, metrics = METRICS, errors = missing_values)]
+    fn lookup(key: &str) -> Option<usize> {
+        key.find('=')
+    }
+
+    struct Service {
+        metrics: TestMetrics,
+    }
+
+    impl Service {
+        #[crate::instrument(crate = crate, metrics = self.metrics, latency = plain_latency)]
+        fn call(&self, value: u64) -> u64 {
+            value * 2
+        }
+
+        #[crate::instrument(crate = crate, metrics = self.metrics, latency = latency, errors = errors)]
+        async fn call_async(&self, fail: bool) -> Result<(), TestError> {
+            tokio::task::yield_now().await;
+            if fail {
+                Err(TestError::Internal)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    macro_rules! bail {
+        ($err:expr) => {
+            return Err($err)
+        };
+    }
+
+    struct Storage {
+        metrics: TestMetrics,
+        entries: Vec<String>,
+    }
+
+    impl Storage {
+        #[crate::instrument(crate = crate, metrics = self.metrics, latency = plain_latency, errors = missing_values)]
+        fn get(&self, idx: usize) -> Option<&str> {
+            let entry = self.entries.get(idx)?;
+            Some(entry)
+        }
+
+        #[crate::instrument(crate = crate, metrics = self.metrics, latency = latency, errors = errors)]
+        fn push(&mut self, entry: &str) -> Result<usize, TestError> {
+            let is_valid = |entry: &str| {
+                if entry.is_empty() {
+                    return false;
+                }
+                entry.is_ascii()
+            };
+            if !is_valid(entry) {
+                bail!(TestError::Internal);
+            }
+            if self.entries.len() >= 2 {
+                return Err(TestError::Timeout);
+            }
+            self.entries.push(entry.to_owned());
+            Ok(self.entries.len())
+        }
+    }
+
+    #[test]
+    fn instrumenting_functions_with_early_returns() {
+        let mut storage = Storage {
+            metrics: TestMetrics::default(),
+            entries: vec![],
+        };
+        assert_eq!(storage.push("first"), Ok(1));
+        assert_eq!(storage.push(""), Err(TestError::Internal));
+        assert_eq!(storage.push("second"), Ok(2));
+        assert_eq!(storage.push("third"), Err(TestError::Timeout));
+
+        assert_eq!(storage.get(1), Some("second"));
+        assert_eq!(storage.get(2), None);
+
+        // The error returned from a macro has unknown value, so it's not counted in the labeled family.
+        assert!(!storage.metrics.errors.contains(&TestError::Internal));
+        assert_eq!(storage.metrics.errors[&TestError::Timeout].get(), 1);
+        assert_eq!(storage.metrics.missing_values.get(), 1);
+        let lines = encode_metrics(&storage.metrics);
+        assert!(lines.contains(&r#"instrumented_latency_count{outcome="ok"} 2"#.to_owned()));
+        assert!(lines.contains(&r#"instrumented_latency_count{outcome="error"} 2"#.to_owned()));
+        assert!(lines.contains(&"instrumented_plain_latency_count 2".to_owned()));
+    }
+
+    #[test]
+    fn instrumenting_sync_functions() {
+        assert_eq!(fallible(false), Ok(42));
+        assert_eq!(fallible(true), Err(TestError::Timeout));
+        assert_eq!(fallible(true), Err(TestError::Timeout));
+
+        assert_eq!(METRICS.errors[&TestError::Timeout].get(), 2);
+        assert!(!METRICS.errors.contains(&TestError::Internal));
+        let lines = encode_metrics(&METRICS);
+        assert!(lines.contains(&r#"instrumented_latency_count{outcome="ok"} 1"#.to_owned()));
+        assert!(lines.contains(&r#"instrumented_latency_count{outcome="error"} 2"#.to_owned()));
+
+        assert_eq!(lookup("a=b"), Some(1));
+        assert_eq!(lookup("ab"), None);
+        assert_eq!(METRICS.missing_values.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn instrumenting_methods() {
+        let service = Service {
+            metrics: TestMetrics::default(),
+        };
+        assert_eq!(service.call(21), 42);
+
+        service.call_async(false).await.unwrap();
+        service.call_async(true).await.unwrap_err();
+        service.call_async(true).await.unwrap_err();
+        assert_eq!(service.metrics.errors[&TestError::Internal].get(), 2);
+        let lines = encode_metrics(&service.metrics);
+        assert!(lines.contains(&"instrumented_plain_latency_count 1".to_owned()));
+        assert!(lines.contains(&r#"instrumented_latency_count{outcome="ok"} 1"#.to_owned()));
+        assert!(lines.contains(&r#"instrumented_latency_count{outcome="error"} 2"#.to_owned()));
+    }
+}
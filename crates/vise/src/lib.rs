@@ -16,7 +16,29 @@
 //!   metric data in the OpenMetrics text format. Registration can be automated using the [`register`]
 //!   attribute, but it can be manual as well.
 //! - In order to allow for metrics computed during scraping, you can use [`Collector`].
+//! - Build information about the binary can be exported using the [`build_info!`] macro.
+//! - Metrics reported via the [`metrics`](https://docs.rs/metrics/) crate façade can be exported
+//!   together with `vise` metrics using `MetricsRecorder` (requires the `metrics-bridge` crate feature).
+//! - Instruments created via the [OpenTelemetry](https://docs.rs/opentelemetry/) metrics API can be exported
+//!   using `OtelMeterProvider` (requires the `opentelemetry-bridge` crate feature).
+//! - Durations of `tracing` spans and the number of `tracing` events can be exported using `MetricsLayer`
+//!   (requires the `tracing-layer` crate feature).
+//! - Standard HTTP metrics for `tower` services can be collected using `HttpMetricsLayer`
+//!   (requires the `tower-middleware` crate feature).
+//! - Futures and streams can be instrumented with [`FutureMetrics`] / [`StreamMetrics`]
+//!   using [`InstrumentFuture`] and [`InstrumentStream`] extension traits.
+//! - Message queues can be instrumented with [`ChannelMetrics`] using channel wrappers from the [`mpsc`] module.
+//! - Metric groups can be enabled or disabled at runtime using [`GroupSwitch`]es.
+//! - Metrics can be selected at scrape time by name, crate or module using [`MetricsFilter`].
+//! - Counters, gauges and histograms updated from many threads can be [sharded](MetricBuilder::with_sharding())
+//!   to avoid contention.
 //! - To share one or more labels for a group of metrics, wrap them in a [`MetricsFamily`].
+//! - Descriptors of registered metrics can be exported as a JSON or Markdown catalogue
+//!   using [`RegisteredDescriptors::encode_catalogue()`].
+//! - Metric definitions can be checked against Prometheus naming conventions using the [`lint`] module.
+//! - All instrumentation can be compiled out by enabling the `noop` crate feature in the final binary.
+//!   With it, metric types retain their APIs, but are zero-sized, updating them is a no-op,
+//!   and [`Registry::encode()`] produces an empty output.
 //!
 //! # Examples
 //!
@@ -116,12 +138,109 @@
 
 // Documentation settings.
 #![doc(html_root_url = "https://docs.rs/vise/0.3.2")]
+#![cfg_attr(docsrs, feature(doc_cfg))]
 // Linter settings.
 #![warn(missing_debug_implementations, missing_docs, bare_trait_objects)]
 #![warn(clippy::all, clippy::pedantic)]
 #![allow(clippy::must_use_candidate, clippy::module_name_repetitions)]
 
-pub use prometheus_client::{metrics::counter::Counter, registry::Unit};
+pub use prometheus_client::registry::Unit;
+/// Instruments a function, recording its latency and / or errors into metrics.
+///
+/// The macro can be placed on sync or async functions (including methods). The function body is executed
+/// as is; once it completes, its latency and outcome are recorded into the specified metrics.
+/// For sync functions, `return` expressions and `?` operators in the body are rewritten to observe the returned value.
+/// The function may also exit via a `return` inside a macro (e.g., `anyhow::bail!`); in this case, the returned value
+/// is unknown, and the call is classified as `Error` or `None` for `Result` and `Option` outputs respectively.
+/// An unknown error is counted by a [`Counter`], but not by a [`Family`] of counters. Panicking calls are not recorded.
+///
+/// The `metrics` expression is borrowed for the duration of a sync call, so it must not conflict with borrows
+/// in the function body (e.g., a method taking `&mut self` cannot mutate `self.metrics`).
+///
+/// # Attributes
+///
+/// ## `metrics`
+///
+/// **Type:** path to a metrics group, e.g., a [`Global`] static
+///
+/// Metrics group containing the metrics referenced in other attributes. Required.
+///
+/// ## `latency`
+///
+/// **Type:** field name in the metrics group
+///
+/// Metric recording the call latency. Can be either a [`Histogram`]`<Duration>`, or a [`Family`] of
+/// such histograms with [`Outcome`] labels.
+///
+/// ## `errors`
+///
+/// **Type:** field name in the metrics group
+///
+/// Metric counting failed calls, i.e., calls returning `Err(_)` or `None`. Can be either a [`Counter`],
+/// or a [`Family`] of counters with the error type as labels (for `Result`s). In the latter case,
+/// the error type can implement [`EncodeLabelValue`] and be used
+/// in a [`LabeledFamily`] so that the label is derived from the error.
+///
+/// At least one of `latency` or `errors` attributes must be specified.
+///
+/// # Outcome classification
+///
+/// The outcome of a call is determined from the *syntactic* return type of the function:
+///
+/// - `Option<_>` is classified by its variant.
+/// - A type named `Result` (e.g., `Result<_, _>`, `io::Result<_>` or `anyhow::Result<_>`) is classified by its variant;
+///   the type must be a `std::result::Result` (possibly via a type alias).
+/// - Other types (including type aliases with other names, like `RpcResult<_>`) are always classified
+///   as [`Outcome::Ok`].
+///   The `errors` attribute cannot be used for such functions.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use vise::{
+///     Buckets, Counter, EncodeLabelValue, Family, Global, Histogram, LabeledFamily, Metrics, Outcome,
+/// };
+///
+/// #[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelValue)]
+/// #[metrics(rename_all = "snake_case")]
+/// enum RpcError {
+///     NotFound,
+///     Internal,
+/// }
+///
+/// #[derive(Debug, Metrics)]
+/// #[metrics(prefix = "rpc")]
+/// struct RpcMetrics {
+///     /// Latency of RPC calls.
+///     #[metrics(buckets = Buckets::LATENCIES)]
+///     latency: Family<Outcome, Histogram<Duration>>,
+///     /// Number of RPC errors.
+///     #[metrics(labels = ["error"])]
+///     errors: LabeledFamily<RpcError, Counter>,
+/// }
+///
+/// static METRICS: Global<RpcMetrics> = Global::new();
+///
+/// #[vise::instrument(metrics = METRICS, latency = latency, errors = errors)]
+/// fn get_block(number: u64) -> Result<String, RpcError> {
+///     if number > 100 {
+///         return Err(RpcError::NotFound);
+///     }
+///     Ok(format!("block #{number}"))
+/// }
+///
+/// #[vise::instrument(metrics = METRICS, latency = latency)]
+/// async fn get_transaction(hash: &str) -> Option<String> {
+///     hash.starts_with("0x").then(|| hash.to_owned())
+/// }
+///
+/// get_block(1).unwrap();
+/// get_block(1_000).unwrap_err();
+/// assert_eq!(METRICS.errors[&RpcError::NotFound].get(), 1);
+/// assert!(METRICS.latency.contains(&Outcome::Error));
+/// ```
+pub use vise_macros::instrument;
 /// Registers a [`Global`] metrics instance or [`Collector`], so that it will be included
 /// into registries instantiated using [`MetricsCollection`].
 ///
@@ -161,7 +280,8 @@ pub use prometheus_client::{metrics::counter::Counter, registry::Unit};
 pub use vise_macros::register;
 /// Derives the [`EncodeLabelSet`] trait for a type, which encodes a set of metric labels.
 ///
-/// The type for which the trait is derived must not have generics (lifetimes, type params or const params).
+/// The type for which the trait is derived may have type params, but not lifetimes or const params.
+/// For generic types, the derived impl requires all field types to implement [`EncodeLabelValue`].
 /// The macro can be configured using `#[metrics()]` attributes.
 ///
 /// # Container attributes
@@ -197,6 +317,20 @@ pub use vise_macros::register;
 /// Specifies unit of measurement for a label. The unit will be added to the label name as a suffix
 /// (e.g., `timeout_seconds` if placed on a field named `timeout`). This is mostly useful for [`Info`] metrics.
 ///
+/// ## `flatten`
+///
+/// **Type:** flag
+///
+/// Specifies that the field is a label set itself (i.e., implements [`EncodeLabelSet`]), and all its labels
+/// should be encoded in place of the field. Label names from flattened sets are checked for collisions
+/// at compile time. Cannot be combined with other attributes.
+///
+/// # Label names
+///
+/// The derived implementation records label names in [`LabelSetNames::LABELS`](crate::traits::LabelSetNames::LABELS),
+/// which are then used in [metric descriptors](crate::descriptors). Unit suffixes are only recorded
+/// if the unit is specified as a path to a [`Unit`] variant, e.g. `Unit::Seconds`.
+///
 /// # Examples
 ///
 /// ## Set with a single label
@@ -251,10 +385,31 @@ pub use vise_macros::register;
 /// // will be exported as the following labels:
 /// // { version="0.1.0", request_timeout_seconds="0.1", buffer_capacity="1024" }
 /// ```
+///
+/// ## Flattened label sets
+///
+/// ```
+/// # use vise::EncodeLabelSet;
+/// #[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+/// struct RpcContext {
+///     method: &'static str,
+///     network: &'static str,
+/// }
+///
+/// #[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+/// struct RpcErrorLabels {
+///     #[metrics(flatten)]
+///     context: RpcContext,
+///     code: i32,
+/// }
+/// // will be exported as labels: { method="...", network="...", code="..." }
+/// ```
 pub use vise_macros::EncodeLabelSet;
 /// Derives the [`EncodeLabelValue`] trait for a type, which encodes a metric label value.
 ///
-/// The type for which the trait is derived must not have generics (lifetimes, type params or const params).
+/// The type for which the trait is derived may have type params, but not lifetimes or const params.
+/// For generic types with the default format, the derived impl requires the type to implement `Display`;
+/// with a custom `format`, bounds required by the format must be specified on type params.
 /// The macro can be configured using `#[metrics()]` attributes.
 ///
 /// # Container attributes
@@ -343,9 +498,29 @@ pub use vise_macros::EncodeLabelSet;
 pub use vise_macros::EncodeLabelValue;
 /// Derives the [`Metrics`](trait@Metrics) trait for a type.
 ///
-/// This macro must be placed on a struct with named fields and with no generics (lifetimes, type params
-/// or const params). Each field will be registered as metric or a family of metrics.
-/// The macro can be configured using `#[metrics()]` attributes.
+/// This macro must be placed on a struct with named fields. Each field will be registered as metric
+/// or a family of metrics. The macro can be configured using `#[metrics()]` attributes.
+///
+/// The struct may have type params (but not lifetimes or const params); bounds on type params
+/// must be sufficient for all fields to be metrics. Each monomorphization of a generic struct
+/// has its own `'static` [descriptor](Metrics::DESCRIPTOR). Label names of families with generic labels
+/// are not recorded in the descriptor.
+///
+/// ```
+/// # use std::{fmt, hash::Hash};
+/// use vise::{traits::EncodeLabelSet, Counter, Family, Metrics};
+///
+/// trait Backend: 'static + Send + Sync {
+///     type Labels: fmt::Debug + Clone + Eq + Hash + EncodeLabelSet;
+/// }
+///
+/// #[derive(Debug, Metrics)]
+/// #[metrics(prefix = "storage")]
+/// struct StorageMetrics<B: Backend> {
+///     /// Number of storage operations.
+///     operations: Family<B::Labels, Counter>,
+/// }
+/// ```
 ///
 /// # Container attributes
 ///
@@ -380,24 +555,183 @@ pub use vise_macros::EncodeLabelValue;
 /// and will result in a compile-time error if used with other metric types. The number of label names
 /// must match the number of label values in the `LabeledFamily` (i.e., its type).
 ///
+/// ## `stability`
+///
+/// **Type:** string; one of `"alpha"` or `"stable"`
+///
+/// Specifies the [stability level](descriptors::Stability) of a metric. Metrics are stable by default.
+/// Alpha metrics can be hidden from export using [`MetricsCollection::hide_alpha_metrics()`].
+///
+/// ## `deprecated_name`
+///
+/// **Type:** string
+///
+/// Specifies the previous name of a metric to provide a migration path after renaming it. The name is
+/// transformed in the same way as the field name (i.e., the prefix and the unit suffix are added).
+/// The metric will be additionally exported under the deprecated name; this can be disabled
+/// using [`MetricsCollection::hide_deprecated_names()`].
+///
+/// ```
+/// # use vise::{Counter, Metrics};
+/// #[derive(Debug, Metrics)]
+/// #[metrics(prefix = "my_app")]
+/// struct AppMetrics {
+///     /// Number of processed requests. Was previously exported as `my_app_requests_processed`.
+///     #[metrics(deprecated_name = "requests_processed")]
+///     processed_requests: Counter,
+///     /// Experimental metric that can change in future releases.
+///     #[metrics(stability = "alpha")]
+///     cache_hits: Counter,
+/// }
+/// ```
+///
+/// ## `rename`
+///
+/// **Type:** string
+///
+/// Overrides the field name used to construct the metric name. The name is transformed in the same way
+/// as the field name (i.e., the prefix and the unit suffix are added) and is validated at compile time.
+///
+/// ## `help`
+///
+/// **Type:** string or expression evaluating to `&'static str` (e.g., a `concat!` call)
+///
+/// Overrides the metric help, which by default is taken from the field doc comment. A string is processed
+/// in the same way as a doc comment (e.g., the trailing punctuation is removed since it's added on encoding);
+/// other expressions are used as is. If a metric with help specified by an expression has a `deprecated_name`,
+/// the help for the deprecated name is the same.
+///
+/// ## `sharded`
+///
+/// **Type:** flag
+///
+/// Shards a [`Counter`], [`Gauge`] or [`Histogram`] (or a [`Family`] of such metrics) so that it can be updated
+/// from many threads without contention. See [`MetricBuilder::with_sharding()`] for details and caveats.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use vise::{Buckets, Counter, Histogram, Metrics};
+/// #[derive(Debug, Metrics)]
+/// #[metrics(prefix = "my_app")]
+/// struct AppMetrics {
+///     /// Number of processed items; updated by all worker threads.
+///     #[metrics(sharded)]
+///     processed_items: Counter,
+///     /// Item processing latency.
+///     #[metrics(buckets = Buckets::LATENCIES, sharded)]
+///     item_latency: Histogram<Duration>,
+/// }
+/// ```
+///
+/// ## `skip`
+///
+/// **Type:** flag
+///
+/// Marks a field as not being a metric, e.g. configuration or a handle stored alongside metrics.
+/// The field is initialized using its `Default` implementation and is ignored otherwise.
+/// Cannot be combined with other attributes.
+///
+/// ```
+/// # use vise::{Counter, Metrics};
+/// #[derive(Debug, Metrics)]
+/// #[metrics(prefix = "my_app")]
+/// struct AppMetrics {
+///     /// Exported as `my_app_handled_requests`.
+///     #[metrics(rename = "handled_requests")]
+///     requests: Counter,
+///     #[metrics(help = concat!("Number of errors in ", env!("CARGO_PKG_NAME")))]
+///     errors: Counter,
+///     #[metrics(skip)]
+///     app_name: String,
+/// }
+/// ```
+///
+/// ## `flatten`
+///
+/// **Type:** flag
+///
+/// Marks a field as a nested group of metrics, i.e., a type implementing [`Metrics`](trait@Metrics)
+/// and `Default` (e.g., another type deriving `Metrics`). Metrics from the nested group are exported
+/// with unchanged names, ignoring the `prefix` of the enclosing group. Nested metric descriptors are merged into the enclosing group on registration.
+///
+/// ## `prefix`
+///
+/// **Type:** string
+///
+/// Similar to `flatten`, but additionally prepends the specified prefix to the names of nested metrics.
+/// If the enclosing group has a prefix, the nested prefix is appended to it with a `_` separator.
+/// Mutually exclusive with `flatten`.
+///
+/// ```
+/// # use vise::{Counter, Metrics};
+/// #[derive(Debug, Metrics)]
+/// struct CacheMetrics {
+///     /// Number of cache hits.
+///     hits: Counter,
+/// }
+///
+/// #[derive(Debug, Metrics)]
+/// #[metrics(prefix = "my_app")]
+/// struct AppMetrics {
+///     /// Number of processed requests.
+///     requests: Counter,
+///     /// Cache metrics exported as `my_app_cache_hits` etc.
+///     #[metrics(prefix = "cache")]
+///     cache: CacheMetrics,
+/// }
+/// ```
+///
+/// # Descriptors
+///
+/// Besides implementing the trait, the macro records metadata for each metric in
+/// [`Metrics::DESCRIPTOR`](trait@Metrics): its name, type, unit, help, label names
+/// (from the `labels` attribute or from [`EncodeLabelSet`](trait@crate::traits::EncodeLabelSet) for families),
+/// buckets, stability and the deprecated name.
+///
 /// # Examples
 ///
 /// See crate-level docs and other crate docs for the examples of usage.
 pub use vise_macros::Metrics;
 
+#[cfg(feature = "metrics-bridge")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics-bridge")))]
+pub use crate::bridge::{InstallRecorderError, MetricsRecorder};
+#[cfg(feature = "opentelemetry-bridge")]
+#[cfg_attr(docsrs, doc(cfg(feature = "opentelemetry-bridge")))]
+pub use crate::otel::{OtelMeterProvider, OtelMetricDescriptor, OtelScopeDescriptor};
+#[cfg(feature = "tower-middleware")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tower-middleware")))]
+pub use crate::tower_middleware::{
+    HttpMethod, HttpMetrics, HttpMetricsFuture, HttpMetricsLayer, HttpMetricsService,
+    HttpRequestLabels, HttpResponseLabels, HttpStatusClass,
+};
+#[cfg(feature = "tracing-layer")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tracing-layer")))]
+pub use crate::tracing_layer::MetricsLayer;
 pub use crate::{
     buckets::Buckets,
+    build_info::BuildInfo,
     builder::{BuildMetric, MetricBuilder},
+    catalogue::CatalogueFormat,
     collector::{BeforeScrapeError, Collector},
+    encoding::{DurationAsSecs, LabelWithUnit},
+    filter::MetricsFilter,
     format::Format,
+    futures::{
+        FutureMetrics, InstrumentFuture, InstrumentStream, InstrumentedFuture, InstrumentedStream,
+        StreamMetrics,
+    },
+    instrument::Outcome,
     metrics::{Global, Metrics, MetricsFamily},
+    mpsc::ChannelMetrics,
     registry::{
-        CollectToRegistry, MetricsCollection, MetricsVisitor, RegisteredDescriptors, Registry,
-        METRICS_REGISTRATIONS,
+        CollectToRegistry, ConflictPolicy, MetricsCollection, MetricsVisitor,
+        RegisteredDescriptors, RegistrationError, Registry, METRICS_REGISTRATIONS,
     },
+    switch::GroupSwitch,
     wrappers::{
-        DurationAsSecs, Family, Gauge, GaugeGuard, Histogram, Info, LabelWithUnit, LabeledFamily,
-        LatencyObserver, LazyItem, SetInfoError,
+        Counter, Family, Gauge, GaugeGuard, Histogram, Info, LabeledFamily, LatencyObserver,
+        LazyItem, SetInfoError,
     },
 };
 
@@ -408,19 +742,44 @@ pub mod _reexports {
 }
 #[doc(hidden)] // only used by the proc macros
 pub mod _private {
-    pub use crate::format::EncodingContext;
+    pub use crate::{
+        builder::{LabelNamesFallback, LabelNamesProbe},
+        format::EncodingContext,
+        instrument::{record_unknown_error, CallGuard, ObserveLatency, RecordError},
+        metrics::{describe_nested_group, NestedDescriptors},
+        registry::PrefixedVisitor,
+        switch::group_switch,
+    };
 }
 
+#[cfg(feature = "metrics-bridge")]
+mod bridge;
 mod buckets;
+mod build_info;
 mod builder;
+mod catalogue;
 mod collector;
 pub mod descriptors;
 mod encoding;
+mod filter;
 mod format;
+mod futures;
+mod instrument;
+pub mod lint;
 mod metrics;
+pub mod mpsc;
+mod names;
+#[cfg(feature = "opentelemetry-bridge")]
+mod otel;
 mod registry;
+mod sharded;
+mod switch;
 #[cfg(test)]
 mod tests;
+#[cfg(feature = "tower-middleware")]
+mod tower_middleware;
+#[cfg(feature = "tracing-layer")]
+mod tracing_layer;
 pub mod traits;
 #[doc(hidden)]
 pub mod validation;
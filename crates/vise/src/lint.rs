@@ -0,0 +1,467 @@
+//! Linting metric definitions against [Prometheus naming conventions].
+//!
+//! The lints are based on [metric descriptors](crate::descriptors), so they can be checked without
+//! reporting any metric values. The most straightforward way to use lints is to call [`assert_conventions()`]
+//! in a test.
+//!
+//! # Examples
+//!
+//! ```
+//! use vise::{Buckets, Counter, Histogram, Metrics, Registry, Unit};
+//! # use std::time::Duration;
+//!
+//! #[derive(Debug, Metrics)]
+//! #[metrics(prefix = "my_app")]
+//! struct AppMetrics {
+//!     /// Number of processed requests.
+//!     requests: Counter,
+//!     /// Latency of processing requests.
+//!     #[metrics(buckets = Buckets::LATENCIES)]
+//!     request_latency_ms: Histogram<Duration>,
+//! }
+//!
+//! let mut registry = Registry::empty();
+//! registry.register_metrics(&AppMetrics::default());
+//! let report = vise::lint::check_conventions(registry.descriptors());
+//! let report = report.to_string();
+//! assert!(report.contains("Metric `my_app_request_latency_ms`"), "{report}");
+//! assert!(report.contains("uses non-base unit `ms`"), "{report}");
+//! ```
+//!
+//! [Prometheus naming conventions]: https://prometheus.io/docs/practices/naming/
+
+use std::fmt;
+
+use prometheus_client::{metrics::MetricType, registry::Unit};
+
+use crate::{descriptors::FullMetricDescriptor, registry::RegisteredDescriptors, Registry};
+
+/// Maximum recommended length of a full metric name (i.e., the name reported to Prometheus).
+pub const MAX_NAME_LENGTH: usize = 80;
+
+/// Units that can be specified via [`Unit`] variants.
+const BASE_UNITS: &[(&str, &str)] = &[
+    ("amperes", "Unit::Amperes"),
+    ("bytes", "Unit::Bytes"),
+    ("celsius", "Unit::Celsius"),
+    ("grams", "Unit::Grams"),
+    ("joules", "Unit::Joules"),
+    ("meters", "Unit::Meters"),
+    ("ratio", "Unit::Ratios"),
+    ("ratios", "Unit::Ratios"),
+    ("seconds", "Unit::Seconds"),
+    ("volts", "Unit::Volts"),
+];
+
+/// Non-base units together with the corresponding base units.
+const NON_BASE_UNITS: &[(&str, &str)] = &[
+    ("nanoseconds", "seconds"),
+    ("nanos", "seconds"),
+    ("microseconds", "seconds"),
+    ("micros", "seconds"),
+    ("milliseconds", "seconds"),
+    ("millis", "seconds"),
+    ("ms", "seconds"),
+    ("minutes", "seconds"),
+    ("hours", "seconds"),
+    ("days", "seconds"),
+    ("bits", "bytes"),
+    ("kilobytes", "bytes"),
+    ("kb", "bytes"),
+    ("kib", "bytes"),
+    ("megabytes", "bytes"),
+    ("mb", "bytes"),
+    ("mib", "bytes"),
+    ("gigabytes", "bytes"),
+    ("gb", "bytes"),
+    ("gib", "bytes"),
+    ("percent", "ratios"),
+    ("percents", "ratios"),
+    ("kilograms", "grams"),
+    ("kilometers", "meters"),
+];
+
+/// Suffixes appended by Prometheus clients to the metric name for certain metric types.
+const RESERVED_SUFFIXES: &[&str] = &["total", "count", "sum", "bucket", "created", "info"];
+
+/// Label names reserved by Prometheus.
+const RESERVED_LABELS: &[&str] = &["le", "quantile"];
+
+/// Violation of a naming convention for a single metric.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Violation {
+    /// Metric name (excluding the unit suffix added by the library) ends with a unit.
+    UnitInName {
+        /// Unit in the metric name, e.g. `seconds`.
+        unit: &'static str,
+        /// Path to the corresponding [`Unit`] variant.
+        unit_variant: &'static str,
+    },
+    /// Unit is placed in the middle of the metric name rather than at its end.
+    MisplacedUnit {
+        /// Unit in the metric name, e.g. `seconds`.
+        unit: &'static str,
+    },
+    /// Metric uses a non-base unit, e.g. milliseconds instead of seconds.
+    NonBaseUnit {
+        /// Used unit, e.g. `ms`.
+        unit: String,
+        /// Base unit that should be used instead, e.g. `seconds`.
+        base_unit: &'static str,
+    },
+    /// Metric name ends with a suffix reserved for certain metric types (e.g., `_total` for counters
+    /// or `_count` for histograms).
+    ReservedSuffix {
+        /// Reserved suffix, e.g. `total`.
+        suffix: &'static str,
+    },
+    /// Metric uses a label name reserved by Prometheus, such as `le` or `quantile`.
+    ReservedLabel {
+        /// Label name.
+        label: &'static str,
+    },
+    /// Full metric name is longer than [`MAX_NAME_LENGTH`].
+    NameTooLong {
+        /// Length of the full metric name.
+        len: usize,
+    },
+    /// Metric has no help text (i.e., no doc comment).
+    MissingHelp,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnitInName { unit, unit_variant } => write!(
+                formatter,
+                "name ends with unit `_{unit}`; remove it from the name and specify \
+                 `#[metrics(unit = {unit_variant})]` instead"
+            ),
+            Self::MisplacedUnit { unit } => write!(
+                formatter,
+                "unit `{unit}` is in the middle of the name; units should be placed at the end \
+                 of the name via the `unit` attribute"
+            ),
+            Self::NonBaseUnit { unit, base_unit } => write!(
+                formatter,
+                "uses non-base unit `{unit}`; use `{base_unit}` instead (e.g., with `Duration` values \
+                 for seconds)"
+            ),
+            Self::ReservedSuffix { suffix } => write!(
+                formatter,
+                "name ends with `_{suffix}`, which is reserved for specific metric types \
+                 and is added automatically where appropriate; remove the suffix"
+            ),
+            Self::ReservedLabel { label } => write!(
+                formatter,
+                "uses reserved label name `{label}`; rename the label"
+            ),
+            Self::NameTooLong { len } => write!(
+                formatter,
+                "name has {len} chars, which exceeds the recommended maximum of {MAX_NAME_LENGTH}; \
+                 shorten the name or its prefix"
+            ),
+            Self::MissingHelp => formatter.write_str(
+                "help is missing; add a doc comment to the metric field",
+            ),
+        }
+    }
+}
+
+/// Convention violations for a single metric.
+#[derive(Debug, Clone)]
+pub struct MetricViolations {
+    /// Metric descriptor.
+    pub metric: FullMetricDescriptor,
+    /// Violations for the metric. Never empty.
+    pub violations: Vec<Violation>,
+}
+
+impl fmt::Display for MetricViolations {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let group = self.metric.group;
+        writeln!(
+            formatter,
+            "Metric `{name}` ({module}::{group_name}.{field_name} in crate {crate_name}):",
+            name = self.metric.metric.full_name(),
+            module = group.module_path,
+            group_name = group.name,
+            field_name = self.metric.metric.field_name,
+            crate_name = group.crate_name
+        )?;
+        for violation in &self.violations {
+            writeln!(formatter, "  - {violation}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Report produced by [`check_conventions()`].
+#[derive(Debug, Clone, Default)]
+pub struct LintReport {
+    metrics: Vec<MetricViolations>,
+}
+
+impl fmt::Display for LintReport {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for metric in &self.metrics {
+            fmt::Display::fmt(metric, formatter)?;
+        }
+        Ok(())
+    }
+}
+
+impl LintReport {
+    /// Checks whether the report contains no violations.
+    pub fn is_empty(&self) -> bool {
+        self.metrics.is_empty()
+    }
+
+    /// Iterates over metrics with convention violations.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &MetricViolations> + '_ {
+        self.metrics.iter()
+    }
+}
+
+/// Checks all metrics in the provided descriptors against naming conventions.
+pub fn check_conventions(descriptors: &RegisteredDescriptors) -> LintReport {
+    let metrics = descriptors.metrics().filter_map(|metric| {
+        let violations = check_metric(&metric);
+        (!violations.is_empty()).then_some(MetricViolations { metric, violations })
+    });
+    LintReport {
+        metrics: metrics.collect(),
+    }
+}
+
+/// Asserts that all metrics in the `registry` conform to naming conventions. This is intended to be used in tests.
+///
+/// # Panics
+///
+/// Panics with a report listing all violations if there is at least one violation.
+#[track_caller]
+pub fn assert_conventions(registry: &Registry) {
+    let report = check_conventions(registry.descriptors());
+    assert!(
+        report.is_empty(),
+        "Metrics violate naming conventions:\n{report}"
+    );
+}
+
+fn check_metric(descriptor: &FullMetricDescriptor) -> Vec<Violation> {
+    let metric = descriptor.metric;
+    let mut violations = vec![];
+
+    let segments: Vec<_> = metric.name.split('_').collect();
+    let (&last_segment, leading_segments) = segments.split_last().unwrap();
+    let suffix_segment = if RESERVED_SUFFIXES.contains(&last_segment) {
+        // E.g., `latency_seconds_total`; the unit should be checked for the segment before the suffix.
+        leading_segments.last().copied()
+    } else {
+        Some(last_segment)
+    };
+
+    for (i, &segment) in segments.iter().enumerate() {
+        let is_suffix = i + 1 == segments.len();
+        if let Some(&(unit, unit_variant)) = BASE_UNITS.iter().find(|(unit, _)| *unit == segment) {
+            if is_suffix {
+                violations.push(Violation::UnitInName { unit, unit_variant });
+            } else {
+                violations.push(Violation::MisplacedUnit { unit });
+            }
+        }
+        let is_unit_position = is_suffix || Some(segment) == suffix_segment;
+        if let Some(&(_, base_unit)) = NON_BASE_UNITS.iter().find(|(unit, _)| *unit == segment) {
+            if is_unit_position {
+                violations.push(Violation::NonBaseUnit {
+                    unit: segment.to_owned(),
+                    base_unit,
+                });
+            }
+        }
+    }
+
+    if let Some(Unit::Other(unit)) = &metric.unit {
+        if let Some(&(_, base_unit)) = NON_BASE_UNITS.iter().find(|(name, _)| name == unit) {
+            violations.push(Violation::NonBaseUnit {
+                unit: unit.clone(),
+                base_unit,
+            });
+        }
+    }
+
+    let full_name = metric.full_name();
+    if let Some(suffix) = RESERVED_SUFFIXES
+        .iter()
+        .find(|suffix| full_name.ends_with(&format!("_{suffix}")))
+    {
+        violations.push(Violation::ReservedSuffix { suffix });
+    }
+
+    for label in descriptor.label_names() {
+        if RESERVED_LABELS.contains(&label) || label.starts_with("__") {
+            violations.push(Violation::ReservedLabel { label });
+        }
+    }
+
+    let full_len = full_name.len() + type_suffix(metric.metric_type).len();
+    if full_len > MAX_NAME_LENGTH {
+        violations.push(Violation::NameTooLong { len: full_len });
+    }
+    if metric.help.is_empty() {
+        violations.push(Violation::MissingHelp);
+    }
+    violations
+}
+
+/// Returns the longest suffix added to the metric name during encoding.
+fn type_suffix(metric_type: MetricType) -> &'static str {
+    match metric_type {
+        MetricType::Counter => "_total",
+        MetricType::Histogram => "_bucket",
+        MetricType::Info => "_info",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        Buckets, Counter, EncodeLabelSet, Family, Gauge, Histogram, LabeledFamily, Metrics,
+    };
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+    #[metrics(crate = crate)]
+    struct BogusLabels {
+        le: u64,
+        stage: &'static str,
+    }
+
+    #[derive(Debug, Metrics)]
+    #[metrics(crate = crate, prefix = "lint")]
+    struct BogusMetrics {
+        /// Number of processed requests.
+        requests: Counter,
+        /// Latency of requests.
+        #[metrics(buckets = Buckets::LATENCIES)]
+        latency_seconds: Histogram<Duration>,
+        /// Total size of the cache.
+        cache_size_bytes_total: Gauge<u64>,
+        /// Latency of requests in milliseconds.
+        #[metrics(buckets = Buckets::LATENCIES)]
+        request_latency_ms: Histogram<Duration>,
+        events_count: Counter,
+        /// Number of events by stage.
+        events_by_stage: Family<BogusLabels, Counter>,
+        /// Number of events by quantile.
+        #[metrics(labels = ["quantile"])]
+        events_by_quantile: LabeledFamily<u64, Counter>,
+        /// Very long name.
+        this_is_a_very_long_metric_name_which_will_not_be_convenient_to_use_in_queries: Gauge,
+    }
+
+    fn violations_for(report: &LintReport, field_name: &str) -> Vec<Violation> {
+        report
+            .iter()
+            .find(|metric| metric.metric.metric.field_name == field_name)
+            .map(|metric| metric.violations.clone())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn linting_metrics() {
+        let mut registry = Registry::empty();
+        registry.register_metrics(&BogusMetrics::default());
+        let report = check_conventions(registry.descriptors());
+
+        assert_eq!(violations_for(&report, "requests"), []);
+        assert_eq!(
+            violations_for(&report, "latency_seconds"),
+            [Violation::UnitInName {
+                unit: "seconds",
+                unit_variant: "Unit::Seconds"
+            }]
+        );
+        assert_eq!(
+            violations_for(&report, "cache_size_bytes_total"),
+            [
+                Violation::MisplacedUnit { unit: "bytes" },
+                Violation::ReservedSuffix { suffix: "total" }
+            ]
+        );
+        assert_eq!(
+            violations_for(&report, "request_latency_ms"),
+            [Violation::NonBaseUnit {
+                unit: "ms".to_owned(),
+                base_unit: "seconds"
+            }]
+        );
+        assert_eq!(
+            violations_for(&report, "events_count"),
+            [
+                Violation::ReservedSuffix { suffix: "count" },
+                Violation::MissingHelp
+            ]
+        );
+        assert_eq!(
+            violations_for(&report, "events_by_stage"),
+            [Violation::ReservedLabel { label: "le" }]
+        );
+        assert_eq!(
+            violations_for(&report, "events_by_quantile"),
+            [Violation::ReservedLabel { label: "quantile" }]
+        );
+        assert_eq!(
+            violations_for(
+                &report,
+                "this_is_a_very_long_metric_name_which_will_not_be_convenient_to_use_in_queries"
+            ),
+            [Violation::NameTooLong { len: 83 }]
+        );
+        assert_eq!(report.iter().len(), 7);
+
+        let report = report.to_string();
+        assert!(
+            report.contains(
+                "Metric `lint_request_latency_ms` (vise::lint::tests::BogusMetrics.request_latency_ms \
+                 in crate vise):\n  - uses non-base unit `ms`"
+            ),
+            "{report}"
+        );
+    }
+
+    #[test]
+    fn conventional_metrics_pass_lints() {
+        #[derive(Debug, Metrics)]
+        #[metrics(crate = crate, prefix = "lint")]
+        struct ConventionalMetrics {
+            /// Number of processed requests.
+            requests: Counter,
+            /// Latency of requests.
+            #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
+            request_latency: Histogram<Duration>,
+            /// Size of the cache.
+            #[metrics(unit = Unit::Bytes)]
+            cache_size: Gauge<u64>,
+            /// Number of events by stage.
+            #[metrics(labels = ["stage"])]
+            events: LabeledFamily<&'static str, Counter>,
+        }
+
+        let mut registry = Registry::empty();
+        registry.register_metrics(&ConventionalMetrics::default());
+        assert_conventions(&registry);
+    }
+
+    #[test]
+    #[should_panic(expected = "Metrics violate naming conventions")]
+    fn asserting_conventions() {
+        let mut registry = Registry::empty();
+        registry.register_metrics(&BogusMetrics::default());
+        assert_conventions(&registry);
+    }
+}
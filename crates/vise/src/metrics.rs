@@ -1,14 +1,15 @@
 //! Core `Metrics` trait defined by the crate.
 
-use std::{fmt, hash::Hash, ops, sync::Arc};
+use std::{any::TypeId, collections::HashMap, fmt, hash::Hash, ops, sync::Mutex};
 
 use once_cell::sync::Lazy;
 
 use crate::{
-    descriptors::MetricGroupDescriptor,
+    descriptors::{MetricDescriptor, MetricGroupDescriptor},
     encoding::LabelGroups,
-    registry::{CollectToRegistry, MetricsVisitor, Registry},
-    traits::EncodeLabelSet,
+    registry::{CollectToRegistry, MetricsVisitor, RegistrationError, Registry},
+    switch::GroupSwitch,
+    traits::{EncodeLabelSet, LabelSetNames},
     wrappers::FamilyInner,
     LazyItem,
 };
@@ -20,6 +21,23 @@ pub trait Metrics: 'static + Send + Sync {
 
     #[doc(hidden)] // implementation detail
     fn visit_metrics(&self, visitor: &mut dyn MetricsVisitor);
+
+    /// Describes metrics in nested groups, prefixing their names with `prefix`. Generated by the derive macro
+    /// for groups with fields having the `flatten` or `prefix` attribute.
+    #[doc(hidden)] // implementation detail
+    fn describe_nested(_prefix: Option<&str>, _descriptors: &mut NestedDescriptors) {
+        // Do nothing by default
+    }
+
+    /// Creates metrics for a group nested into a group with the specified runtime switch. Generated
+    /// by the derive macro, so that switches of the enclosing groups apply to the nested group.
+    #[doc(hidden)] // implementation detail
+    fn nested_default(_parent_switch: &'static GroupSwitch) -> Self
+    where
+        Self: Sized + Default,
+    {
+        Self::default()
+    }
 }
 
 impl<M: Metrics> Metrics for &'static M {
@@ -28,6 +46,10 @@ impl<M: Metrics> Metrics for &'static M {
     fn visit_metrics(&self, visitor: &mut dyn MetricsVisitor) {
         (**self).visit_metrics(visitor);
     }
+
+    fn describe_nested(prefix: Option<&str>, descriptors: &mut NestedDescriptors) {
+        M::describe_nested(prefix, descriptors);
+    }
 }
 
 impl<M: Metrics> Metrics for Option<M> {
@@ -38,6 +60,69 @@ impl<M: Metrics> Metrics for Option<M> {
             metrics.visit_metrics(visitor);
         }
     }
+
+    fn describe_nested(prefix: Option<&str>, descriptors: &mut NestedDescriptors) {
+        M::describe_nested(prefix, descriptors);
+    }
+}
+
+/// Descriptors of metrics and groups nested into a group, collected by [`Metrics::describe_nested()`].
+#[doc(hidden)] // only used by the proc macros
+#[derive(Debug, Default)]
+pub struct NestedDescriptors {
+    metrics: Vec<MetricDescriptor>,
+    groups: Vec<&'static MetricGroupDescriptor>,
+}
+
+/// Describes metrics in a group nested into another group. `prefix` is the prefix passed to the enclosing group,
+/// and `nested_prefix` is the prefix of the nested group, or `None` if the group is flattened.
+#[doc(hidden)] // only used by the proc macros
+pub fn describe_nested_group<M: Metrics>(
+    prefix: Option<&str>,
+    nested_prefix: Option<&str>,
+    descriptors: &mut NestedDescriptors,
+) {
+    let prefix = match (prefix, nested_prefix) {
+        (Some(prefix), Some(nested_prefix)) => Some(format!("{prefix}_{nested_prefix}")),
+        (prefix, nested_prefix) => prefix.or(nested_prefix).map(str::to_owned),
+    };
+    let prefix = prefix.as_deref();
+    let metrics = M::DESCRIPTOR.metrics.iter().map(|metric| match prefix {
+        Some(prefix) => metric.leak_with_prefix(prefix),
+        None => metric.clone(),
+    });
+    descriptors.metrics.extend(metrics);
+
+    let group: &'static MetricGroupDescriptor = &M::DESCRIPTOR;
+    // The same group may be nested several times (e.g., with different prefixes); it shares the switch
+    // in all places, so it's only listed once.
+    if !descriptors
+        .groups
+        .iter()
+        .any(|other| other.is_same_group(group))
+    {
+        descriptors.groups.push(group);
+    }
+    M::describe_nested(prefix, descriptors);
+}
+
+/// Returns the descriptor of a group with metrics from nested groups (including transitively nested ones)
+/// merged into [`MetricGroupDescriptor::metrics`], and nested groups listed in [`MetricGroupDescriptor::nested_groups`].
+/// The descriptor is built (and leaked) once per group type.
+pub(crate) fn flattened_descriptor<M: Metrics>() -> &'static MetricGroupDescriptor {
+    static DESCRIPTORS: Lazy<Mutex<HashMap<TypeId, &'static MetricGroupDescriptor>>> =
+        Lazy::new(Mutex::default);
+
+    let mut descriptors = DESCRIPTORS.lock().expect("descriptors cache is poisoned");
+    descriptors.entry(TypeId::of::<M>()).or_insert_with(|| {
+        let mut nested = NestedDescriptors::default();
+        M::describe_nested(None, &mut nested);
+        if nested.groups.is_empty() {
+            &M::DESCRIPTOR
+        } else {
+            M::DESCRIPTOR.leak_with_nested(nested.metrics, nested.groups)
+        }
+    })
 }
 
 /// Global instance of [`Metrics`] allowing to access contained metrics from anywhere in code.
@@ -71,8 +156,11 @@ impl<M: Metrics> CollectToRegistry for Global<M> {
         &M::DESCRIPTOR
     }
 
-    fn collect_to_registry(&'static self, registry: &mut Registry) {
-        registry.register_global_metrics(&self.0, false);
+    fn collect_to_registry(
+        &'static self,
+        registry: &mut Registry,
+    ) -> Result<(), RegistrationError> {
+        registry.register_global_metrics(&self.0, false)
     }
 }
 
@@ -180,45 +268,62 @@ where
 
 impl<S, M> Metrics for FamilyInner<S, M>
 where
-    S: EncodeLabelSet + Clone + Eq + Hash + Send + Sync + 'static,
+    S: EncodeLabelSet + LabelSetNames + Clone + Eq + Hash + Send + Sync + 'static,
     M: Metrics + Default,
 {
-    const DESCRIPTOR: MetricGroupDescriptor = M::DESCRIPTOR;
+    const DESCRIPTOR: MetricGroupDescriptor = MetricGroupDescriptor {
+        labels: S::LABELS,
+        ..M::DESCRIPTOR
+    };
 
     fn visit_metrics(&self, visitor: &mut dyn MetricsVisitor) {
         let mut grouped = LabelGroups::default();
-        for (labels, metrics) in self.to_entries() {
-            grouped.set_labels(Arc::new(labels));
+        for (labels, metrics) in self.iter() {
+            grouped.set_labels(labels.clone());
             metrics.visit_metrics(&mut grouped);
         }
         grouped.visit_metrics(visitor);
     }
+
+    fn describe_nested(prefix: Option<&str>, descriptors: &mut NestedDescriptors) {
+        M::describe_nested(prefix, descriptors);
+    }
 }
 
 impl<S, M> Metrics for MetricsFamily<S, M>
 where
-    S: EncodeLabelSet + Clone + Eq + Hash + Send + Sync + 'static,
+    S: EncodeLabelSet + LabelSetNames + Clone + Eq + Hash + Send + Sync + 'static,
     M: Metrics + Default,
 {
-    const DESCRIPTOR: MetricGroupDescriptor = M::DESCRIPTOR;
+    const DESCRIPTOR: MetricGroupDescriptor = MetricGroupDescriptor {
+        labels: S::LABELS,
+        ..M::DESCRIPTOR
+    };
 
     fn visit_metrics(&self, visitor: &mut dyn MetricsVisitor) {
         if let Some(inner) = Lazy::get(&self.0) {
             inner.visit_metrics(visitor);
         }
     }
+
+    fn describe_nested(prefix: Option<&str>, descriptors: &mut NestedDescriptors) {
+        M::describe_nested(prefix, descriptors);
+    }
 }
 
 impl<S, M> CollectToRegistry for MetricsFamily<S, M>
 where
-    S: EncodeLabelSet + Clone + Eq + Hash + Send + Sync + 'static,
+    S: EncodeLabelSet + LabelSetNames + Clone + Eq + Hash + Send + Sync + 'static,
     M: Metrics + Default,
 {
     fn descriptor(&self) -> &'static MetricGroupDescriptor {
-        &M::DESCRIPTOR
+        &<Self as Metrics>::DESCRIPTOR
     }
 
-    fn collect_to_registry(&'static self, registry: &mut Registry) {
-        registry.register_global_metrics(&self.0, true);
+    fn collect_to_registry(
+        &'static self,
+        registry: &mut Registry,
+    ) -> Result<(), RegistrationError> {
+        registry.register_global_metrics(&self.0, true)
     }
 }
@@ -0,0 +1,397 @@
+//! Instrumented multi-producer, single-consumer channels.
+//!
+//! Channels in this module wrap [`std::sync::mpsc`] channels (and, with the `tokio-channels` crate feature,
+//! `tokio::sync::mpsc` channels in the `tokio` submodule) and report [`ChannelMetrics`]
+//! for the messages passing through them.
+//!
+//! # Examples
+//!
+//! Many channels can share a single labeled set of metrics via [`MetricsFamily`](crate::MetricsFamily):
+//!
+//! ```
+//! use vise::{mpsc, ChannelMetrics, EncodeLabelSet, Metrics, MetricsFamily};
+//!
+//! #[derive(Debug, Metrics)]
+//! #[metrics(prefix = "app")]
+//! struct AppChannelMetrics {
+//!     /// Metrics exported as `app_channel_depth`, `app_channel_sent_total` etc.
+//!     #[metrics(prefix = "channel")]
+//!     channel: ChannelMetrics,
+//! }
+//!
+//! #[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+//! struct ChannelLabels {
+//!     name: &'static str,
+//! }
+//!
+//! #[vise::register]
+//! static METRICS: MetricsFamily<ChannelLabels, AppChannelMetrics> = MetricsFamily::new();
+//!
+//! let metrics = &METRICS[&ChannelLabels { name: "jobs" }].channel;
+//! let (sender, receiver) = mpsc::channel(metrics);
+//! sender.send("job").unwrap();
+//! assert_eq!(metrics.depth.get(), 1);
+//! assert_eq!(receiver.recv().unwrap(), "job");
+//! assert_eq!(metrics.depth.get(), 0);
+//! ```
+
+pub use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError};
+use std::{
+    fmt,
+    sync::{mpsc as std_mpsc, Arc, OnceLock},
+    time::{Duration, Instant},
+};
+
+use crate::{Buckets, Counter, Gauge, GaugeGuard, Histogram, Metrics, Unit};
+
+#[cfg(feature = "tokio-channels")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio-channels")))]
+pub mod tokio;
+
+/// Metrics for [instrumented](self) channels.
+///
+/// Normally, this group is nested into another metrics group using the `prefix` field attribute
+/// so that metric names are distinguishable; see the [module docs](self) for an example.
+///
+/// Metrics are cloned into the created channels; a clone shares metric values with the original,
+/// so metrics don't need to be `'static`.
+#[derive(Debug, Clone, Metrics)]
+#[metrics(crate = crate)]
+pub struct ChannelMetrics {
+    /// Number of messages currently buffered in the channel.
+    pub depth: Gauge<u64>,
+    /// Number of messages sent to the channel.
+    pub sent: Counter,
+    /// Number of messages received from the channel.
+    pub received: Counter,
+    /// Time between sending a message and receiving it.
+    #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
+    pub time_in_queue: Histogram<Duration>,
+    /// Time senders spent waiting for capacity in a full bounded channel.
+    #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
+    pub send_blocked: Histogram<Duration>,
+}
+
+impl ChannelMetrics {
+    fn wrap<T>(&self, value: T) -> Envelope<T> {
+        Envelope {
+            value,
+            state: EnvelopeState::Enqueued(self.enqueued()),
+        }
+    }
+
+    fn enqueued(&self) -> Enqueued {
+        Enqueued {
+            at: Instant::now(),
+            _depth: self.depth.inc_guard(1),
+        }
+    }
+
+    fn unwrap<T>(&self, envelope: Envelope<T>) -> T {
+        self.received.inc();
+        let enqueued_at = match &envelope.state {
+            EnvelopeState::Enqueued(enqueued) => Some(enqueued.at),
+            EnvelopeState::Deferred(slot) => slot.get().map(|enqueued| enqueued.at),
+        };
+        // If the sender hasn't marked the message as enqueued yet, it was received immediately.
+        let time_in_queue = enqueued_at.map_or(Duration::ZERO, |at| at.elapsed());
+        self.time_in_queue.observe(time_in_queue);
+        envelope.value
+    }
+}
+
+/// Message together with its metadata. The depth guard ensures that the channel depth is decreased
+/// however the message leaves the channel (including the receiver being dropped).
+struct Envelope<T> {
+    value: T,
+    state: EnvelopeState,
+}
+
+/// Metadata of a message enqueued to a channel.
+struct Enqueued {
+    at: Instant,
+    _depth: GaugeGuard<u64>,
+}
+
+enum EnvelopeState {
+    Enqueued(Enqueued),
+    /// Metadata set by the sender once a blocking send succeeds, so that blocked senders contribute
+    /// neither to the channel depth, nor to the time in queue. The slot is shared with the sender, so the depth
+    /// is decreased only after both the sender and the message release it (the message may be received
+    /// before the metadata is set).
+    Deferred(Arc<OnceLock<Enqueued>>),
+}
+
+/// Creates an instrumented unbounded channel. This is an instrumented version of [`std::sync::mpsc::channel()`].
+pub fn channel<T>(metrics: &ChannelMetrics) -> (Sender<T>, Receiver<T>) {
+    let metrics = Arc::new(metrics.clone());
+    let (inner_sender, inner_receiver) = std_mpsc::channel();
+    let sender = Sender {
+        inner: inner_sender,
+        metrics: metrics.clone(),
+    };
+    let receiver = Receiver {
+        inner: inner_receiver,
+        metrics,
+    };
+    (sender, receiver)
+}
+
+/// Creates an instrumented bounded channel. This is an instrumented version of [`std::sync::mpsc::sync_channel()`].
+pub fn sync_channel<T>(bound: usize, metrics: &ChannelMetrics) -> (SyncSender<T>, Receiver<T>) {
+    let metrics = Arc::new(metrics.clone());
+    let (inner_sender, inner_receiver) = std_mpsc::sync_channel(bound);
+    let sender = SyncSender {
+        inner: inner_sender,
+        metrics: metrics.clone(),
+    };
+    let receiver = Receiver {
+        inner: inner_receiver,
+        metrics,
+    };
+    (sender, receiver)
+}
+
+/// Sending half of an instrumented unbounded channel created with [`channel()`].
+pub struct Sender<T> {
+    inner: std_mpsc::Sender<Envelope<T>>,
+    metrics: Arc<ChannelMetrics>,
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Sends a value to the channel.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the receiving half of the channel is dropped.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.inner
+            .send(self.metrics.wrap(value))
+            .map_err(|err| SendError(err.0.value))?;
+        self.metrics.sent.inc();
+        Ok(())
+    }
+}
+
+/// Sending half of an instrumented bounded channel created with [`sync_channel()`].
+pub struct SyncSender<T> {
+    inner: std_mpsc::SyncSender<Envelope<T>>,
+    metrics: Arc<ChannelMetrics>,
+}
+
+impl<T> fmt::Debug for SyncSender<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.debug_struct("SyncSender").finish_non_exhaustive()
+    }
+}
+
+impl<T> Clone for SyncSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+impl<T> SyncSender<T> {
+    /// Sends a value to the channel, blocking if the channel is full. The blocking time is recorded
+    /// in [`ChannelMetrics::send_blocked`]. A blocked message is included into [`ChannelMetrics::depth`]
+    /// and [`ChannelMetrics::time_in_queue`] only after it's sent (same as for `tokio` channels).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the receiving half of the channel is dropped.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        match self.inner.try_send(self.metrics.wrap(value)) {
+            Ok(()) => {
+                self.metrics.sent.inc();
+                Ok(())
+            }
+            Err(std_mpsc::TrySendError::Full(envelope)) => {
+                // Drops the acquired metadata so that the depth doesn't include blocked senders.
+                self.send_blocking(envelope.value)
+            }
+            Err(std_mpsc::TrySendError::Disconnected(envelope)) => Err(SendError(envelope.value)),
+        }
+    }
+
+    /// Sends a value to the full channel, blocking until there is capacity.
+    fn send_blocking(&self, value: T) -> Result<(), SendError<T>> {
+        let started_at = Instant::now();
+        let slot = Arc::new(OnceLock::new());
+        let envelope = Envelope {
+            value,
+            state: EnvelopeState::Deferred(slot.clone()),
+        };
+        let result = self.inner.send(envelope);
+        self.metrics.send_blocked.observe(started_at.elapsed());
+        result.map_err(|err| SendError(err.0.value))?;
+        slot.get_or_init(|| self.metrics.enqueued());
+        self.metrics.sent.inc();
+        Ok(())
+    }
+
+    /// Attempts to send a value to the channel without blocking.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the channel is full or the receiving half of the channel is dropped.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.inner
+            .try_send(self.metrics.wrap(value))
+            .map_err(|err| match err {
+                std_mpsc::TrySendError::Full(envelope) => TrySendError::Full(envelope.value),
+                std_mpsc::TrySendError::Disconnected(envelope) => {
+                    TrySendError::Disconnected(envelope.value)
+                }
+            })?;
+        self.metrics.sent.inc();
+        Ok(())
+    }
+}
+
+/// Receiving half of an instrumented channel created with [`channel()`] or [`sync_channel()`].
+pub struct Receiver<T> {
+    inner: std_mpsc::Receiver<Envelope<T>>,
+    metrics: Arc<ChannelMetrics>,
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Blocks until a value is received from the channel.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if all sending halves of the channel are dropped and the channel is empty.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let envelope = self.inner.recv()?;
+        Ok(self.metrics.unwrap(envelope))
+    }
+
+    /// Attempts to receive a value from the channel without blocking.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the channel is empty or all sending halves of the channel are dropped.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let envelope = self.inner.try_recv()?;
+        Ok(self.metrics.unwrap(envelope))
+    }
+
+    /// Blocks until a value is received from the channel or the timeout expires.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the timeout expires or all sending halves of the channel are dropped.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let envelope = self.inner.recv_timeout(timeout)?;
+        Ok(self.metrics.unwrap(envelope))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Barrier, thread};
+
+    use super::*;
+    use crate::{Format, Registry};
+
+    #[test]
+    fn instrumenting_unbounded_channel() {
+        let metrics = ChannelMetrics::default();
+        let (sender, receiver) = channel(&metrics);
+        sender.send(1).unwrap();
+        sender.clone().send(2).unwrap();
+        assert_eq!(metrics.depth.get(), 2);
+        assert_eq!(metrics.sent.get(), 2);
+
+        assert_eq!(receiver.recv().unwrap(), 1);
+        assert_eq!(metrics.depth.get(), 1);
+        assert_eq!(metrics.received.get(), 1);
+
+        // Dropping the receiver should drop buffered messages and reset the depth.
+        drop(receiver);
+        assert_eq!(metrics.depth.get(), 0);
+        let err = sender.send(3).unwrap_err();
+        assert_eq!(err.0, 3);
+        assert_eq!(metrics.depth.get(), 0);
+        assert_eq!(metrics.sent.get(), 2);
+
+        let mut registry = Registry::empty();
+        registry.register_metrics(&metrics);
+        let mut buffer = String::new();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        let lines: Vec<_> = buffer.lines().collect();
+        assert!(lines.contains(&"depth 0"), "{lines:#?}");
+        assert!(lines.contains(&"sent_total 2"));
+        assert!(lines.contains(&"received_total 1"));
+        assert!(lines.contains(&"time_in_queue_seconds_count 1"));
+        assert!(lines.contains(&"send_blocked_seconds_count 0"));
+    }
+
+    #[test]
+    fn instrumenting_bounded_channel() {
+        let metrics = ChannelMetrics::default();
+        let (sender, receiver) = sync_channel(1, &metrics);
+        sender.try_send(1).unwrap();
+        let err = sender.try_send(2).unwrap_err();
+        assert!(matches!(err, TrySendError::Full(2)), "{err:?}");
+        assert_eq!(metrics.depth.get(), 1);
+
+        let barrier = Arc::new(Barrier::new(2));
+        let sender_thread = thread::spawn({
+            let barrier = barrier.clone();
+            move || {
+                barrier.wait();
+                // Uses the blocking path directly, so that the test doesn't depend on thread scheduling.
+                sender.send_blocking(2)
+            }
+        });
+        barrier.wait();
+        // The blocked message is not included into the depth.
+        assert_eq!(metrics.depth.get(), 1);
+        assert_eq!(receiver.recv().unwrap(), 1);
+        sender_thread.join().unwrap().unwrap();
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(1)).unwrap(), 2);
+        assert!(matches!(
+            receiver.try_recv(),
+            Err(TryRecvError::Disconnected)
+        ));
+
+        assert_eq!(metrics.depth.get(), 0);
+        assert_eq!(metrics.sent.get(), 2);
+        assert_eq!(metrics.received.get(), 2);
+
+        let mut registry = Registry::empty();
+        registry.register_metrics(&metrics);
+        let mut buffer = String::new();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        let lines: Vec<_> = buffer.lines().collect();
+        assert!(
+            lines.contains(&"time_in_queue_seconds_count 2"),
+            "{lines:#?}"
+        );
+        assert!(lines.contains(&"send_blocked_seconds_count 1"));
+    }
+}
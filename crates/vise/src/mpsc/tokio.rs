@@ -0,0 +1,286 @@
+//! Instrumented versions of [`tokio::sync::mpsc`] channels.
+
+use std::{fmt, sync::Arc, time::Instant};
+
+use ::tokio::sync::mpsc as tokio_mpsc;
+pub use ::tokio::sync::mpsc::error::{SendError, TryRecvError, TrySendError};
+
+use super::{ChannelMetrics, Envelope};
+
+/// Creates an instrumented bounded channel. This is an instrumented version of
+/// [`tokio::sync::mpsc::channel()`](::tokio::sync::mpsc::channel()).
+///
+/// # Panics
+///
+/// Panics if `buffer` is 0, same as the wrapped function.
+pub fn channel<T>(buffer: usize, metrics: &ChannelMetrics) -> (Sender<T>, Receiver<T>) {
+    let metrics = Arc::new(metrics.clone());
+    let (inner_sender, inner_receiver) = tokio_mpsc::channel(buffer);
+    let sender = Sender {
+        inner: inner_sender,
+        metrics: metrics.clone(),
+    };
+    let receiver = Receiver {
+        inner: inner_receiver,
+        metrics,
+    };
+    (sender, receiver)
+}
+
+/// Creates an instrumented unbounded channel. This is an instrumented version of
+/// [`tokio::sync::mpsc::unbounded_channel()`](::tokio::sync::mpsc::unbounded_channel()).
+pub fn unbounded_channel<T>(
+    metrics: &ChannelMetrics,
+) -> (UnboundedSender<T>, UnboundedReceiver<T>) {
+    let metrics = Arc::new(metrics.clone());
+    let (inner_sender, inner_receiver) = tokio_mpsc::unbounded_channel();
+    let sender = UnboundedSender {
+        inner: inner_sender,
+        metrics: metrics.clone(),
+    };
+    let receiver = UnboundedReceiver {
+        inner: inner_receiver,
+        metrics,
+    };
+    (sender, receiver)
+}
+
+/// Sending half of an instrumented bounded channel created with [`channel()`].
+pub struct Sender<T> {
+    inner: tokio_mpsc::Sender<Envelope<T>>,
+    metrics: Arc<ChannelMetrics>,
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Sends a value to the channel, waiting until there is capacity. The waiting time is recorded
+    /// in [`ChannelMetrics::send_blocked`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the receiving half of the channel is closed.
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let permit = match self.inner.try_reserve() {
+            Ok(permit) => permit,
+            Err(TrySendError::Closed(())) => return Err(SendError(value)),
+            Err(TrySendError::Full(())) => {
+                let started_at = Instant::now();
+                let permit = self.inner.reserve().await;
+                self.metrics.send_blocked.observe(started_at.elapsed());
+                match permit {
+                    Ok(permit) => permit,
+                    Err(_) => return Err(SendError(value)),
+                }
+            }
+        };
+        permit.send(self.metrics.wrap(value));
+        self.metrics.sent.inc();
+        Ok(())
+    }
+
+    /// Attempts to send a value to the channel without waiting.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the channel is full or the receiving half of the channel is closed.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        // Reserve capacity first, so that the depth guard is only taken for a message that will be sent.
+        let permit = match self.inner.try_reserve() {
+            Ok(permit) => permit,
+            Err(TrySendError::Full(())) => return Err(TrySendError::Full(value)),
+            Err(TrySendError::Closed(())) => return Err(TrySendError::Closed(value)),
+        };
+        permit.send(self.metrics.wrap(value));
+        self.metrics.sent.inc();
+        Ok(())
+    }
+
+    /// Checks whether the receiving half of the channel is closed.
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+/// Receiving half of an instrumented bounded channel created with [`channel()`].
+pub struct Receiver<T> {
+    inner: tokio_mpsc::Receiver<Envelope<T>>,
+    metrics: Arc<ChannelMetrics>,
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Receives the next value from the channel. Returns `None` if the channel is closed and empty.
+    pub async fn recv(&mut self) -> Option<T> {
+        let envelope = self.inner.recv().await?;
+        Some(self.metrics.unwrap(envelope))
+    }
+
+    /// Attempts to receive the next value from the channel without waiting.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the channel is empty, or is closed and empty.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let envelope = self.inner.try_recv()?;
+        Ok(self.metrics.unwrap(envelope))
+    }
+
+    /// Closes the channel without dropping it. Buffered values can still be received.
+    pub fn close(&mut self) {
+        self.inner.close();
+    }
+}
+
+/// Sending half of an instrumented unbounded channel created with [`unbounded_channel()`].
+pub struct UnboundedSender<T> {
+    inner: tokio_mpsc::UnboundedSender<Envelope<T>>,
+    metrics: Arc<ChannelMetrics>,
+}
+
+impl<T> fmt::Debug for UnboundedSender<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("UnboundedSender")
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Clone for UnboundedSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+impl<T> UnboundedSender<T> {
+    /// Sends a value to the channel.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the receiving half of the channel is closed.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.inner
+            .send(self.metrics.wrap(value))
+            .map_err(|err| SendError(err.0.value))?;
+        self.metrics.sent.inc();
+        Ok(())
+    }
+
+    /// Checks whether the receiving half of the channel is closed.
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+/// Receiving half of an instrumented unbounded channel created with [`unbounded_channel()`].
+pub struct UnboundedReceiver<T> {
+    inner: tokio_mpsc::UnboundedReceiver<Envelope<T>>,
+    metrics: Arc<ChannelMetrics>,
+}
+
+impl<T> fmt::Debug for UnboundedReceiver<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("UnboundedReceiver")
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> UnboundedReceiver<T> {
+    /// Receives the next value from the channel. Returns `None` if the channel is closed and empty.
+    pub async fn recv(&mut self) -> Option<T> {
+        let envelope = self.inner.recv().await?;
+        Some(self.metrics.unwrap(envelope))
+    }
+
+    /// Attempts to receive the next value from the channel without waiting.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the channel is empty, or is closed and empty.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let envelope = self.inner.try_recv()?;
+        Ok(self.metrics.unwrap(envelope))
+    }
+
+    /// Closes the channel without dropping it. Buffered values can still be received.
+    pub fn close(&mut self) {
+        self.inner.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn instrumenting_bounded_channel() {
+        let metrics = ChannelMetrics::default();
+        let (sender, mut receiver) = channel(1, &metrics);
+        sender.send(1).await.unwrap();
+        let err = sender.try_send(2).unwrap_err();
+        assert!(matches!(err, TrySendError::Full(2)), "{err:?}");
+        assert_eq!(metrics.depth.get(), 1);
+
+        let sender_task = tokio::spawn({
+            let sender = sender.clone();
+            async move { sender.send(2).await }
+        });
+        tokio::task::yield_now().await; // lets the sender task block on a full channel
+        assert_eq!(metrics.depth.get(), 1);
+        assert_eq!(receiver.recv().await, Some(1));
+        sender_task.await.unwrap().unwrap();
+        assert_eq!(receiver.recv().await, Some(2));
+
+        assert_eq!(metrics.depth.get(), 0);
+        assert_eq!(metrics.sent.get(), 2);
+        assert_eq!(metrics.received.get(), 2);
+
+        sender.send(3).await.unwrap();
+        drop(receiver);
+        assert_eq!(metrics.depth.get(), 0);
+        assert!(sender.is_closed());
+        let err = sender.send(4).await.unwrap_err();
+        assert_eq!(err.0, 4);
+    }
+
+    #[tokio::test]
+    async fn instrumenting_unbounded_channel() {
+        let metrics = ChannelMetrics::default();
+        let (sender, mut receiver) = unbounded_channel(&metrics);
+        sender.send("a").unwrap();
+        sender.send("b").unwrap();
+        assert_eq!(metrics.depth.get(), 2);
+
+        assert_eq!(receiver.recv().await, Some("a"));
+        assert_eq!(receiver.try_recv().unwrap(), "b");
+        assert_eq!(receiver.try_recv().unwrap_err(), TryRecvError::Empty);
+        receiver.close();
+        assert_eq!(sender.send("c").unwrap_err().0, "c");
+
+        assert_eq!(metrics.depth.get(), 0);
+        assert_eq!(metrics.sent.get(), 2);
+        assert_eq!(metrics.received.get(), 2);
+    }
+}
@@ -0,0 +1,152 @@
+//! Helpers for metric and label names shared by bridges and filters.
+
+#[cfg(any(
+    feature = "metrics-bridge",
+    feature = "opentelemetry-bridge",
+    feature = "tracing-layer"
+))]
+use std::borrow::Cow;
+
+#[cfg(any(feature = "metrics-bridge", feature = "opentelemetry-bridge"))]
+use crate::Unit;
+
+/// Replaces chars not allowed in Prometheus metric names with underscores.
+#[cfg(any(feature = "metrics-bridge", feature = "opentelemetry-bridge"))]
+pub(crate) fn sanitize_metric_name(name: &str) -> String {
+    sanitize(name, |ch| {
+        ch.is_ascii_alphanumeric() || ch == '_' || ch == ':'
+    })
+    .into_owned()
+}
+
+/// Replaces chars not allowed in Prometheus label names with underscores.
+#[cfg(any(
+    feature = "metrics-bridge",
+    feature = "opentelemetry-bridge",
+    feature = "tracing-layer"
+))]
+pub(crate) fn sanitize_label_name(name: &str) -> Cow<'_, str> {
+    sanitize(name, |ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+#[cfg(any(
+    feature = "metrics-bridge",
+    feature = "opentelemetry-bridge",
+    feature = "tracing-layer"
+))]
+fn sanitize(name: &str, is_allowed: impl Fn(char) -> bool) -> Cow<'_, str> {
+    let starts_with_digit = name.starts_with(|ch: char| ch.is_ascii_digit());
+    if !starts_with_digit && name.chars().all(&is_allowed) {
+        return Cow::Borrowed(name);
+    }
+
+    let mut sanitized = String::with_capacity(name.len() + 1);
+    if starts_with_digit {
+        sanitized.push('_');
+    }
+    sanitized.extend(name.chars().map(|ch| if is_allowed(ch) { ch } else { '_' }));
+    Cow::Owned(sanitized)
+}
+
+/// Strips the `_total` suffix from a counter name; the suffix is added back on encoding.
+#[cfg(any(feature = "metrics-bridge", feature = "opentelemetry-bridge"))]
+pub(crate) fn counter_name(name: &str) -> &str {
+    name.strip_suffix("_total").unwrap_or(name)
+}
+
+/// Returns the unit for a metric unless the metric name already ends with the unit suffix
+/// (in which case, specifying the unit would duplicate the suffix).
+#[cfg(any(feature = "metrics-bridge", feature = "opentelemetry-bridge"))]
+pub(crate) fn unit_for_name(unit: Unit, name: &str) -> Option<Unit> {
+    let has_suffix = name
+        .strip_suffix(unit.as_str())
+        .is_some_and(|name| name.ends_with('_'));
+    (!has_suffix).then_some(unit)
+}
+
+/// Matches `text` against a glob `pattern` supporting `*` (any sequence of chars) and `?` (a single char)
+/// wildcards.
+pub(crate) fn glob_matches(pattern: &str, text: &str) -> bool {
+    let (pattern, text) = (pattern.as_bytes(), text.as_bytes());
+    let (mut pattern_pos, mut text_pos) = (0, 0);
+    // Position of the last encountered `*` in the pattern and the text position it was matched at.
+    let mut backtrack = None::<(usize, usize)>;
+    while text_pos < text.len() {
+        match pattern.get(pattern_pos) {
+            Some(b'*') => {
+                backtrack = Some((pattern_pos, text_pos));
+                pattern_pos += 1;
+            }
+            Some(&ch) if ch == b'?' || ch == text[text_pos] => {
+                pattern_pos += 1;
+                text_pos += 1;
+            }
+            _ => {
+                // Extend the match of the last `*` by one char and retry.
+                let Some((star_pos, star_text_pos)) = backtrack else {
+                    return false;
+                };
+                backtrack = Some((star_pos, star_text_pos + 1));
+                pattern_pos = star_pos + 1;
+                text_pos = star_text_pos + 1;
+            }
+        }
+    }
+    pattern[pattern_pos..].iter().all(|&ch| ch == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matching() {
+        assert!(glob_matches("", ""));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("*", "my_app_requests"));
+        assert!(glob_matches("my_app_*", "my_app_requests"));
+        assert!(glob_matches("my_app_*", "my_app_"));
+        assert!(!glob_matches("my_app_*", "my_app"));
+        assert!(glob_matches("*_requests", "my_app_requests"));
+        assert!(glob_matches("my_*_requests", "my_app_http_requests"));
+        assert!(glob_matches("my_*_*s", "my_app_requests"));
+        assert!(glob_matches("my_ap?_requests", "my_app_requests"));
+        assert!(!glob_matches("my_ap?_requests", "my_ap_requests"));
+        assert!(!glob_matches("my_app", "my_app_requests"));
+        assert!(!glob_matches("*_errors", "my_app_requests"));
+        assert!(!glob_matches("*_size", "db_size_max"));
+        assert!(!glob_matches("http_*_size", "http_request_latency"));
+    }
+
+    #[cfg(any(feature = "metrics-bridge", feature = "opentelemetry-bridge"))]
+    #[test]
+    fn sanitizing_names() {
+        assert_eq!(sanitize_metric_name("http.requests"), "http_requests");
+        assert_eq!(
+            sanitize_metric_name("rpc:calls-in-flight"),
+            "rpc:calls_in_flight"
+        );
+        assert_eq!(sanitize_metric_name("2xx_responses"), "_2xx_responses");
+        assert_eq!(sanitize_label_name("method"), "method");
+        assert_eq!(sanitize_label_name("http.status_code"), "http_status_code");
+        assert_eq!(sanitize_label_name("rpc:method"), "rpc_method");
+        assert_eq!(sanitize_label_name("2xx"), "_2xx");
+        assert_eq!(counter_name("requests_total"), "requests");
+        assert_eq!(counter_name("requests"), "requests");
+    }
+
+    #[cfg(any(feature = "metrics-bridge", feature = "opentelemetry-bridge"))]
+    #[test]
+    fn selecting_units() {
+        assert!(matches!(
+            unit_for_name(Unit::Seconds, "latency"),
+            Some(Unit::Seconds)
+        ));
+        assert!(unit_for_name(Unit::Seconds, "latency_seconds").is_none());
+        assert!(unit_for_name(Unit::Bytes, "sent_bytes").is_none());
+        assert!(matches!(
+            unit_for_name(Unit::Bytes, "sentbytes"),
+            Some(Unit::Bytes)
+        ));
+    }
+}
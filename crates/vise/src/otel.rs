@@ -0,0 +1,825 @@
+//! Bridge for the [`opentelemetry`] metrics API.
+
+use std::{
+    any::Any,
+    borrow::Cow,
+    collections::BTreeMap,
+    fmt,
+    sync::{atomic::AtomicU64, Arc, Mutex, RwLock},
+};
+
+use opentelemetry::{
+    metrics::{
+        self as otel, AsyncInstrument, AsyncInstrumentBuilder, Callback, HistogramBuilder,
+        InstrumentBuilder, InstrumentProvider, Meter, MeterProvider, SyncInstrument,
+    },
+    InstrumentationScope, KeyValue,
+};
+use prometheus_client::{
+    encoding::EncodeMetric,
+    metrics::{MetricType, TypedMetric},
+};
+
+use crate::{
+    builder::BuildMetric,
+    descriptors::{MetricGroupDescriptor, Stability},
+    encoding::GroupedMetric,
+    names::{counter_name, sanitize_label_name, sanitize_metric_name, unit_for_name},
+    registry::MetricsVisitor,
+    traits::HistogramValue,
+    wrappers::{Family, Gauge, Histogram},
+    BeforeScrapeError, Buckets, Collector, Counter, MetricBuilder, Metrics, Unit,
+};
+
+type Labels = Vec<(String, String)>;
+
+/// Default histogram boundaries used by the OpenTelemetry SDK. Used for histograms
+/// without explicit boundaries, unless they measure seconds (in which case [`Buckets::LATENCIES`] are used).
+const DEFAULT_BUCKETS: Buckets = Buckets::values(&[
+    0.0, 5.0, 10.0, 25.0, 50.0, 75.0, 100.0, 250.0, 500.0, 750.0, 1_000.0, 2_500.0, 5_000.0,
+    7_500.0, 10_000.0,
+]);
+
+/// Descriptor of an OpenTelemetry instrumentation scope, similar to [`MetricGroupDescriptor`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct OtelScopeDescriptor {
+    /// Name of the scope, usually the name of the instrumented library.
+    pub name: String,
+    /// Version of the scope, if specified.
+    pub version: Option<String>,
+    /// Schema URL of the scope, if specified.
+    pub schema_url: Option<String>,
+    /// Descriptors of instruments created in the scope, in the creation order.
+    pub metrics: Vec<OtelMetricDescriptor>,
+}
+
+/// Descriptor of an OpenTelemetry instrument, similar to [`MetricDescriptor`](crate::descriptors::MetricDescriptor).
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct OtelMetricDescriptor {
+    /// Name of the metric **excluding** the unit suffix.
+    pub name: String,
+    /// Type of the metric.
+    pub metric_type: MetricType,
+    /// Measurement unit of the metric, if any.
+    pub unit: Option<Unit>,
+    /// Help for the metric exported to Prometheus.
+    pub help: &'static str,
+}
+
+/// Type-erased instrument stored in [`OtelMeterProvider`].
+trait DynInstrument: Send + Sync {
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    fn metric_type(&self) -> MetricType;
+
+    /// Collects the instrument into a metric. For observable instruments, this invokes their callbacks.
+    fn collect(&self) -> Box<dyn GroupedMetric>;
+}
+
+impl<M> DynInstrument for Family<Labels, M>
+where
+    M: BuildMetric + EncodeMetric + TypedMetric + fmt::Debug + Send + Sync,
+    M::Builder: fmt::Debug + Send + Sync,
+{
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn metric_type(&self) -> MetricType {
+        M::TYPE
+    }
+
+    fn collect(&self) -> Box<dyn GroupedMetric> {
+        Box::new(self.clone())
+    }
+}
+
+/// Instrument with values reported by callbacks. Values are collected into a fresh metric on each scrape.
+struct Observable<M: BuildMetric, T> {
+    builder: M::Builder,
+    record: fn(&M, T),
+    callbacks: Vec<(Arc<Labels>, Callback<T>)>,
+}
+
+impl<M, T> DynInstrument for Observable<M, T>
+where
+    M: BuildMetric + EncodeMetric + TypedMetric + fmt::Debug + Send + Sync,
+    M::Builder: fmt::Debug + Send + Sync,
+    T: 'static,
+{
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn metric_type(&self) -> MetricType {
+        M::TYPE
+    }
+
+    fn collect(&self) -> Box<dyn GroupedMetric> {
+        let family = Family::new(self.builder, ());
+        for (scope_labels, callback) in &self.callbacks {
+            callback(&Observer {
+                family: &family,
+                scope_labels,
+                record: self.record,
+            });
+        }
+        Box::new(family)
+    }
+}
+
+struct Observer<'a, M: BuildMetric, T> {
+    family: &'a Family<Labels, M>,
+    scope_labels: &'a Labels,
+    record: fn(&M, T),
+}
+
+impl<M, T> AsyncInstrument<T> for Observer<'_, M, T>
+where
+    M: BuildMetric + Send + Sync,
+    M::Builder: Send + Sync,
+{
+    fn observe(&self, measurement: T, attributes: &[KeyValue]) {
+        (self.record)(
+            &self.family[&labels(self.scope_labels, attributes)],
+            measurement,
+        );
+    }
+}
+
+/// Synchronous instrument returned to the OpenTelemetry API. If the instrument conflicts
+/// with a previously created one, measurements are ignored.
+struct Recorder<M: BuildMetric, T> {
+    family: Option<Family<Labels, M>>,
+    scope_labels: Arc<Labels>,
+    record: fn(&M, T),
+}
+
+impl<M, T> SyncInstrument<T> for Recorder<M, T>
+where
+    M: BuildMetric + Send + Sync,
+    M::Builder: Send + Sync,
+{
+    fn measure(&self, measurement: T, attributes: &[KeyValue]) {
+        if let Some(family) = &self.family {
+            (self.record)(
+                &family[&labels(&self.scope_labels, attributes)],
+                measurement,
+            );
+        }
+    }
+}
+
+struct InstrumentEntry {
+    help: &'static str,
+    unit: Option<Unit>,
+    instrument: Box<dyn DynInstrument>,
+}
+
+#[derive(Default)]
+struct ProviderInner {
+    instruments: RwLock<BTreeMap<String, InstrumentEntry>>,
+    scopes: Mutex<Vec<OtelScopeDescriptor>>,
+}
+
+impl fmt::Debug for ProviderInner {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let instruments = self.instruments.read().expect("instruments are poisoned");
+        formatter
+            .debug_struct("ProviderInner")
+            .field("instruments", &instruments.keys().collect::<Vec<_>>())
+            .field("scopes", &self.scopes)
+            .finish()
+    }
+}
+
+/// OpenTelemetry [`MeterProvider`] exporting metrics via `vise`.
+///
+/// Instruments are mapped to `vise` metrics as follows:
+///
+/// - Counters are mapped to [`Counter`]s.
+/// - Up-down counters and gauges are mapped to [`Gauge`]s.
+/// - Histograms are mapped to [`Histogram`]s. Explicit bucket boundaries are respected;
+///   otherwise, [`Buckets::LATENCIES`] are used for histograms measuring seconds, and default OpenTelemetry
+///   boundaries for other histograms.
+/// - Observable instruments are mapped to the same metrics as their synchronous counterparts;
+///   their callbacks are invoked on each scrape, similar to a [`Collector`].
+///
+/// Attributes are exported as labels, together with the `otel_scope_name` and `otel_scope_version` labels
+/// identifying the instrumentation scope. Instrument names are sanitized to conform to Prometheus naming rules.
+/// Units are only mapped for seconds (`s`) and bytes (`By`). If an instrument conflicts with a previously created
+/// instrument with the same name (e.g., has another type), measurements for it are ignored.
+///
+/// Metrics can be registered in a [`Registry`](crate::Registry) manually, or globally
+/// using [`Self::register_collector()`]. Instrumentation scopes are described by [`Self::scopes()`].
+///
+/// # Examples
+///
+/// ```
+/// use opentelemetry::{metrics::MeterProvider, KeyValue};
+/// use vise::OtelMeterProvider;
+///
+/// let provider = OtelMeterProvider::default();
+/// provider.register_collector().expect("OpenTelemetry metrics are already collected");
+/// opentelemetry::global::set_meter_provider(provider);
+///
+/// // Instrumented library code:
+/// let meter = opentelemetry::global::meter("my_library");
+/// let counter = meter.u64_counter("requests").build();
+/// counter.add(1, &[KeyValue::new("method", "eth_call")]);
+/// // Will be exported as
+/// // requests_total{otel_scope_name="my_library",method="eth_call"} 1
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct OtelMeterProvider {
+    inner: Arc<ProviderInner>,
+}
+
+impl OtelMeterProvider {
+    /// Returns descriptors of instrumentation scopes for which meters were created, in the creation order.
+    #[allow(clippy::missing_panics_doc)] // the mutex is never poisoned
+    pub fn scopes(&self) -> Vec<OtelScopeDescriptor> {
+        self.inner
+            .scopes
+            .lock()
+            .expect("scopes are poisoned")
+            .clone()
+    }
+
+    /// Registers a collector exporting metrics of this provider in [`METRICS_REGISTRATIONS`](crate::METRICS_REGISTRATIONS).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the collector is already registered.
+    pub fn register_collector(&self) -> Result<(), BeforeScrapeError> {
+        let this = self.clone();
+        PROVIDER_COLLECTOR.before_scrape(move || this.clone())
+    }
+
+    fn scope_index(&self, scope: &InstrumentationScope) -> usize {
+        let mut scopes = self.inner.scopes.lock().expect("scopes are poisoned");
+        let existing_idx = scopes.iter().position(|descriptor| {
+            descriptor.name == scope.name()
+                && descriptor.version.as_deref() == scope.version()
+                && descriptor.schema_url.as_deref() == scope.schema_url()
+        });
+        existing_idx.unwrap_or_else(|| {
+            scopes.push(OtelScopeDescriptor {
+                name: scope.name().to_owned(),
+                version: scope.version().map(str::to_owned),
+                schema_url: scope.schema_url().map(str::to_owned),
+                metrics: vec![],
+            });
+            scopes.len() - 1
+        })
+    }
+}
+
+impl MeterProvider for OtelMeterProvider {
+    fn meter_with_scope(&self, scope: InstrumentationScope) -> Meter {
+        let mut scope_labels = vec![("otel_scope_name".to_owned(), scope.name().to_owned())];
+        if let Some(version) = scope.version() {
+            scope_labels.push(("otel_scope_version".to_owned(), version.to_owned()));
+        }
+        Meter::new(Arc::new(ScopedInstruments {
+            provider: self.clone(),
+            scope_idx: self.scope_index(&scope),
+            scope_labels: Arc::new(scope_labels),
+        }))
+    }
+}
+
+/// Basic information about an instrument.
+#[derive(Clone, Copy)]
+struct InstrumentInfo<'a> {
+    name: &'a str,
+    description: Option<&'a str>,
+    unit: Option<&'a str>,
+}
+
+impl<'a, T> From<&'a InstrumentBuilder<'_, T>> for InstrumentInfo<'a> {
+    fn from(builder: &'a InstrumentBuilder<'_, T>) -> Self {
+        Self {
+            name: &builder.name,
+            description: builder.description.as_deref(),
+            unit: builder.unit.as_deref(),
+        }
+    }
+}
+
+impl<'a, T> From<&'a HistogramBuilder<'_, T>> for InstrumentInfo<'a> {
+    fn from(builder: &'a HistogramBuilder<'_, T>) -> Self {
+        Self {
+            name: &builder.name,
+            description: builder.description.as_deref(),
+            unit: builder.unit.as_deref(),
+        }
+    }
+}
+
+impl<'a, I, T> From<&'a AsyncInstrumentBuilder<'_, I, T>> for InstrumentInfo<'a> {
+    fn from(builder: &'a AsyncInstrumentBuilder<'_, I, T>) -> Self {
+        Self {
+            name: &builder.name,
+            description: builder.description.as_deref(),
+            unit: builder.unit.as_deref(),
+        }
+    }
+}
+
+/// Instrument provider for a specific instrumentation scope.
+struct ScopedInstruments {
+    provider: OtelMeterProvider,
+    scope_idx: usize,
+    scope_labels: Arc<Labels>,
+}
+
+impl ScopedInstruments {
+    /// Gets or creates an instrument with the specified name and accesses it. Returns `None` if an instrument
+    /// with the same name but another type is already created.
+    fn access_instrument<I: DynInstrument + 'static, R>(
+        &self,
+        info: InstrumentInfo<'_>,
+        is_counter: bool,
+        create: impl FnOnce(Option<&Unit>) -> I,
+        access: impl FnOnce(&mut I) -> R,
+    ) -> Option<R> {
+        let mut name = sanitize_metric_name(info.name);
+        if is_counter {
+            let len = counter_name(&name).len();
+            name.truncate(len);
+        }
+        let mut instruments = self
+            .provider
+            .inner
+            .instruments
+            .write()
+            .expect("instruments are poisoned");
+        let entry = instruments.entry(name.clone()).or_insert_with(|| {
+            let unit = info.unit.and_then(|unit| convert_unit(unit, &name));
+            let help = info.description.unwrap_or_default();
+            InstrumentEntry {
+                // Leaking is fine since the number of instruments is bounded.
+                help: Box::leak(help.to_owned().into_boxed_str()),
+                instrument: Box::new(create(unit.as_ref())),
+                unit,
+            }
+        });
+        let output = access(entry.instrument.as_any_mut().downcast_mut::<I>()?);
+
+        let mut scopes = self
+            .provider
+            .inner
+            .scopes
+            .lock()
+            .expect("scopes are poisoned");
+        let scope = &mut scopes[self.scope_idx];
+        if !scope.metrics.iter().any(|metric| metric.name == name) {
+            scope.metrics.push(OtelMetricDescriptor {
+                name,
+                metric_type: entry.instrument.metric_type(),
+                unit: entry.unit.clone(),
+                help: entry.help,
+            });
+        }
+        Some(output)
+    }
+
+    fn sync_instrument<M, T>(
+        &self,
+        info: InstrumentInfo<'_>,
+        builder: M::Builder,
+        record: fn(&M, T),
+    ) -> Arc<Recorder<M, T>>
+    where
+        M: BuildMetric + EncodeMetric + TypedMetric + fmt::Debug + Send + Sync,
+        M::Builder: fmt::Debug + Send + Sync,
+    {
+        let is_counter = matches!(M::TYPE, MetricType::Counter);
+        let family = self.access_instrument(
+            info,
+            is_counter,
+            |_| Family::<Labels, M>::new(builder, ()),
+            |family| family.clone(),
+        );
+        Arc::new(Recorder {
+            family,
+            scope_labels: self.scope_labels.clone(),
+            record,
+        })
+    }
+
+    fn histogram<T>(&self, builder: &HistogramBuilder<'_, otel::Histogram<T>>) -> otel::Histogram<T>
+    where
+        T: HistogramValue + Send + Sync,
+        Histogram<T>: BuildMetric<Builder = MetricBuilder<Buckets>> + EncodeMetric + TypedMetric,
+    {
+        let boundaries = builder.boundaries.clone();
+        let family = self.access_instrument(
+            InstrumentInfo::from(builder),
+            false,
+            |unit| {
+                let default_buckets = if matches!(unit, Some(Unit::Seconds)) {
+                    Buckets::LATENCIES
+                } else {
+                    DEFAULT_BUCKETS
+                };
+                let buckets = boundaries
+                    .and_then(custom_buckets)
+                    .unwrap_or(default_buckets);
+                Family::<Labels, Histogram<T>>::new(MetricBuilder::new().with_buckets(buckets), ())
+            },
+            |family| family.clone(),
+        );
+        otel::Histogram::new(Arc::new(Recorder {
+            family,
+            scope_labels: self.scope_labels.clone(),
+            record: Histogram::observe,
+        }))
+    }
+
+    fn observable<M, T>(
+        &self,
+        builder: AsyncInstrumentBuilder<'_, impl Sized, T>,
+        metric_builder: M::Builder,
+        record: fn(&M, T),
+    ) where
+        M: BuildMetric + EncodeMetric + TypedMetric + fmt::Debug + Send + Sync,
+        M::Builder: fmt::Debug + Send + Sync,
+        T: 'static,
+    {
+        let is_counter = matches!(M::TYPE, MetricType::Counter);
+        let info = InstrumentInfo {
+            name: &builder.name,
+            description: builder.description.as_deref(),
+            unit: builder.unit.as_deref(),
+        };
+        let callbacks = builder.callbacks;
+        self.access_instrument(
+            info,
+            is_counter,
+            |_| Observable {
+                builder: metric_builder,
+                record,
+                callbacks: vec![],
+            },
+            |observable: &mut Observable<M, T>| {
+                let scope_labels = &self.scope_labels;
+                let callbacks = callbacks
+                    .into_iter()
+                    .map(|callback| (scope_labels.clone(), callback));
+                observable.callbacks.extend(callbacks);
+            },
+        );
+    }
+}
+
+impl InstrumentProvider for ScopedInstruments {
+    fn u64_counter(
+        &self,
+        builder: InstrumentBuilder<'_, otel::Counter<u64>>,
+    ) -> otel::Counter<u64> {
+        let record: fn(&Counter, u64) = |counter, value| {
+            counter.inc_by(value);
+        };
+        otel::Counter::new(self.sync_instrument((&builder).into(), MetricBuilder::new(), record))
+    }
+
+    fn f64_counter(
+        &self,
+        builder: InstrumentBuilder<'_, otel::Counter<f64>>,
+    ) -> otel::Counter<f64> {
+        let record: fn(&Counter<f64, AtomicU64>, f64) = |counter, value| {
+            counter.inc_by(value);
+        };
+        otel::Counter::new(self.sync_instrument((&builder).into(), MetricBuilder::new(), record))
+    }
+
+    fn u64_observable_counter(
+        &self,
+        builder: AsyncInstrumentBuilder<'_, otel::ObservableCounter<u64>, u64>,
+    ) -> otel::ObservableCounter<u64> {
+        // Observed values are cumulative, so we convert them to increments of a fresh counter.
+        let record: fn(&Counter, u64) = |counter, value| {
+            counter.inc_by(value.saturating_sub(counter.get()));
+        };
+        self.observable(builder, MetricBuilder::new(), record);
+        otel::ObservableCounter::new()
+    }
+
+    fn f64_observable_counter(
+        &self,
+        builder: AsyncInstrumentBuilder<'_, otel::ObservableCounter<f64>, f64>,
+    ) -> otel::ObservableCounter<f64> {
+        let record: fn(&Counter<f64, AtomicU64>, f64) = |counter, value| {
+            let increment = value - counter.get();
+            if increment > 0.0 {
+                counter.inc_by(increment);
+            }
+        };
+        self.observable(builder, MetricBuilder::new(), record);
+        otel::ObservableCounter::new()
+    }
+
+    fn i64_up_down_counter(
+        &self,
+        builder: InstrumentBuilder<'_, otel::UpDownCounter<i64>>,
+    ) -> otel::UpDownCounter<i64> {
+        let record: fn(&Gauge<i64>, i64) = |gauge, value| {
+            gauge.inc_by(value);
+        };
+        otel::UpDownCounter::new(self.sync_instrument(
+            (&builder).into(),
+            MetricBuilder::new(),
+            record,
+        ))
+    }
+
+    fn f64_up_down_counter(
+        &self,
+        builder: InstrumentBuilder<'_, otel::UpDownCounter<f64>>,
+    ) -> otel::UpDownCounter<f64> {
+        let record: fn(&Gauge<f64>, f64) = |gauge, value| {
+            gauge.inc_by(value);
+        };
+        otel::UpDownCounter::new(self.sync_instrument(
+            (&builder).into(),
+            MetricBuilder::new(),
+            record,
+        ))
+    }
+
+    fn i64_observable_up_down_counter(
+        &self,
+        builder: AsyncInstrumentBuilder<'_, otel::ObservableUpDownCounter<i64>, i64>,
+    ) -> otel::ObservableUpDownCounter<i64> {
+        let record: fn(&Gauge<i64>, i64) = |gauge, value| {
+            gauge.set(value);
+        };
+        self.observable(builder, MetricBuilder::new(), record);
+        otel::ObservableUpDownCounter::new()
+    }
+
+    fn f64_observable_up_down_counter(
+        &self,
+        builder: AsyncInstrumentBuilder<'_, otel::ObservableUpDownCounter<f64>, f64>,
+    ) -> otel::ObservableUpDownCounter<f64> {
+        let record: fn(&Gauge<f64>, f64) = |gauge, value| {
+            gauge.set(value);
+        };
+        self.observable(builder, MetricBuilder::new(), record);
+        otel::ObservableUpDownCounter::new()
+    }
+
+    fn u64_gauge(&self, builder: InstrumentBuilder<'_, otel::Gauge<u64>>) -> otel::Gauge<u64> {
+        let record: fn(&Gauge<u64>, u64) = |gauge, value| {
+            gauge.set(value);
+        };
+        otel::Gauge::new(self.sync_instrument((&builder).into(), MetricBuilder::new(), record))
+    }
+
+    fn f64_gauge(&self, builder: InstrumentBuilder<'_, otel::Gauge<f64>>) -> otel::Gauge<f64> {
+        let record: fn(&Gauge<f64>, f64) = |gauge, value| {
+            gauge.set(value);
+        };
+        otel::Gauge::new(self.sync_instrument((&builder).into(), MetricBuilder::new(), record))
+    }
+
+    fn i64_gauge(&self, builder: InstrumentBuilder<'_, otel::Gauge<i64>>) -> otel::Gauge<i64> {
+        let record: fn(&Gauge<i64>, i64) = |gauge, value| {
+            gauge.set(value);
+        };
+        otel::Gauge::new(self.sync_instrument((&builder).into(), MetricBuilder::new(), record))
+    }
+
+    fn u64_observable_gauge(
+        &self,
+        builder: AsyncInstrumentBuilder<'_, otel::ObservableGauge<u64>, u64>,
+    ) -> otel::ObservableGauge<u64> {
+        let record: fn(&Gauge<u64>, u64) = |gauge, value| {
+            gauge.set(value);
+        };
+        self.observable(builder, MetricBuilder::new(), record);
+        otel::ObservableGauge::new()
+    }
+
+    fn i64_observable_gauge(
+        &self,
+        builder: AsyncInstrumentBuilder<'_, otel::ObservableGauge<i64>, i64>,
+    ) -> otel::ObservableGauge<i64> {
+        let record: fn(&Gauge<i64>, i64) = |gauge, value| {
+            gauge.set(value);
+        };
+        self.observable(builder, MetricBuilder::new(), record);
+        otel::ObservableGauge::new()
+    }
+
+    fn f64_observable_gauge(
+        &self,
+        builder: AsyncInstrumentBuilder<'_, otel::ObservableGauge<f64>, f64>,
+    ) -> otel::ObservableGauge<f64> {
+        let record: fn(&Gauge<f64>, f64) = |gauge, value| {
+            gauge.set(value);
+        };
+        self.observable(builder, MetricBuilder::new(), record);
+        otel::ObservableGauge::new()
+    }
+
+    fn f64_histogram(
+        &self,
+        builder: HistogramBuilder<'_, otel::Histogram<f64>>,
+    ) -> otel::Histogram<f64> {
+        self.histogram(&builder)
+    }
+
+    fn u64_histogram(
+        &self,
+        builder: HistogramBuilder<'_, otel::Histogram<u64>>,
+    ) -> otel::Histogram<u64> {
+        self.histogram(&builder)
+    }
+}
+
+impl Metrics for OtelMeterProvider {
+    const DESCRIPTOR: MetricGroupDescriptor = MetricGroupDescriptor {
+        crate_name: "vise",
+        crate_version: env!("CARGO_PKG_VERSION"),
+        module_path: module_path!(),
+        name: "OtelMeterProvider",
+        line: line!(),
+        labels: &[],
+        metrics: &[],
+        nested_groups: &[],
+    };
+
+    fn visit_metrics(&self, visitor: &mut dyn MetricsVisitor) {
+        let instruments = self
+            .inner
+            .instruments
+            .read()
+            .expect("instruments are poisoned");
+        for (name, entry) in instruments.iter() {
+            visitor.visit_metric(
+                Cow::Owned(name.clone()),
+                entry.help,
+                entry.unit.clone(),
+                Stability::Stable,
+                false,
+                entry.instrument.collect(),
+            );
+        }
+    }
+}
+
+#[crate::register]
+#[metrics(crate = crate)]
+static PROVIDER_COLLECTOR: Collector<OtelMeterProvider> = Collector::new();
+
+fn labels(scope_labels: &Labels, attributes: &[KeyValue]) -> Labels {
+    let mut attributes: Vec<_> = attributes
+        .iter()
+        .map(|attr| {
+            (
+                sanitize_label_name(attr.key.as_str()).into_owned(),
+                attr.value.as_str().into_owned(),
+            )
+        })
+        .collect();
+    // Ensure that the same attributes in a different order map to the same label set.
+    attributes.sort_unstable_by(|(key, _), (other_key, _)| key.cmp(other_key));
+    scope_labels.iter().cloned().chain(attributes).collect()
+}
+
+fn convert_unit(unit: &str, name: &str) -> Option<Unit> {
+    let unit = match unit {
+        "s" => Unit::Seconds,
+        "By" => Unit::Bytes,
+        _ => return None,
+    };
+    unit_for_name(unit, name)
+}
+
+fn custom_buckets(boundaries: Vec<f64>) -> Option<Buckets> {
+    let is_valid = !boundaries.is_empty()
+        && boundaries.iter().all(|bound| bound.is_finite())
+        && boundaries.windows(2).all(|window| window[0] < window[1]);
+    // Leaking is fine since the number of instruments is bounded.
+    is_valid.then(|| Buckets::values(Box::leak(boundaries.into_boxed_slice())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Format, Registry};
+
+    fn encode(provider: &OtelMeterProvider) -> String {
+        let mut registry = Registry::empty();
+        registry.register_metrics(provider);
+        let mut buffer = String::new();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn recording_sync_instruments() {
+        let provider = OtelMeterProvider::default();
+        let scope = InstrumentationScope::builder("test_lib")
+            .with_version("1.0.0")
+            .build();
+        let meter = provider.meter_with_scope(scope);
+
+        let counter = meter
+            .u64_counter("http.requests")
+            .with_description("Number of HTTP requests")
+            .build();
+        counter.add(2, &[KeyValue::new("method", "GET")]);
+        counter.add(1, &[KeyValue::new("method", "POST")]);
+        counter.add(1, &[KeyValue::new("method", "GET")]);
+
+        let up_down_counter = meter.i64_up_down_counter("queue.len").build();
+        up_down_counter.add(5, &[]);
+        up_down_counter.add(-2, &[]);
+
+        let histogram = meter
+            .f64_histogram("http.latency")
+            .with_unit("s")
+            .with_boundaries(vec![0.01, 0.1, 1.0])
+            .build();
+        histogram.record(
+            0.05,
+            &[KeyValue::new("status", 200), KeyValue::new("ok", true)],
+        );
+
+        // Conflicting instrument type; should be ignored.
+        meter.f64_gauge("queue.len").build().record(100.0, &[]);
+
+        let buffer = encode(&provider);
+        let lines: Vec<_> = buffer.lines().collect();
+        let scope_labels = r#"otel_scope_name="test_lib",otel_scope_version="1.0.0""#;
+        let expected_lines = [
+            "# HELP http_requests Number of HTTP requests.".to_owned(),
+            "# TYPE http_requests counter".to_owned(),
+            format!(r#"http_requests_total{{{scope_labels},method="GET"}} 3"#),
+            format!(r#"http_requests_total{{{scope_labels},method="POST"}} 1"#),
+            "# TYPE queue_len gauge".to_owned(),
+            format!("queue_len{{{scope_labels}}} 3"),
+            "# TYPE http_latency_seconds histogram".to_owned(),
+            format!(
+                r#"http_latency_seconds_bucket{{le="0.1",{scope_labels},ok="true",status="200"}} 1"#
+            ),
+        ];
+        for line in &expected_lines {
+            assert!(lines.contains(&line.as_str()), "{lines:#?}");
+        }
+
+        let scopes = provider.scopes();
+        assert_eq!(scopes.len(), 1);
+        assert_eq!(scopes[0].name, "test_lib");
+        assert_eq!(scopes[0].version.as_deref(), Some("1.0.0"));
+        let metric_names: Vec<_> = scopes[0]
+            .metrics
+            .iter()
+            .map(|metric| &metric.name)
+            .collect();
+        assert_eq!(metric_names, ["http_requests", "queue_len", "http_latency"]);
+        assert!(matches!(scopes[0].metrics[2].unit, Some(Unit::Seconds)));
+    }
+
+    #[test]
+    fn recording_observable_instruments() {
+        let provider = OtelMeterProvider::default();
+        for scope_name in ["first", "second"] {
+            let meter = provider.meter(scope_name);
+            meter
+                .u64_observable_gauge("connections")
+                .with_callback(|instrument| instrument.observe(3, &[]))
+                .build();
+            meter
+                .f64_observable_counter("cpu.time")
+                .with_unit("s")
+                .with_callback(|instrument| instrument.observe(1.5, &[]))
+                .build();
+        }
+
+        let buffer = encode(&provider);
+        let lines: Vec<_> = buffer.lines().collect();
+        let expected_lines = [
+            r#"connections{otel_scope_name="first"} 3"#,
+            r#"connections{otel_scope_name="second"} 3"#,
+            r#"cpu_time_seconds_total{otel_scope_name="first"} 1.5"#,
+            r#"cpu_time_seconds_total{otel_scope_name="second"} 1.5"#,
+        ];
+        for line in expected_lines {
+            assert!(lines.contains(&line), "{lines:#?}");
+        }
+        assert_eq!(provider.scopes().len(), 2);
+    }
+
+    #[test]
+    fn validating_custom_buckets() {
+        assert!(custom_buckets(vec![1.0, 0.5]).is_none());
+        assert!(custom_buckets(vec![]).is_none());
+    }
+}
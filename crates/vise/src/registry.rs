@@ -1,23 +1,32 @@
 //! Wrapper around metrics registry.
 
-use std::{borrow::Cow, collections::HashMap, fmt, sync::Mutex};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    error, fmt, iter,
+    sync::Mutex,
+};
 
 use once_cell::sync::Lazy;
 use prometheus_client::{
+    collector::Collector as CollectorTrait,
     encoding::{text, DescriptorEncoder},
     registry::{Registry as RegistryInner, Unit},
 };
 
 use crate::{
-    collector::{Collector, LazyGlobalCollector},
-    descriptors::{FullMetricDescriptor, MetricGroupDescriptor},
+    collector::{Collector, LazyGlobalCollector, RegisteredCollector},
+    descriptors::{FullMetricDescriptor, MetricDescriptor, MetricGroupDescriptor, Stability},
     encoding::GroupedMetric,
+    filter::{self, ActiveFilterGuard, MetricsFilter},
     format::{EscapeWrapper, Format, PrometheusWrapper},
+    metrics::flattened_descriptor,
+    switch::GroupSwitch,
     Metrics,
 };
 
 impl FullMetricDescriptor {
-    fn format_for_panic(&self) -> String {
+    fn format_location(&self) -> String {
         format!(
             "{module}::{group_name}.{field_name} (line {line}) in crate {crate_name} {crate_version}",
             module = self.group.module_path,
@@ -30,6 +39,105 @@ impl FullMetricDescriptor {
     }
 }
 
+impl MetricDescriptor {
+    /// Returns all names under which the metric is exported (i.e., the full name and the deprecated name, if any).
+    fn exported_names(&self) -> impl Iterator<Item = String> {
+        iter::once(self.full_name()).chain(self.full_deprecated_name())
+    }
+}
+
+impl MetricGroupDescriptor {
+    /// Creates a copy of this descriptor with all metric names prefixed by `prefix`.
+    /// The copy is leaked, which is fine since it is only created on metric name conflicts.
+    fn leak_with_prefix(&self, prefix: &str) -> &'static Self {
+        let metrics = self
+            .metrics
+            .iter()
+            .map(|metric| metric.leak_with_prefix(prefix));
+        let metrics: Vec<_> = metrics.collect();
+        Box::leak(Box::new(Self {
+            metrics: Box::leak(metrics.into_boxed_slice()),
+            ..*self
+        }))
+    }
+}
+
+/// Policy to resolve metric name conflicts when registering [`Metrics`] in a [`Registry`].
+///
+/// The policy only applies to conflicts among metric names; label name conflicts
+/// (e.g., a [`MetricsFamily`](crate::MetricsFamily) label clashing with a [`Family`](crate::Family) label)
+/// always result in an error since they cannot be resolved by the registry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ConflictPolicy {
+    /// Fail registration with a [`RegistrationError`]. This is the default.
+    #[default]
+    Fail,
+    /// Skip the group of metrics registered later. The previously registered group is retained.
+    Skip,
+    /// Rename all metrics in the group registered later by prefixing them with the name of the crate
+    /// the group is defined in (i.e., [`MetricGroupDescriptor::crate_name`]). If renamed metrics
+    /// still conflict with the registered ones, registration fails.
+    RenameWithCratePrefix,
+}
+
+/// Error registering [`Metrics`] in a [`Registry`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RegistrationError {
+    /// Metric with the same full name is already registered.
+    MetricName {
+        /// Full name of the conflicting metric (i.e., the name reported to Prometheus).
+        name: String,
+        /// Descriptor of the metric being registered.
+        new: FullMetricDescriptor,
+        /// Descriptor of the previously registered metric.
+        previous: FullMetricDescriptor,
+    },
+    /// Label name is used multiple times for the same metric, e.g. a [`MetricsFamily`](crate::MetricsFamily)
+    /// and a [`Family`](crate::Family) inside it use the same label.
+    LabelName {
+        /// Name of the duplicated label.
+        label: &'static str,
+        /// Descriptor of the metric being registered.
+        metric: FullMetricDescriptor,
+    },
+}
+
+impl fmt::Display for RegistrationError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MetricName {
+                name,
+                new,
+                previous,
+            } => write!(
+                formatter,
+                "Metric `{name}` is redefined. New definition is at {new}, \
+                 previous definition was at {previous}",
+                new = new.format_location(),
+                previous = previous.format_location()
+            ),
+            Self::LabelName { label, metric } => write!(
+                formatter,
+                "Label `{label}` is used multiple times for metric `{name}` defined at {location}",
+                name = metric.metric.full_name(),
+                location = metric.format_location()
+            ),
+        }
+    }
+}
+
+impl error::Error for RegistrationError {}
+
+/// Outcome of adding a group of metrics to [`RegisteredDescriptors`].
+#[derive(Debug)]
+enum PushOutcome {
+    Pushed,
+    Skipped,
+    Renamed { prefix: &'static str },
+}
+
 /// Descriptors of all metrics in a registry.
 #[derive(Debug, Default)]
 pub struct RegisteredDescriptors {
@@ -44,6 +152,7 @@ impl RegisteredDescriptors {
     }
 
     /// Obtains a metric by its full name (i.e., the name reported to Prometheus).
+    /// [Deprecated names](MetricDescriptor::deprecated_name) are resolved as well.
     pub fn metric(&self, full_name: &str) -> Option<FullMetricDescriptor> {
         self.metrics_by_name.get(full_name).copied()
     }
@@ -53,25 +162,130 @@ impl RegisteredDescriptors {
         self.groups.iter().map(|group| group.metrics.len()).sum()
     }
 
-    fn push(&mut self, group: &'static MetricGroupDescriptor) {
+    /// Iterates over all registered metrics in the registration order.
+    pub(crate) fn metrics(&self) -> impl Iterator<Item = FullMetricDescriptor> + '_ {
+        self.groups.iter().flat_map(|&group| {
+            group
+                .metrics
+                .iter()
+                .map(move |metric| FullMetricDescriptor::new(group, metric))
+        })
+    }
+
+    fn try_push(
+        &mut self,
+        group: &'static MetricGroupDescriptor,
+        policy: ConflictPolicy,
+    ) -> Result<PushOutcome, RegistrationError> {
+        Self::check_label_names(group)?;
+        let Err(err) = self.check_metric_names(group, None) else {
+            self.insert(group);
+            return Ok(PushOutcome::Pushed);
+        };
+
+        match policy {
+            ConflictPolicy::Fail => Err(err),
+            ConflictPolicy::Skip => Ok(PushOutcome::Skipped),
+            ConflictPolicy::RenameWithCratePrefix => {
+                let prefix = group.crate_name;
+                self.check_metric_names(group, Some(prefix))?;
+                self.insert(group.leak_with_prefix(prefix));
+                Ok(PushOutcome::Renamed { prefix })
+            }
+        }
+    }
+
+    fn check_label_names(group: &'static MetricGroupDescriptor) -> Result<(), RegistrationError> {
+        for metric in group.metrics {
+            let metric = FullMetricDescriptor::new(group, metric);
+            let mut label_names = HashSet::new();
+            for label in metric.label_names() {
+                if !label_names.insert(label) {
+                    return Err(RegistrationError::LabelName { label, metric });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_metric_names(
+        &self,
+        group: &'static MetricGroupDescriptor,
+        prefix: Option<&str>,
+    ) -> Result<(), RegistrationError> {
+        let mut new_metrics = HashMap::with_capacity(group.metrics.len());
+        for metric in group.metrics {
+            let descriptor = FullMetricDescriptor::new(group, metric);
+            for name in metric.exported_names() {
+                let name = match prefix {
+                    Some(prefix) => format!("{prefix}_{name}"),
+                    None => name,
+                };
+                let prev_descriptor = self.metrics_by_name.get(&name).copied();
+                let prev_descriptor = prev_descriptor.or_else(|| new_metrics.get(&name).copied());
+                if let Some(previous) = prev_descriptor {
+                    return Err(RegistrationError::MetricName {
+                        name,
+                        new: descriptor,
+                        previous,
+                    });
+                }
+                new_metrics.insert(name, descriptor);
+            }
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, group: &'static MetricGroupDescriptor) {
         for field in group.metrics {
             let descriptor = FullMetricDescriptor::new(group, field);
-            let metric_name = field.full_name();
-            if let Some(prev_descriptor) =
-                self.metrics_by_name.insert(metric_name.clone(), descriptor)
-            {
-                panic!(
-                    "Metric `{metric_name}` is redefined. New definition is at {descriptor}, \
-                     previous definition was at {prev_descriptor}",
-                    descriptor = descriptor.format_for_panic(),
-                    prev_descriptor = prev_descriptor.format_for_panic()
-                );
+            for name in field.exported_names() {
+                self.metrics_by_name.insert(name, descriptor);
             }
         }
         self.groups.push(group);
     }
 }
 
+/// Filter for metrics exported by a [`Registry`] based on their stability and deprecation.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct VisibilityFilter {
+    hide_alpha: bool,
+    hide_deprecated: bool,
+}
+
+impl VisibilityFilter {
+    pub(crate) fn is_visible(self, stability: Stability, is_deprecated: bool) -> bool {
+        let is_hidden = (self.hide_alpha && stability == Stability::Alpha)
+            || (self.hide_deprecated && is_deprecated);
+        !is_hidden
+    }
+}
+
+/// Context of a metrics group registered in a [`Registry`] used when encoding the group.
+#[derive(Debug, Clone)]
+pub(crate) struct GroupContext {
+    group: &'static MetricGroupDescriptor,
+    visibility: VisibilityFilter,
+    /// Prefix added to metric names by the registry, e.g. via [`MetricsCollection::with_prefix()`]
+    /// or when resolving name conflicts.
+    name_prefix: Option<String>,
+}
+
+impl GroupContext {
+    pub(crate) fn new(group: &'static MetricGroupDescriptor) -> Self {
+        Self {
+            group,
+            visibility: VisibilityFilter::default(),
+            name_prefix: None,
+        }
+    }
+
+    fn is_visible(&self, stability: Stability, is_deprecated: bool) -> bool {
+        self.visibility.is_visible(stability, is_deprecated)
+    }
+}
+
 /// Configures collection of [`register`](crate::register)ed metrics.
 ///
 /// # Examples
@@ -83,6 +297,8 @@ pub struct MetricsCollection<F = fn(&MetricGroupDescriptor) -> bool> {
     filter_fn: F,
     prefix: Option<String>,
     labels: Vec<(Cow<'static, str>, Cow<'static, str>)>,
+    conflict_policy: ConflictPolicy,
+    visibility: VisibilityFilter,
 }
 
 impl Default for MetricsCollection {
@@ -92,6 +308,8 @@ impl Default for MetricsCollection {
             filter_fn: |_| true,
             prefix: None,
             labels: Vec::new(),
+            conflict_policy: ConflictPolicy::default(),
+            visibility: VisibilityFilter::default(),
         }
     }
 }
@@ -129,6 +347,8 @@ impl MetricsCollection {
             filter_fn,
             prefix: self.prefix,
             labels: self.labels,
+            conflict_policy: self.conflict_policy,
+            visibility: self.visibility,
         }
     }
 
@@ -159,18 +379,60 @@ impl MetricsCollection {
             ..self
         }
     }
+
+    /// Configures the policy to resolve metric name conflicts for this collection.
+    /// By default, conflicts lead to an error / panic.
+    #[must_use]
+    pub fn with_conflict_policy(self, policy: ConflictPolicy) -> Self {
+        Self {
+            conflict_policy: policy,
+            ..self
+        }
+    }
+
+    /// Hides metrics with the [alpha stability level](Stability::Alpha) from export.
+    /// Metric descriptors are still collected.
+    #[must_use]
+    pub fn hide_alpha_metrics(mut self) -> Self {
+        self.visibility.hide_alpha = true;
+        self
+    }
+
+    /// Hides [deprecated metric names](MetricDescriptor::deprecated_name) from export; metrics
+    /// are only exported under their current names. Metric descriptors are still collected.
+    #[must_use]
+    pub fn hide_deprecated_names(mut self) -> Self {
+        self.visibility.hide_deprecated = true;
+        self
+    }
 }
 
 impl<F: FnMut(&MetricGroupDescriptor) -> bool> MetricsCollection<F> {
     /// Creates a registry with all [`register`](crate::register)ed [`Global`](crate::Global) metrics
     /// and [`Collector`]s. If a filtering predicate [was provided](MetricsCollection::filter()),
     /// only metrics satisfying this function will be collected.
-    #[allow(clippy::missing_panics_doc)]
-    pub fn collect(mut self) -> Registry {
-        let mut registry = Registry::empty();
+    ///
+    /// # Panics
+    ///
+    /// Panics if registration fails according to the [conflict policy](MetricsCollection::with_conflict_policy()).
+    /// Use [`Self::try_collect()`] to handle such errors.
+    pub fn collect(self) -> Registry {
+        self.try_collect().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Fallible version of [`Self::collect()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if registration fails according to the [conflict policy](MetricsCollection::with_conflict_policy()),
+    /// e.g. if several metrics have the same name.
+    pub fn try_collect(mut self) -> Result<Registry, RegistrationError> {
+        let mut registry = Registry::empty().with_conflict_policy(self.conflict_policy);
         registry.is_lazy = self.is_lazy;
+        registry.visibility = self.visibility;
 
         if let Some(prefix) = self.prefix {
+            registry.name_prefix = Some(prefix.clone());
             registry.inner = RegistryInner::with_prefix_and_labels(prefix, self.labels.into_iter());
         } else if !self.labels.is_empty() {
             registry.inner = RegistryInner::with_labels(self.labels.into_iter());
@@ -178,10 +440,10 @@ impl<F: FnMut(&MetricGroupDescriptor) -> bool> MetricsCollection<F> {
 
         for metric in METRICS_REGISTRATIONS.get() {
             if (self.filter_fn)(metric.descriptor()) {
-                metric.collect_to_registry(&mut registry);
+                metric.collect_to_registry(&mut registry)?;
             }
         }
-        registry
+        Ok(registry)
     }
 }
 
@@ -256,11 +518,47 @@ impl<F: FnMut(&MetricGroupDescriptor) -> bool> MetricsCollection<F> {
 ///
 /// let registry = MetricsCollection::default().collect(); // will panic
 /// ```
+///
+/// Conflicts can be handled gracefully using [`MetricsCollection::try_collect()`] and / or
+/// a [`ConflictPolicy`]:
+///
+/// ```
+/// # use vise::{Collector, Global, Gauge, Metrics, MetricsCollection, Unit};
+/// use vise::{ConflictPolicy, RegistrationError};
+/// # use assert_matches::assert_matches;
+/// # #[derive(Debug, Metrics)]
+/// # pub(crate) struct AppMetrics {
+/// #     #[metrics(unit = Unit::Bytes)]
+/// #     cache_memory_use: Gauge<u64>,
+/// # }
+/// # #[vise::register]
+/// # pub(crate) static APP_METRICS: Global<AppMetrics> = Global::new();
+/// # #[vise::register]
+/// # pub(crate) static APP_COLLECTOR: Collector<AppMetrics> = Collector::new();
+/// // Using the same metric definitions as above...
+/// let err = MetricsCollection::default().try_collect().unwrap_err();
+/// assert_matches!(
+///     err,
+///     RegistrationError::MetricName { name, .. } if name == "cache_memory_use_bytes"
+/// );
+///
+/// let registry = MetricsCollection::default()
+///     .with_conflict_policy(ConflictPolicy::RenameWithCratePrefix)
+///     .collect();
+/// let descriptors = registry.descriptors();
+/// assert!(descriptors.metric("cache_memory_use_bytes").is_some());
+/// // The metric registered later is prefixed with the crate name.
+/// let renamed = format!("{}_cache_memory_use_bytes", env!("CARGO_CRATE_NAME"));
+/// assert!(descriptors.metric(&renamed).is_some());
+/// ```
 #[derive(Debug)]
 pub struct Registry {
     descriptors: RegisteredDescriptors,
     inner: RegistryInner,
     is_lazy: bool,
+    conflict_policy: ConflictPolicy,
+    visibility: VisibilityFilter,
+    name_prefix: Option<String>,
 }
 
 impl Registry {
@@ -270,6 +568,19 @@ impl Registry {
             descriptors: RegisteredDescriptors::default(),
             inner: RegistryInner::default(),
             is_lazy: false,
+            conflict_policy: ConflictPolicy::default(),
+            visibility: VisibilityFilter::default(),
+            name_prefix: None,
+        }
+    }
+
+    /// Sets the policy to resolve metric name conflicts for this registry. By default,
+    /// conflicts lead to an error / panic.
+    #[must_use]
+    pub fn with_conflict_policy(self, policy: ConflictPolicy) -> Self {
+        Self {
+            conflict_policy: policy,
+            ..self
         }
     }
 
@@ -279,29 +590,105 @@ impl Registry {
     }
 
     /// Registers a group of metrics.
+    ///
+    /// # Panics
+    ///
+    /// Panics if registration fails according to the [conflict policy](Self::with_conflict_policy()).
+    /// Use [`Self::try_register_metrics()`] to handle such errors.
     pub fn register_metrics<M: Metrics>(&mut self, metrics: &M) {
-        self.descriptors.push(&M::DESCRIPTOR);
-        metrics.visit_metrics(self);
+        self.try_register_metrics(metrics)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    /// Fallible version of [`Self::register_metrics()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if registration fails according to the [conflict policy](Self::with_conflict_policy()),
+    /// e.g. if a metric with the same name is already registered.
+    pub fn try_register_metrics<M: Metrics>(
+        &mut self,
+        metrics: &M,
+    ) -> Result<(), RegistrationError> {
+        if let Some((inner, context)) = self.target_registry(flattened_descriptor::<M>())? {
+            let mut group = SwitchedGroup::new(M::DESCRIPTOR.switch(), context);
+            metrics.visit_metrics(&mut group);
+            inner.register_collector(Box::new(group));
+        }
+        Ok(())
     }
 
     pub(crate) fn register_global_metrics<M: Metrics>(
         &mut self,
         metrics: &'static Lazy<M>,
         force_lazy: bool,
-    ) {
-        if force_lazy || self.is_lazy {
-            self.descriptors.push(&M::DESCRIPTOR);
-            let collector = LazyGlobalCollector::new(metrics);
-            self.inner.register_collector(Box::new(collector));
-        } else {
-            self.register_metrics::<M>(metrics);
+    ) -> Result<(), RegistrationError> {
+        let is_lazy = force_lazy || self.is_lazy;
+        if let Some((inner, context)) = self.target_registry(flattened_descriptor::<M>())? {
+            if is_lazy {
+                let collector = LazyGlobalCollector::new(metrics, context);
+                inner.register_collector(Box::new(collector));
+            } else {
+                let mut group = SwitchedGroup::new(M::DESCRIPTOR.switch(), context);
+                Lazy::force(metrics).visit_metrics(&mut group);
+                inner.register_collector(Box::new(group));
+            }
         }
+        Ok(())
     }
 
     /// Registers a [`Collector`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if registration fails according to the [conflict policy](Self::with_conflict_policy()).
+    /// Use [`Self::try_register_collector()`] to handle such errors.
     pub fn register_collector<M: Metrics>(&mut self, collector: &'static Collector<M>) {
-        self.descriptors.push(&M::DESCRIPTOR);
-        self.inner.register_collector(Box::new(collector));
+        self.try_register_collector(collector)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    /// Fallible version of [`Self::register_collector()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if registration fails according to the [conflict policy](Self::with_conflict_policy()),
+    /// e.g. if a metric with the same name is already registered.
+    pub fn try_register_collector<M: Metrics>(
+        &mut self,
+        collector: &'static Collector<M>,
+    ) -> Result<(), RegistrationError> {
+        if let Some((inner, context)) = self.target_registry(flattened_descriptor::<M>())? {
+            let collector = RegisteredCollector::new(collector, context);
+            inner.register_collector(Box::new(collector));
+        }
+        Ok(())
+    }
+
+    /// Adds a group descriptor and returns the registry to register metrics in together with the group context,
+    /// or `None` if the group should be skipped.
+    fn target_registry(
+        &mut self,
+        group: &'static MetricGroupDescriptor,
+    ) -> Result<Option<(&mut RegistryInner, GroupContext)>, RegistrationError> {
+        let mut context = GroupContext {
+            group,
+            visibility: self.visibility,
+            name_prefix: self.name_prefix.clone(),
+        };
+        Ok(
+            match self.descriptors.try_push(group, self.conflict_policy)? {
+                PushOutcome::Pushed => Some((&mut self.inner, context)),
+                PushOutcome::Skipped => None,
+                PushOutcome::Renamed { prefix } => {
+                    context.name_prefix = Some(match context.name_prefix {
+                        Some(registry_prefix) => format!("{registry_prefix}_{prefix}"),
+                        None => prefix.to_owned(),
+                    });
+                    Some((self.inner.sub_registry_with_prefix(prefix), context))
+                }
+            },
+        )
     }
 
     /// Encodes all metrics in this registry to the specified text format.
@@ -310,6 +697,36 @@ impl Registry {
     ///
     /// Proxies formatting errors of the provided `writer`.
     pub fn encode<W: fmt::Write>(&self, writer: &mut W, format: Format) -> fmt::Result {
+        self.encode_filtered(writer, format, &MetricsFilter::default())
+    }
+
+    /// Encodes metrics in this registry selected by the provided `filter` to the specified text format.
+    /// Metrics not selected by the filter are not encoded at all; e.g., [`Collector`]s for groups
+    /// not selected by the filter are not invoked.
+    ///
+    /// # Limitations
+    ///
+    /// `prometheus_client` doesn't allow passing state to collectors, so the filter is handed over to them
+    /// via a thread-local for the duration of the call. Thus, the filter only applies to metrics encoded
+    /// on the calling thread. This is always the case for metrics and collectors registered via this crate,
+    /// but a custom `prometheus_client` collector delegating to them from another thread would encode
+    /// all metrics. Registries encoded while this call is in progress (e.g., from a [`Collector`] hook)
+    /// are not affected by the filter.
+    ///
+    /// # Errors
+    ///
+    /// Proxies formatting errors of the provided `writer`.
+    pub fn encode_filtered<W: fmt::Write>(
+        &self,
+        writer: &mut W,
+        format: Format,
+        filter: &MetricsFilter,
+    ) -> fmt::Result {
+        if cfg!(feature = "noop") {
+            // All metrics are no-ops, so there's nothing to encode.
+            return Ok(());
+        }
+        let _filter_guard = ActiveFilterGuard::new(filter);
         match format {
             Format::Prometheus | Format::OpenMetricsForPrometheus => {
                 let mut wrapper = PrometheusWrapper::new(writer);
@@ -331,9 +748,11 @@ pub trait MetricsVisitor {
     #[doc(hidden)] // implementation detail
     fn visit_metric(
         &mut self,
-        name: &'static str,
+        name: Cow<'static, str>,
         help: &'static str,
         unit: Option<Unit>,
+        stability: Stability,
+        is_deprecated: bool,
         metric: Box<dyn GroupedMetric>,
     );
 }
@@ -341,51 +760,222 @@ pub trait MetricsVisitor {
 impl MetricsVisitor for Registry {
     fn visit_metric(
         &mut self,
-        name: &'static str,
+        name: Cow<'static, str>,
+        help: &'static str,
+        unit: Option<Unit>,
+        stability: Stability,
+        is_deprecated: bool,
+        metric: Box<dyn GroupedMetric>,
+    ) {
+        let mut visitor = InnerVisitor::new(&mut self.inner, self.visibility);
+        visitor.visit_metric(name, help, unit, stability, is_deprecated, metric);
+    }
+}
+
+/// Visitor adding a prefix to the names of visited metrics. Used for nested [`Metrics`].
+#[doc(hidden)] // only used by the proc macros
+pub struct PrefixedVisitor<'a> {
+    prefix: &'static str,
+    inner: &'a mut dyn MetricsVisitor,
+}
+
+impl fmt::Debug for PrefixedVisitor<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("PrefixedVisitor")
+            .field("prefix", &self.prefix)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> PrefixedVisitor<'a> {
+    pub fn new(prefix: &'static str, inner: &'a mut dyn MetricsVisitor) -> Self {
+        Self { prefix, inner }
+    }
+}
+
+impl MetricsVisitor for PrefixedVisitor<'_> {
+    fn visit_metric(
+        &mut self,
+        name: Cow<'static, str>,
+        help: &'static str,
+        unit: Option<Unit>,
+        stability: Stability,
+        is_deprecated: bool,
+        metric: Box<dyn GroupedMetric>,
+    ) {
+        let name = format!("{}_{name}", self.prefix);
+        self.inner
+            .visit_metric(name.into(), help, unit, stability, is_deprecated, metric);
+    }
+}
+
+/// Visitor registering metrics in a (sub-)registry.
+#[derive(Debug)]
+struct InnerVisitor<'a> {
+    registry: &'a mut RegistryInner,
+    visibility: VisibilityFilter,
+}
+
+impl<'a> InnerVisitor<'a> {
+    fn new(registry: &'a mut RegistryInner, visibility: VisibilityFilter) -> Self {
+        Self {
+            registry,
+            visibility,
+        }
+    }
+}
+
+impl MetricsVisitor for InnerVisitor<'_> {
+    fn visit_metric(
+        &mut self,
+        name: Cow<'static, str>,
         help: &'static str,
         unit: Option<Unit>,
+        stability: Stability,
+        is_deprecated: bool,
         metric: Box<dyn GroupedMetric>,
     ) {
+        if !self.visibility.is_visible(stability, is_deprecated) {
+            return;
+        }
         if let Some(unit) = unit {
-            self.inner.register_with_unit(name, help, unit, metric);
+            self.registry.register_with_unit(name, help, unit, metric);
         } else {
-            self.inner.register(name, help, metric);
+            self.registry.register(name, help, metric);
         }
     }
 }
 
-#[derive(Debug)]
-pub(crate) struct MetricsEncoder<'a> {
-    inner: Result<DescriptorEncoder<'a>, fmt::Error>,
+/// Metric visited by a [`SwitchedGroup`].
+struct VisitedMetric {
+    name: Cow<'static, str>,
+    help: &'static str,
+    unit: Option<Unit>,
+    metric: Box<dyn GroupedMetric>,
 }
 
-impl MetricsEncoder<'_> {
-    pub(crate) fn check(self) -> fmt::Result {
-        self.inner.map(drop)
+/// Metrics group registered in a (sub-)registry. Unlike metrics registered directly, the group
+/// is only encoded if its [runtime switch](GroupSwitch) is on.
+struct SwitchedGroup {
+    switch: &'static GroupSwitch,
+    context: GroupContext,
+    metrics: Vec<VisitedMetric>,
+}
+
+impl fmt::Debug for SwitchedGroup {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<_> = self.metrics.iter().map(|metric| &metric.name).collect();
+        formatter
+            .debug_struct("SwitchedGroup")
+            .field("switch", self.switch)
+            .field("metrics", &names)
+            .finish_non_exhaustive()
     }
 }
 
-impl<'a> From<DescriptorEncoder<'a>> for MetricsEncoder<'a> {
-    fn from(inner: DescriptorEncoder<'a>) -> Self {
-        Self { inner: Ok(inner) }
+impl SwitchedGroup {
+    fn new(switch: &'static GroupSwitch, context: GroupContext) -> Self {
+        Self {
+            switch,
+            context,
+            metrics: vec![],
+        }
     }
 }
 
-impl MetricsVisitor for MetricsEncoder<'_> {
+impl MetricsVisitor for SwitchedGroup {
     fn visit_metric(
         &mut self,
-        name: &'static str,
+        name: Cow<'static, str>,
         help: &'static str,
         unit: Option<Unit>,
+        stability: Stability,
+        is_deprecated: bool,
         metric: Box<dyn GroupedMetric>,
     ) {
+        if self.context.is_visible(stability, is_deprecated) {
+            self.metrics.push(VisitedMetric {
+                name,
+                help,
+                unit,
+                metric,
+            });
+        }
+    }
+}
+
+impl CollectorTrait for SwitchedGroup {
+    fn encode(&self, encoder: DescriptorEncoder<'_>) -> fmt::Result {
+        if !self.switch.is_enabled() {
+            return Ok(());
+        }
+        let Some(mut encoder) = MetricsEncoder::new(encoder, &self.context) else {
+            return Ok(());
+        };
+        for metric in &self.metrics {
+            encoder.encode_metric(
+                &metric.name,
+                metric.help,
+                metric.unit.as_ref(),
+                &*metric.metric,
+            );
+        }
+        encoder.check()
+    }
+}
+
+/// Encoder for metrics in a group. Applies the [`MetricsFilter`] of the current encoding, if any.
+#[derive(Debug)]
+pub(crate) struct MetricsEncoder<'a, 'ctx> {
+    inner: Result<DescriptorEncoder<'a>, fmt::Error>,
+    context: &'ctx GroupContext,
+    filter: Option<MetricsFilter>,
+}
+
+impl<'a, 'ctx> MetricsEncoder<'a, 'ctx> {
+    /// Returns `None` if the group is not selected by the filter of the current encoding.
+    pub(crate) fn new(inner: DescriptorEncoder<'a>, context: &'ctx GroupContext) -> Option<Self> {
+        let filter = filter::active_filter();
+        if let Some(filter) = &filter {
+            if !filter.matches_group(context.group) {
+                return None;
+            }
+        }
+        Some(Self {
+            inner: Ok(inner),
+            context,
+            filter,
+        })
+    }
+
+    fn is_name_selected(&self, name: &str) -> bool {
+        self.filter.as_ref().map_or(true, |filter| {
+            filter.matches_prefixed_name(self.context.name_prefix.as_deref(), name)
+        })
+    }
+
+    pub(crate) fn check(self) -> fmt::Result {
+        self.inner.map(drop)
+    }
+
+    fn encode_metric(
+        &mut self,
+        name: &str,
+        help: &str,
+        unit: Option<&Unit>,
+        metric: &dyn GroupedMetric,
+    ) {
+        if !metric.is_enabled() || !self.is_name_selected(name) {
+            return;
+        }
         if let Ok(encoder) = &mut self.inner {
             // Append a full stop to `help` to be consistent with registered metrics.
             let mut help = String::from(help);
             help.push('.');
 
             let new_result = encoder
-                .encode_descriptor(name, &help, unit.as_ref(), metric.metric_type())
+                .encode_descriptor(name, &help, unit, metric.metric_type())
                 .and_then(|encoder| metric.encode(encoder));
             if let Err(err) = new_result {
                 self.inner = Err(err);
@@ -394,13 +984,30 @@ impl MetricsVisitor for MetricsEncoder<'_> {
     }
 }
 
+impl MetricsVisitor for MetricsEncoder<'_, '_> {
+    fn visit_metric(
+        &mut self,
+        name: Cow<'static, str>,
+        help: &'static str,
+        unit: Option<Unit>,
+        stability: Stability,
+        is_deprecated: bool,
+        metric: Box<dyn GroupedMetric>,
+    ) {
+        if self.context.is_visible(stability, is_deprecated) {
+            self.encode_metric(&name, help, unit.as_ref(), metric.as_ref());
+        }
+    }
+}
+
 /// Collects metrics from this type to registry. This is used by the [`register`](crate::register)
 /// macro to handle registration of [`Global`](crate::Global) metrics and [`Collector`]s.
 pub trait CollectToRegistry: 'static + Send + Sync {
     #[doc(hidden)] // implementation detail
     fn descriptor(&self) -> &'static MetricGroupDescriptor;
     #[doc(hidden)] // implementation detail
-    fn collect_to_registry(&'static self, registry: &mut Registry);
+    fn collect_to_registry(&'static self, registry: &mut Registry)
+        -> Result<(), RegistrationError>;
 }
 
 // Intentionally not re-exported; used by the proc macros
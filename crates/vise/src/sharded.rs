@@ -0,0 +1,184 @@
+//! Sharded storage for metrics updated from many threads.
+
+use std::{
+    fmt,
+    num::NonZeroUsize,
+    ops,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    thread,
+};
+
+use once_cell::sync::Lazy;
+
+/// Upper bound on the number of shards for a single metric.
+const MAX_SHARDS: usize = 64;
+
+/// Number of shards used by sharded metrics: the available parallelism rounded up to a power of 2.
+static SHARD_COUNT: Lazy<usize> = Lazy::new(|| {
+    let parallelism = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    parallelism.next_power_of_two().min(MAX_SHARDS)
+});
+
+thread_local! {
+    /// Slot of the current thread, assigned in the round-robin fashion when the thread first updates
+    /// a sharded metric. The slot is the same for all metrics.
+    static THREAD_SLOT: usize = {
+        static NEXT_SLOT: AtomicUsize = AtomicUsize::new(0);
+        NEXT_SLOT.fetch_add(1, Ordering::Relaxed)
+    };
+}
+
+/// Aligns the wrapped value to a cache line, so that values in adjacent shards don't share a line.
+/// 128 bytes covers CPUs prefetching pairs of 64-byte lines.
+#[derive(Debug, Default)]
+#[repr(align(128))]
+pub(crate) struct CachePadded<T>(T);
+
+impl<T> ops::Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+/// Storage for a metric value that is either a single value or a set of per-thread-slot shards.
+/// Sharded values are updated via the shard of the current thread, and must be aggregated on reads.
+pub(crate) enum Shards<T> {
+    Single(T),
+    Sharded(Box<[CachePadded<T>]>),
+}
+
+impl<T: fmt::Debug> fmt::Debug for Shards<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Single(value) => fmt::Debug::fmt(value, formatter),
+            Self::Sharded(shards) => formatter
+                .debug_list()
+                .entries(shards.iter().map(|shard| &shard.0))
+                .finish(),
+        }
+    }
+}
+
+impl<T> Shards<T> {
+    pub(crate) fn new(sharded: bool, mut new_value: impl FnMut() -> T) -> Self {
+        if sharded {
+            let shards = (0..*SHARD_COUNT)
+                .map(|_| CachePadded(new_value()))
+                .collect();
+            Self::Sharded(shards)
+        } else {
+            Self::Single(new_value())
+        }
+    }
+
+    /// Returns the value for the current thread.
+    #[inline]
+    pub(crate) fn local(&self) -> &T {
+        match self {
+            Self::Single(value) => value,
+            Self::Sharded(shards) => {
+                // The number of shards is a power of 2, so masking is equivalent to taking the remainder.
+                let idx = THREAD_SLOT.with(|slot| *slot) & (shards.len() - 1);
+                &shards[idx]
+            }
+        }
+    }
+
+    /// Iterates over all shards.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        let (single, sharded) = match self {
+            Self::Single(value) => (Some(value), &[][..]),
+            Self::Sharded(shards) => (None, &shards[..]),
+        };
+        single
+            .into_iter()
+            .chain(sharded.iter().map(|shard| &shard.0))
+    }
+}
+
+/// Lock-free histogram shard. Unlike the `prometheus-client` histogram, it doesn't use a lock, so updating it
+/// from multiple threads doesn't block.
+#[derive(Debug)]
+pub(crate) struct HistogramShard {
+    /// Bit representation of the `f64` sum of observed values.
+    sum: AtomicU64,
+    count: AtomicU64,
+    buckets: Box<[AtomicU64]>,
+}
+
+impl HistogramShard {
+    pub(crate) fn new(bucket_count: usize) -> Self {
+        Self {
+            sum: AtomicU64::new(0.0_f64.to_bits()),
+            count: AtomicU64::new(0),
+            buckets: (0..bucket_count).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    /// Observes a value; `bucket_idx` is the index of the first bucket with the upper bound
+    /// not less than the value.
+    pub(crate) fn observe(&self, value: f64, bucket_idx: Option<usize>) {
+        // `fetch_update()` never fails if the closure always returns `Some(_)`
+        self.sum
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |sum| {
+                Some((f64::from_bits(sum) + value).to_bits())
+            })
+            .ok();
+        self.count.fetch_add(1, Ordering::Relaxed);
+        if let Some(idx) = bucket_idx {
+            self.buckets[idx].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Adds the contents of this shard to the provided aggregated values.
+    pub(crate) fn aggregate(&self, sum: &mut f64, count: &mut u64, buckets: &mut [(f64, u64)]) {
+        *sum += f64::from_bits(self.sum.load(Ordering::Relaxed));
+        *count += self.count.load(Ordering::Relaxed);
+        for ((_, aggregated), bucket) in buckets.iter_mut().zip(&*self.buckets) {
+            *aggregated += bucket.load(Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Barrier;
+
+    use super::*;
+
+    #[test]
+    fn shard_count_is_power_of_two() {
+        assert!(SHARD_COUNT.is_power_of_two());
+        assert!(*SHARD_COUNT <= MAX_SHARDS);
+        let shards = Shards::new(true, || AtomicU64::new(0));
+        assert_eq!(shards.iter().count(), *SHARD_COUNT);
+        let shards = Shards::new(false, || AtomicU64::new(0));
+        assert_eq!(shards.iter().count(), 1);
+    }
+
+    #[test]
+    fn updating_shards_from_multiple_threads() {
+        const THREAD_COUNT: usize = 8;
+
+        let shards = Shards::new(true, || AtomicU64::new(0));
+        let barrier = Barrier::new(THREAD_COUNT);
+        thread::scope(|scope| {
+            for _ in 0..THREAD_COUNT {
+                scope.spawn(|| {
+                    barrier.wait();
+                    for _ in 0..1_000 {
+                        shards.local().fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            }
+        });
+
+        let total: u64 = shards
+            .iter()
+            .map(|shard| shard.load(Ordering::Relaxed))
+            .sum();
+        assert_eq!(total, THREAD_COUNT as u64 * 1_000);
+    }
+}
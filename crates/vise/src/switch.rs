@@ -0,0 +1,418 @@
+//! Runtime switches for metric groups.
+
+use std::{
+    collections::HashMap,
+    ptr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use once_cell::sync::Lazy;
+
+use crate::descriptors::MetricGroupDescriptor;
+
+type SwitchKey = (String, String, String);
+/// Addresses of a nested group switch and the switch of the enclosing group.
+type NestedSwitchKey = (usize, usize);
+
+/// Runtime switch for a group of [`Metrics`](crate::Metrics), allowing to enable or disable
+/// the group without restarting the app.
+///
+/// Switches are keyed by the [crate name](MetricGroupDescriptor::crate_name), [module path](MetricGroupDescriptor::module_path)
+/// and [name](MetricGroupDescriptor::name) of the group; i.e., a switch applies to all instances of a metrics struct
+/// (including ones in a [`MetricsFamily`](crate::MetricsFamily)). All groups are enabled by default.
+///
+/// While a group is disabled:
+///
+/// - Updating metrics declared in the group is a no-op. For [`Gauge`](crate::Gauge)s, this means that
+///   incremental updates (e.g., [`Gauge::inc_by()`](crate::Gauge::inc_by())) made while the group is disabled
+///   are lost, so the gauge value may be inaccurate after the group is re-enabled. Guards returned by
+///   [`Gauge::inc_guard()`](crate::Gauge::inc_guard()) are not affected by this: a guard only decrements
+///   the gauge if it was incremented on creation.
+/// - Metrics in the group are omitted from [`Registry::encode()`](crate::Registry::encode()) output.
+///
+/// Switches compose for nested groups (i.e., fields with the `flatten` or `prefix` attribute in the
+/// [`Metrics`](macro@crate::Metrics) derive macro): metrics in a nested group are updated and encoded
+/// only if both the switch of the nested group and the switches of all enclosing groups are on.
+///
+/// # Examples
+///
+/// ```
+/// use vise::{Buckets, Format, Histogram, Metrics, Registry};
+/// # use std::time::Duration;
+///
+/// #[derive(Debug, Metrics)]
+/// #[metrics(prefix = "debug")]
+/// struct DebugMetrics {
+///     /// Latency of a hot path.
+///     #[metrics(buckets = Buckets::LATENCIES)]
+///     hot_path_latency: Histogram<Duration>,
+/// }
+///
+/// let metrics = DebugMetrics::default();
+/// let mut registry = Registry::empty();
+/// registry.register_metrics(&metrics);
+///
+/// let switch = DebugMetrics::DESCRIPTOR.switch();
+/// switch.set_enabled(false);
+/// // This is a no-op now.
+/// metrics.hot_path_latency.observe(Duration::from_millis(10));
+/// let mut buffer = String::new();
+/// registry.encode(&mut buffer, Format::OpenMetrics)?;
+/// assert!(!buffer.contains("debug_hot_path_latency"));
+///
+/// switch.set_enabled(true);
+/// metrics.hot_path_latency.observe(Duration::from_millis(10));
+/// buffer.clear();
+/// registry.encode(&mut buffer, Format::OpenMetrics)?;
+/// assert!(buffer.contains("debug_hot_path_latency_count 1"));
+/// # Ok::<_, std::fmt::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct GroupSwitch(SwitchRepr);
+
+#[derive(Debug)]
+enum SwitchRepr {
+    /// Switch of a specific group.
+    Group(AtomicBool),
+    /// Switch of a group nested into another group.
+    Nested {
+        switch: &'static GroupSwitch,
+        parent: &'static GroupSwitch,
+    },
+}
+
+impl GroupSwitch {
+    /// Returns the switch for a group with the specified crate name, module path and name. If the switch
+    /// was not accessed before, it is created in the enabled state.
+    #[allow(clippy::missing_panics_doc)] // the mutex is never poisoned
+    pub fn get(crate_name: &str, module_path: &str, name: &str) -> &'static Self {
+        static SWITCHES: Lazy<Mutex<HashMap<SwitchKey, &'static GroupSwitch>>> =
+            Lazy::new(Mutex::default);
+
+        let key = (
+            crate_name.to_owned(),
+            module_path.to_owned(),
+            name.to_owned(),
+        );
+        let mut switches = SWITCHES.lock().unwrap();
+        switches
+            .entry(key)
+            .or_insert_with(|| Box::leak(Box::new(Self(SwitchRepr::Group(AtomicBool::new(true))))))
+    }
+
+    /// Returns the switch for metrics in a group with the specified `switch` nested into a group with
+    /// the `parent` switch. The returned switch is on only if both switches are on.
+    pub(crate) fn nested(switch: &'static Self, parent: &'static Self) -> &'static Self {
+        static SWITCHES: Lazy<Mutex<HashMap<NestedSwitchKey, &'static GroupSwitch>>> =
+            Lazy::new(Mutex::default);
+
+        let key = (
+            ptr::from_ref(switch) as usize,
+            ptr::from_ref(parent) as usize,
+        );
+        let mut switches = SWITCHES.lock().unwrap();
+        switches
+            .entry(key)
+            .or_insert_with(|| Box::leak(Box::new(Self(SwitchRepr::Nested { switch, parent }))))
+    }
+
+    /// Checks whether the group is enabled. For a switch of a nested group, also checks switches
+    /// of the enclosing groups.
+    pub fn is_enabled(&self) -> bool {
+        match &self.0 {
+            SwitchRepr::Group(enabled) => enabled.load(Ordering::Relaxed),
+            SwitchRepr::Nested { switch, parent } => parent.is_enabled() && switch.is_enabled(),
+        }
+    }
+
+    /// Enables or disables the group. For a switch of a nested group, only the switch
+    /// of the nested group itself is changed.
+    pub fn set_enabled(&self, enabled: bool) {
+        match &self.0 {
+            SwitchRepr::Group(flag) => flag.store(enabled, Ordering::Relaxed),
+            SwitchRepr::Nested { switch, .. } => switch.set_enabled(enabled),
+        }
+    }
+
+    /// Checks whether an optional switch is enabled; a missing switch is treated as always enabled.
+    /// With the `noop` crate feature, all switches are treated as disabled.
+    #[inline]
+    pub(crate) fn is_on(switch: Option<&Self>) -> bool {
+        !cfg!(feature = "noop") && switch.map_or(true, Self::is_enabled)
+    }
+}
+
+/// Returns the switch for metrics in a group, taking into account the switch of the enclosing group (if any).
+#[doc(hidden)] // only used by the proc macros
+pub fn group_switch(
+    switch: &'static GroupSwitch,
+    parent: Option<&'static GroupSwitch>,
+) -> &'static GroupSwitch {
+    match parent {
+        Some(parent) => GroupSwitch::nested(switch, parent),
+        None => switch,
+    }
+}
+
+/// Shared state of a metric together with its optional switch. With the `noop` crate feature,
+/// the state is zero-sized and never present, so metrics holding it are zero-sized as well.
+#[cfg(not(feature = "noop"))]
+#[derive(Debug, Clone)]
+pub(crate) struct MetricState<T> {
+    value: T,
+    switch: Option<&'static GroupSwitch>,
+}
+
+#[cfg(not(feature = "noop"))]
+impl<T> MetricState<T> {
+    pub(crate) fn new(switch: Option<&'static GroupSwitch>, value: impl FnOnce() -> T) -> Self {
+        Self {
+            value: value(),
+            switch,
+        }
+    }
+
+    /// Returns the state regardless of the switch.
+    #[inline]
+    #[allow(clippy::unnecessary_wraps)] // the state is missing with the `noop` feature
+    pub(crate) fn get(&self) -> Option<&T> {
+        Some(&self.value)
+    }
+
+    /// Checks whether the metric should be updated and encoded.
+    #[inline]
+    pub(crate) fn is_on(&self) -> bool {
+        GroupSwitch::is_on(self.switch)
+    }
+}
+
+#[cfg(feature = "noop")]
+#[derive(Debug)]
+pub(crate) struct MetricState<T>(std::marker::PhantomData<T>);
+
+#[cfg(feature = "noop")]
+impl<T> Clone for MetricState<T> {
+    fn clone(&self) -> Self {
+        Self(std::marker::PhantomData)
+    }
+}
+
+#[cfg(feature = "noop")]
+#[allow(clippy::unused_self)] // methods mirror the non-noop implementation
+impl<T> MetricState<T> {
+    pub(crate) fn new(_switch: Option<&'static GroupSwitch>, _value: impl FnOnce() -> T) -> Self {
+        Self(std::marker::PhantomData)
+    }
+
+    #[inline]
+    pub(crate) fn get(&self) -> Option<&T> {
+        None
+    }
+
+    #[inline]
+    pub(crate) fn is_on(&self) -> bool {
+        false
+    }
+}
+
+impl<T> MetricState<T> {
+    /// Returns the state if the metric should be updated.
+    #[inline]
+    pub(crate) fn on(&self) -> Option<&T> {
+        self.get().filter(|_| self.is_on())
+    }
+}
+
+impl MetricGroupDescriptor {
+    /// Returns the [runtime switch](GroupSwitch) for this group.
+    pub fn switch(&self) -> &'static GroupSwitch {
+        GroupSwitch::get(self.crate_name, self.module_path, self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        Buckets, Counter, EncodeLabelSet, Format, Gauge, Histogram, Info, LabeledFamily, Metrics,
+        Registry,
+    };
+
+    #[derive(Debug, Metrics)]
+    #[metrics(crate = crate, prefix = "switched")]
+    struct SwitchedMetrics {
+        counter: Counter,
+        gauge: Gauge,
+        #[metrics(buckets = Buckets::LATENCIES, labels = ["method"])]
+        latencies: LabeledFamily<&'static str, Histogram<Duration>>,
+    }
+
+    #[test]
+    fn disabling_group() {
+        let metrics = SwitchedMetrics::default();
+        let mut registry = Registry::empty();
+        registry.register_metrics(&metrics);
+        let switch = SwitchedMetrics::DESCRIPTOR.switch();
+        assert!(switch.is_enabled());
+        assert!(std::ptr::eq(
+            switch,
+            GroupSwitch::get(
+                SwitchedMetrics::DESCRIPTOR.crate_name,
+                SwitchedMetrics::DESCRIPTOR.module_path,
+                "SwitchedMetrics"
+            )
+        ));
+
+        metrics.counter.inc();
+        metrics.latencies[&"test"].observe(Duration::from_millis(10));
+        switch.set_enabled(false);
+        metrics.counter.inc_by(5);
+        metrics.gauge.set(3);
+        metrics.latencies[&"test"].observe(Duration::from_millis(10));
+        assert_eq!(metrics.counter.get(), 1);
+        assert_eq!(metrics.gauge.get(), 0);
+
+        let mut buffer = String::new();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        assert_eq!(buffer, "# EOF\n");
+
+        switch.set_enabled(true);
+        metrics.counter.inc();
+        metrics.gauge.set(3);
+        buffer.clear();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        let lines: Vec<_> = buffer.lines().collect();
+        assert!(lines.contains(&"switched_counter_total 2"), "{lines:#?}");
+        assert!(lines.contains(&"switched_gauge 3"));
+        assert!(lines.contains(&"switched_latencies_count{method=\"test\"} 1"));
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+    #[metrics(crate = crate)]
+    struct Config {
+        mode: &'static str,
+    }
+
+    #[derive(Debug, Metrics)]
+    #[metrics(crate = crate, prefix = "guarded")]
+    struct GuardedMetrics {
+        in_flight: Gauge,
+        config: Info<Config>,
+    }
+
+    #[test]
+    fn gauge_guards_and_info_with_disabled_group() {
+        let metrics = GuardedMetrics::default();
+        let switch = GuardedMetrics::DESCRIPTOR.switch();
+
+        let guard = metrics.in_flight.inc_guard(2);
+        switch.set_enabled(false);
+        let skipped_guard = metrics.in_flight.inc_guard(3);
+        assert_eq!(metrics.in_flight.get(), 2);
+        metrics.config.set(Config { mode: "test" }).unwrap();
+        assert!(metrics.config.get().is_none());
+
+        // The guard decrements the gauge even if the group is disabled.
+        drop(guard);
+        assert_eq!(metrics.in_flight.get(), 0);
+        switch.set_enabled(true);
+        // ...and doesn't decrement it if it wasn't incremented.
+        drop(skipped_guard);
+        assert_eq!(metrics.in_flight.get(), 0);
+        metrics.config.set(Config { mode: "test" }).unwrap();
+        assert_eq!(metrics.config.get().unwrap().mode, "test");
+    }
+
+    #[derive(Debug, Metrics)]
+    #[metrics(crate = crate, prefix = "inner")]
+    struct InnerMetrics {
+        counter: Counter,
+        gauge: Gauge,
+    }
+
+    #[derive(Debug, Metrics)]
+    #[metrics(crate = crate, prefix = "outer")]
+    struct OuterMetrics {
+        counter: Counter,
+        #[metrics(flatten)]
+        inner: InnerMetrics,
+    }
+
+    #[derive(Debug, Metrics)]
+    #[metrics(crate = crate, prefix = "root")]
+    struct RootMetrics {
+        #[metrics(prefix = "nested")]
+        outer: OuterMetrics,
+    }
+
+    fn encode(registry: &Registry) -> String {
+        let mut buffer = String::new();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn switches_in_nested_groups() {
+        let metrics = RootMetrics::default();
+        let mut registry = Registry::empty();
+        registry.register_metrics(&metrics);
+        let outer_switch = OuterMetrics::DESCRIPTOR.switch();
+        let inner_switch = InnerMetrics::DESCRIPTOR.switch();
+
+        let nested_groups: Vec<_> = registry
+            .descriptors()
+            .groups()
+            .next()
+            .unwrap()
+            .nested_groups
+            .iter()
+            .map(|group| group.name)
+            .collect();
+        assert_eq!(nested_groups, ["OuterMetrics", "InnerMetrics"]);
+
+        // Disabling the nested group disables its updates and hides it from encoding.
+        inner_switch.set_enabled(false);
+        metrics.outer.counter.inc();
+        metrics.outer.inner.counter.inc();
+        metrics.outer.inner.gauge.set(5);
+        assert_eq!(metrics.outer.counter.get(), 1);
+        assert_eq!(metrics.outer.inner.counter.get(), 0);
+        assert_eq!(metrics.outer.inner.gauge.get(), 0);
+        let buffer = encode(&registry);
+        assert!(
+            buffer.contains("root_nested_outer_counter_total 1"),
+            "{buffer}"
+        );
+        assert!(!buffer.contains("inner"), "{buffer}");
+
+        // Disabling the enclosing group disables the transitively nested group as well.
+        inner_switch.set_enabled(true);
+        outer_switch.set_enabled(false);
+        metrics.outer.counter.inc();
+        metrics.outer.inner.counter.inc();
+        assert_eq!(metrics.outer.counter.get(), 1);
+        assert_eq!(metrics.outer.inner.counter.get(), 0);
+        let buffer = encode(&registry);
+        assert_eq!(buffer, "# EOF\n");
+
+        outer_switch.set_enabled(true);
+        metrics.outer.inner.counter.inc();
+        let buffer = encode(&registry);
+        assert!(
+            buffer.contains("root_nested_inner_counter_total 1"),
+            "{buffer}"
+        );
+
+        // Standalone instances of the nested group are not affected by the enclosing group switch.
+        let standalone = InnerMetrics::default();
+        outer_switch.set_enabled(false);
+        standalone.counter.inc();
+        assert_eq!(standalone.counter.get(), 1);
+        outer_switch.set_enabled(true);
+    }
+}
@@ -1,11 +1,13 @@
 #![allow(clippy::float_cmp)]
 
-use std::{collections::HashMap, time::Duration};
+use std::{collections::HashMap, fmt, hash::Hash, time::Duration};
 
 use assert_matches::assert_matches;
 use derive_more::Display;
+use prometheus_client::encoding::EncodeLabelValue as EncodeLabelValueTrait;
 
 use super::*;
+use crate::descriptors::{MetricGroupDescriptor, Stability};
 
 #[derive(Debug, Display, Clone, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
 #[metrics(crate = crate, label = "method")]
@@ -326,6 +328,505 @@ fn lazy_metrics_registration() {
     assert_test_metrics(&registry);
 }
 
+#[derive(Debug, Metrics)]
+#[metrics(crate = crate, prefix = "test")]
+struct ConflictingMetrics {
+    /// Conflicting counter.
+    counter: Counter,
+    /// Non-conflicting gauge.
+    other_gauge: Gauge,
+}
+
+#[test]
+fn metric_name_conflict() {
+    let mut registry = Registry::empty();
+    registry.register_metrics(&TestMetrics::default());
+    let err = registry
+        .try_register_metrics(&ConflictingMetrics::default())
+        .unwrap_err();
+
+    assert_matches!(
+        &err,
+        RegistrationError::MetricName { name, new, previous }
+            if name == "test_counter"
+                && new.group.name == "ConflictingMetrics"
+                && previous.group.name == "TestMetrics"
+    );
+    let err = err.to_string();
+    assert!(
+        err.contains("vise::tests::ConflictingMetrics.counter"),
+        "{err}"
+    );
+    assert!(err.contains("vise::tests::TestMetrics.counter"), "{err}");
+
+    // The conflicting group must not be partially registered.
+    assert_eq!(registry.descriptors().groups().len(), 1);
+    assert!(registry.descriptors().metric("test_other_gauge").is_none());
+}
+
+#[test]
+fn skipping_conflicting_metrics() {
+    let mut registry = Registry::empty().with_conflict_policy(ConflictPolicy::Skip);
+    let test_metrics = TestMetrics::default();
+    test_metrics.counter.inc();
+    registry.register_metrics(&test_metrics);
+    let conflicting_metrics = ConflictingMetrics::default();
+    conflicting_metrics.counter.inc_by(10);
+    registry.register_metrics(&conflicting_metrics);
+
+    let descriptors = registry.descriptors();
+    assert_eq!(descriptors.groups().len(), 1);
+    let counter_descriptor = descriptors.metric("test_counter").unwrap();
+    assert_eq!(counter_descriptor.group.name, "TestMetrics");
+
+    let mut buffer = String::new();
+    registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+    let lines: Vec<_> = buffer.lines().collect();
+    assert!(lines.contains(&"test_counter_total 1"), "{lines:#?}");
+    assert!(!buffer.contains("test_other_gauge"), "{buffer}");
+}
+
+#[test]
+fn renaming_conflicting_metrics() {
+    let mut registry =
+        Registry::empty().with_conflict_policy(ConflictPolicy::RenameWithCratePrefix);
+    registry.register_metrics(&TestMetrics::default());
+    let conflicting_metrics = ConflictingMetrics::default();
+    conflicting_metrics.counter.inc_by(10);
+    conflicting_metrics.other_gauge.set(3);
+    registry.register_metrics(&conflicting_metrics);
+
+    let descriptors = registry.descriptors();
+    assert_eq!(descriptors.groups().len(), 2);
+    let renamed_descriptor = descriptors.metric("vise_test_counter").unwrap();
+    assert_eq!(renamed_descriptor.group.name, "ConflictingMetrics");
+    assert_eq!(renamed_descriptor.metric.name, "vise_test_counter");
+    assert!(descriptors.metric("vise_test_other_gauge").is_some());
+    assert!(descriptors.metric("test_other_gauge").is_none());
+
+    let mut buffer = String::new();
+    registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+    let lines: Vec<_> = buffer.lines().collect();
+    assert!(lines.contains(&"vise_test_counter_total 10"), "{lines:#?}");
+    assert!(lines.contains(&"vise_test_other_gauge 3"), "{lines:#?}");
+
+    // Renamed metrics conflict with the already renamed group.
+    let err = registry
+        .try_register_metrics(&ConflictingMetrics::default())
+        .unwrap_err();
+    assert_matches!(err, RegistrationError::MetricName { name, .. } if name == "vise_test_counter");
+}
+
+#[test]
+fn label_name_conflict() {
+    #[derive(Debug, Metrics)]
+    #[metrics(crate = crate, prefix = "conflicting")]
+    struct MethodMetrics {
+        requests: Family<Method, Counter>,
+    }
+
+    static GROUP_METRICS: MetricsFamily<Method, MethodMetrics> = MetricsFamily::new();
+
+    // Label conflicts cannot be resolved by renaming.
+    let mut registry =
+        Registry::empty().with_conflict_policy(ConflictPolicy::RenameWithCratePrefix);
+    let err = GROUP_METRICS
+        .collect_to_registry(&mut registry)
+        .unwrap_err();
+    assert_matches!(
+        &err,
+        RegistrationError::LabelName { label: "method", metric }
+            if metric.metric.name == "conflicting_requests"
+    );
+    assert!(
+        err.to_string()
+            .contains("Label `method` is used multiple times"),
+        "{err}"
+    );
+    assert_eq!(registry.descriptors().groups().len(), 0);
+}
+
+#[derive(Debug, Metrics)]
+#[metrics(crate = crate, prefix = "versioned")]
+struct VersionedMetrics {
+    /// Number of processed requests.
+    #[metrics(deprecated_name = "processed_requests")]
+    requests: Counter,
+    /// Experimental gauge.
+    #[metrics(stability = "alpha", unit = Unit::Bytes)]
+    experimental_gauge: Gauge<u64>,
+    /// Latency of requests.
+    #[metrics(deprecated_name = "latency", unit = Unit::Seconds, buckets = Buckets::LATENCIES)]
+    request_latency: Histogram<Duration>,
+}
+
+#[register]
+#[metrics(crate = crate)]
+static VERSIONED_METRICS: Global<VersionedMetrics> = Global::new();
+
+#[test]
+fn describing_stability_and_deprecation() {
+    let mut registry = Registry::empty();
+    registry.register_metrics(&VersionedMetrics::default());
+    let descriptors = registry.descriptors();
+
+    let requests = descriptors.metric("versioned_requests").unwrap();
+    assert_eq!(requests.metric.stability, Stability::Stable);
+    assert_eq!(
+        requests.metric.deprecated_name,
+        Some("versioned_processed_requests")
+    );
+    let deprecated_requests = descriptors.metric("versioned_processed_requests").unwrap();
+    assert_eq!(deprecated_requests.metric.field_name, "requests");
+    let deprecated_latency = descriptors.metric("versioned_latency_seconds").unwrap();
+    assert_eq!(deprecated_latency.metric.field_name, "request_latency");
+
+    let gauge = descriptors
+        .metric("versioned_experimental_gauge_bytes")
+        .unwrap();
+    assert_eq!(gauge.metric.stability, Stability::Alpha);
+    assert_eq!(gauge.metric.deprecated_name, None);
+}
+
+#[test]
+fn exporting_deprecated_names() {
+    let mut registry = Registry::empty();
+    let metrics = VersionedMetrics::default();
+    metrics.requests.inc_by(3);
+    metrics.experimental_gauge.set(42);
+    metrics.request_latency.observe(Duration::from_millis(20));
+    registry.register_metrics(&metrics);
+
+    let mut buffer = String::new();
+    registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+    let lines: Vec<_> = buffer.lines().collect();
+    let expected_lines = [
+        "# HELP versioned_requests Number of processed requests.",
+        "versioned_requests_total 3",
+        "# HELP versioned_processed_requests Number of processed requests \
+         (deprecated name of `versioned_requests`).",
+        "versioned_processed_requests_total 3",
+        "versioned_experimental_gauge_bytes 42",
+        "# HELP versioned_latency_seconds Latency of requests \
+         (deprecated name of `versioned_request_latency_seconds`).",
+        "versioned_request_latency_seconds_count 1",
+        "versioned_latency_seconds_count 1",
+    ];
+    for line in expected_lines {
+        assert!(lines.contains(&line), "{lines:#?}");
+    }
+}
+
+#[test]
+fn hiding_alpha_and_deprecated_metrics() {
+    VERSIONED_METRICS.requests.inc();
+    VERSIONED_METRICS.experimental_gauge.set(1);
+
+    for collection in [MetricsCollection::default(), MetricsCollection::lazy()] {
+        let registry = collection
+            .hide_alpha_metrics()
+            .hide_deprecated_names()
+            .filter(|group| group.name == "VersionedMetrics")
+            .collect();
+        // Descriptors are retained for hidden metrics.
+        assert_eq!(registry.descriptors().metric_count(), 3);
+
+        let mut buffer = String::new();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        assert!(buffer.contains("versioned_requests_total"), "{buffer}");
+        assert!(
+            buffer.contains("versioned_request_latency_seconds_count"),
+            "{buffer}"
+        );
+        assert!(!buffer.contains("experimental_gauge"), "{buffer}");
+        assert!(!buffer.contains("versioned_processed_requests"), "{buffer}");
+        assert!(!buffer.contains("versioned_latency_seconds"), "{buffer}");
+    }
+}
+
+#[test]
+fn filtering_metrics_when_encoding() {
+    let mut registry =
+        Registry::empty().with_conflict_policy(ConflictPolicy::RenameWithCratePrefix);
+    let test_metrics = TestMetrics::default();
+    test_metrics.counter.inc();
+    registry.register_metrics(&test_metrics);
+    let conflicting_metrics = ConflictingMetrics::default();
+    conflicting_metrics.counter.inc_by(10);
+    registry.register_metrics(&conflicting_metrics);
+    registry.register_metrics(&VersionedMetrics::default());
+
+    let encode = |filter: &MetricsFilter| {
+        let mut buffer = String::new();
+        registry
+            .encode_filtered(&mut buffer, Format::OpenMetrics, filter)
+            .unwrap();
+        buffer
+    };
+
+    let buffer = encode(&MetricsFilter::default().with_name("*_counter"));
+    let lines: Vec<_> = buffer.lines().collect();
+    assert!(lines.contains(&"test_counter_total 1"), "{lines:#?}");
+    assert!(lines.contains(&"vise_test_counter_total 10"), "{lines:#?}");
+    assert!(!buffer.contains("gauge"), "{buffer}");
+    assert!(!buffer.contains("versioned_"), "{buffer}");
+    assert_eq!(*lines.last().unwrap(), "# EOF");
+
+    // Renamed metrics are matched by their exported names.
+    let buffer = encode(&MetricsFilter::default().with_name("vise_test_*"));
+    assert!(buffer.contains("vise_test_counter_total 10"), "{buffer}");
+    assert!(buffer.contains("vise_test_other_gauge 0"), "{buffer}");
+    assert!(!buffer.contains("\ntest_counter"), "{buffer}");
+
+    // Deprecated names are matched separately.
+    let buffer = encode(&MetricsFilter::default().with_name("versioned_processed_*"));
+    assert!(
+        buffer.contains("versioned_processed_requests_total 0"),
+        "{buffer}"
+    );
+    assert!(!buffer.contains("versioned_requests_total"), "{buffer}");
+
+    let buffer = encode(&MetricsFilter::default().with_module("vise::tests"));
+    assert_eq!(buffer, {
+        let mut full_buffer = String::new();
+        registry
+            .encode(&mut full_buffer, Format::OpenMetrics)
+            .unwrap();
+        full_buffer
+    });
+    let buffer = encode(&MetricsFilter::default().with_crate("other"));
+    assert_eq!(buffer, "# EOF\n");
+    let buffer = encode(
+        &MetricsFilter::default()
+            .with_crate("vise")
+            .with_name("versioned_*"),
+    );
+    assert!(buffer.contains("versioned_requests_total"), "{buffer}");
+    assert!(!buffer.contains("test_counter"), "{buffer}");
+}
+
+#[test]
+fn filtering_prefixed_collection_when_encoding() {
+    VERSIONED_METRICS.requests.inc();
+
+    for collection in [MetricsCollection::default(), MetricsCollection::lazy()] {
+        let registry = collection
+            .with_prefix("app")
+            .filter(|group| group.name == "VersionedMetrics")
+            .collect();
+        let filter = MetricsFilter::default().with_name("app_versioned_request?");
+        let mut buffer = String::new();
+        registry
+            .encode_filtered(&mut buffer, Format::OpenMetrics, &filter)
+            .unwrap();
+        assert!(buffer.contains("app_versioned_requests_total"), "{buffer}");
+        assert!(!buffer.contains("latency"), "{buffer}");
+        assert!(!buffer.contains("experimental_gauge"), "{buffer}");
+    }
+}
+
+#[test]
+fn filtering_when_encoding_registry_from_collector() {
+    static NESTED_COLLECTOR: Collector<NestedMetrics> = Collector::new();
+
+    NESTED_COLLECTOR
+        .before_scrape(|| {
+            // This registry is encoded while the outer registry is being encoded, and must not inherit its filter.
+            let test_metrics = TestMetrics::default();
+            test_metrics.counter.inc();
+            let mut registry = Registry::empty();
+            registry.register_metrics(&test_metrics);
+            let mut buffer = String::new();
+            registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+            assert!(buffer.contains("test_counter_total 1"), "{buffer}");
+
+            let metrics = NestedMetrics::default();
+            metrics.db_cache.hits.inc_by(3);
+            metrics
+        })
+        .unwrap();
+
+    let mut registry = Registry::empty();
+    registry.register_collector(&NESTED_COLLECTOR);
+    let filter = MetricsFilter::default().with_name("nested_db_*");
+    let mut buffer = String::new();
+    registry
+        .encode_filtered(&mut buffer, Format::OpenMetrics, &filter)
+        .unwrap();
+    assert!(buffer.contains("nested_db_cache_hits_total 3"), "{buffer}");
+    assert!(!buffer.contains("nested_requests"), "{buffer}");
+    assert!(!buffer.contains("\ncache_hits"), "{buffer}");
+
+    // The filter is reset after encoding.
+    let mut buffer = String::new();
+    registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+    assert!(buffer.contains("nested_requests_total 0"), "{buffer}");
+}
+
+#[derive(Debug, Metrics)]
+#[metrics(crate = crate, prefix = "cache")]
+struct CacheMetrics {
+    /// Number of cache hits.
+    hits: Counter,
+    /// Cache size.
+    #[metrics(unit = Unit::Bytes)]
+    size: Gauge<u64>,
+}
+
+#[derive(Debug, Metrics)]
+#[metrics(crate = crate, prefix = "nested")]
+struct NestedMetrics {
+    /// Number of processed requests.
+    requests: Counter,
+    /// Flattened metrics.
+    #[metrics(flatten)]
+    cache: CacheMetrics,
+    /// Prefixed metrics.
+    #[metrics(prefix = "db")]
+    db_cache: CacheMetrics,
+    /// Latency of requests.
+    #[metrics(buckets = Buckets::LATENCIES)]
+    latency: Histogram<Duration>,
+}
+
+#[test]
+fn describing_nested_metrics() {
+    let descriptor = &NestedMetrics::DESCRIPTOR;
+    let metric_names: Vec<_> = descriptor
+        .metrics
+        .iter()
+        .map(|metric| metric.name)
+        .collect();
+    assert_eq!(metric_names, ["nested_requests", "nested_latency"]);
+
+    let mut registry = Registry::empty();
+    registry.register_metrics(&NestedMetrics::default());
+    let descriptors = registry.descriptors();
+    assert_eq!(descriptors.groups().len(), 1);
+    assert_eq!(descriptors.metric_count(), 6);
+    let hits = descriptors.metric("cache_hits").unwrap();
+    assert_eq!(hits.group.name, "NestedMetrics");
+    assert_eq!(hits.metric.field_name, "hits");
+    let db_size = descriptors.metric("nested_db_cache_size_bytes").unwrap();
+    assert_matches!(db_size.metric.unit, Some(Unit::Bytes));
+
+    let group = descriptors.groups().next().unwrap();
+    let metric_names: Vec<_> = group.metrics.iter().map(|metric| metric.name).collect();
+    assert_eq!(
+        metric_names,
+        [
+            "nested_requests",
+            "nested_latency",
+            "cache_hits",
+            "cache_size",
+            "nested_db_cache_hits",
+            "nested_db_cache_size"
+        ]
+    );
+
+    // The merged descriptor must be built once per group type rather than on each registration.
+    let mut other_registry = Registry::empty();
+    other_registry.register_metrics(&NestedMetrics::default());
+    let other_group = other_registry.descriptors().groups().next().unwrap();
+    assert!(std::ptr::eq(group, other_group));
+    assert!(std::ptr::eq(group.metrics, other_group.metrics));
+}
+
+#[test]
+fn exporting_nested_metrics() {
+    let metrics = NestedMetrics::default();
+    metrics.requests.inc();
+    metrics.cache.hits.inc_by(2);
+    metrics.db_cache.hits.inc_by(3);
+    metrics.db_cache.size.set(1_024);
+    metrics.latency.observe(Duration::from_millis(10));
+    let mut registry = Registry::empty();
+    registry.register_metrics(&metrics);
+
+    let mut buffer = String::new();
+    registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+    let lines: Vec<_> = buffer.lines().collect();
+    let expected_lines = [
+        "nested_requests_total 1",
+        "# HELP cache_hits Number of cache hits.",
+        "cache_hits_total 2",
+        "cache_size_bytes 0",
+        "# HELP nested_db_cache_hits Number of cache hits.",
+        "nested_db_cache_hits_total 3",
+        "nested_db_cache_size_bytes 1024",
+        "nested_latency_count 1",
+    ];
+    for line in expected_lines {
+        assert!(lines.contains(&line), "{lines:#?}");
+    }
+}
+
+#[derive(Debug, Metrics)]
+#[metrics(crate = crate, prefix = "custom")]
+struct CustomizedMetrics {
+    /// Number of processed requests.
+    #[metrics(rename = "handled_requests")]
+    requests: Counter,
+    /// Overridden docs.
+    #[metrics(help = "Number of errors.", rename = "errors")]
+    error_count: Counter,
+    #[metrics(help = concat!("Cache size in ", "`custom`"), unit = Unit::Bytes)]
+    cache_size: Gauge<u64>,
+    #[metrics(skip)]
+    config: HashMap<String, u64>,
+    #[metrics(help = HELP, deprecated_name = "old_latency", buckets = Buckets::LATENCIES)]
+    latency: Histogram<Duration>,
+}
+
+const HELP: &str = "Latency with help from a constant";
+
+#[test]
+fn describing_customized_metrics() {
+    let descriptor = &CustomizedMetrics::DESCRIPTOR;
+    let metrics: Vec<_> = descriptor
+        .metrics
+        .iter()
+        .map(|metric| (metric.name, metric.field_name, metric.help))
+        .collect();
+    assert_eq!(
+        metrics,
+        [
+            (
+                "custom_handled_requests",
+                "requests",
+                "Number of processed requests"
+            ),
+            ("custom_errors", "error_count", "Number of errors"),
+            ("custom_cache_size", "cache_size", "Cache size in `custom`"),
+            ("custom_latency", "latency", HELP),
+        ]
+    );
+
+    let metrics = CustomizedMetrics::default();
+    assert!(metrics.config.is_empty());
+    metrics.requests.inc();
+    metrics.error_count.inc_by(2);
+    metrics.latency.observe(Duration::from_millis(5));
+    let mut registry = Registry::empty();
+    registry.register_metrics(&metrics);
+    let mut buffer = String::new();
+    registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+    let lines: Vec<_> = buffer.lines().collect();
+    let expected_lines = [
+        "# HELP custom_handled_requests Number of processed requests.",
+        "custom_handled_requests_total 1",
+        "# HELP custom_errors Number of errors.",
+        "custom_errors_total 2",
+        "# HELP custom_cache_size_bytes Cache size in `custom`.",
+        "# HELP custom_latency Latency with help from a constant.",
+        "# HELP custom_old_latency Latency with help from a constant.",
+        "custom_old_latency_count 1",
+    ];
+    for line in expected_lines {
+        assert!(lines.contains(&line), "{lines:#?}");
+    }
+}
+
 #[test]
 fn using_gauge_guard() {
     let test_metrics: TestMetrics = TestMetrics::default();
@@ -384,6 +885,111 @@ fn using_label_set() {
     assert!(lines.contains(&r#"test_gauges{name="test",num="5"} 4.2"#));
 }
 
+trait StorageBackend: 'static + Send + Sync {
+    type Table: Clone + fmt::Debug + Eq + Hash + Send + Sync + EncodeLabelValueTrait;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue)]
+#[metrics(crate = crate, rename_all = "snake_case")]
+enum RocksdbColumnFamily {
+    State,
+    Blocks,
+}
+
+#[derive(Debug)]
+struct Rocksdb;
+
+impl StorageBackend for Rocksdb {
+    type Table = RocksdbColumnFamily;
+}
+
+#[derive(Debug)]
+struct Postgres;
+
+impl StorageBackend for Postgres {
+    type Table = &'static str;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+#[metrics(crate = crate)]
+struct StorageLabels<T> {
+    table: T,
+    op: &'static str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelValue)]
+#[metrics(crate = crate)]
+struct Shard<T>(T);
+
+impl<T: fmt::Display> fmt::Display for Shard<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "shard-{}", self.0)
+    }
+}
+
+#[derive(Debug, Metrics)]
+#[metrics(crate = crate, prefix = "storage")]
+struct StorageMetrics<B: StorageBackend> {
+    /// Number of storage operations.
+    operations: Family<StorageLabels<B::Table>, Counter>,
+    /// Storage size per shard.
+    #[metrics(labels = ["shard"], unit = Unit::Bytes)]
+    size: LabeledFamily<Shard<u32>, Gauge<u64>>,
+}
+
+#[test]
+fn generic_metrics() {
+    let rocksdb_descriptor: &'static MetricGroupDescriptor = &StorageMetrics::<Rocksdb>::DESCRIPTOR;
+    assert_eq!(rocksdb_descriptor.name, "StorageMetrics");
+    assert_eq!(rocksdb_descriptor.metrics[0].labels, ["table", "op"]);
+    assert_eq!(rocksdb_descriptor.metrics[1].labels, ["shard"]);
+
+    let rocksdb_metrics = StorageMetrics::<Rocksdb>::default();
+    let labels = StorageLabels {
+        table: RocksdbColumnFamily::Blocks,
+        op: "get",
+    };
+    rocksdb_metrics.operations[&labels].inc();
+    let labels = StorageLabels {
+        table: RocksdbColumnFamily::State,
+        op: "put",
+    };
+    rocksdb_metrics.operations[&labels].inc_by(3);
+    rocksdb_metrics.size[&Shard(1)].set(1_024);
+    let postgres_metrics = StorageMetrics::<Postgres>::default();
+    let labels = StorageLabels {
+        table: "transactions",
+        op: "insert",
+    };
+    postgres_metrics.operations[&labels].inc_by(2);
+
+    let mut buffer = String::new();
+    let mut registry = Registry::empty();
+    registry.register_metrics(&rocksdb_metrics);
+    registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+    assert!(
+        buffer.contains(r#"storage_operations_total{table="blocks",op="get"} 1"#),
+        "{buffer}"
+    );
+    assert!(
+        buffer.contains(r#"storage_operations_total{table="state",op="put"} 3"#),
+        "{buffer}"
+    );
+    assert!(
+        buffer.contains(r#"storage_size_bytes{shard="shard-1"} 1024"#),
+        "{buffer}"
+    );
+
+    buffer.clear();
+    let mut registry = Registry::empty();
+    registry.register_metrics(&postgres_metrics);
+    registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+    assert!(
+        buffer.contains(r#"storage_operations_total{table="transactions",op="insert"} 2"#),
+        "{buffer}"
+    );
+}
+
 #[test]
 fn label_with_raw_ident() {
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelSet)]
@@ -469,6 +1075,117 @@ fn renamed_labels() {
     }
 }
 
+#[test]
+fn flattened_labels() {
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+    #[metrics(crate = crate)]
+    struct RpcContext {
+        method: &'static str,
+        network: &'static str,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
+    #[metrics(crate = crate, rename_all = "snake_case", label = "status")]
+    enum Status {
+        Ok,
+        Error,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+    #[metrics(crate = crate)]
+    struct RpcLabels<S> {
+        #[metrics(flatten)]
+        context: RpcContext,
+        #[metrics(flatten)]
+        status: S,
+        error: Option<&'static str>,
+    }
+
+    #[derive(Debug, Metrics)]
+    #[metrics(crate = crate, prefix = "rpc")]
+    struct RpcMetrics {
+        calls: Family<RpcLabels<Status>, Counter>,
+    }
+
+    assert_eq!(
+        <RpcLabels<Status> as traits::LabelSetNames>::LABELS,
+        ["method", "network", "status", "error"]
+    );
+    assert_eq!(
+        RpcMetrics::DESCRIPTOR.metrics[0].labels,
+        ["method", "network", "status", "error"]
+    );
+
+    let context = RpcContext {
+        method: "eth_call",
+        network: "mainnet",
+    };
+    let test_metrics = RpcMetrics::default();
+    test_metrics.calls[&RpcLabels {
+        context: context.clone(),
+        status: Status::Ok,
+        error: None,
+    }]
+        .inc();
+    test_metrics.calls[&RpcLabels {
+        context,
+        status: Status::Error,
+        error: Some("timeout"),
+    }]
+        .inc_by(2);
+
+    let mut registry = Registry::empty();
+    registry.register_metrics(&test_metrics);
+    let mut buffer = String::new();
+    registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+    let lines: Vec<_> = buffer.lines().collect();
+
+    let expected_lines = [
+        r#"rpc_calls_total{method="eth_call",network="mainnet",status="ok"} 1"#,
+        r#"rpc_calls_total{method="eth_call",network="mainnet",status="error",error="timeout"} 2"#,
+    ];
+    for line in expected_lines {
+        assert!(lines.contains(&line), "{lines:#?}");
+    }
+}
+
+#[test]
+fn family_with_manually_implemented_labels() {
+    use prometheus_client::encoding::{EncodeLabel, LabelSetEncoder};
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct ShardLabels(u32);
+
+    impl traits::EncodeLabelSet for ShardLabels {
+        fn encode(&self, encoder: &mut LabelSetEncoder<'_>) -> fmt::Result {
+            ("shard", self.0.to_string()).encode(encoder.encode_label())
+        }
+    }
+
+    #[derive(Debug, Metrics)]
+    #[metrics(crate = crate, prefix = "storage")]
+    struct StorageMetrics {
+        writes: Family<ShardLabels, Counter>,
+        reads: Family<Method, Counter>,
+    }
+
+    let descriptor = &StorageMetrics::DESCRIPTOR;
+    assert!(descriptor.metrics[0].labels.is_empty());
+    assert_eq!(descriptor.metrics[1].labels, ["method"]);
+
+    let test_metrics = StorageMetrics::default();
+    test_metrics.writes[&ShardLabels(3)].inc();
+    let mut registry = Registry::empty();
+    registry.register_metrics(&test_metrics);
+    let mut buffer = String::new();
+    registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+    let lines: Vec<_> = buffer.lines().collect();
+    assert!(
+        lines.contains(&r#"storage_writes_total{shard="3"} 1"#),
+        "{lines:#?}"
+    );
+}
+
 #[test]
 fn labels_with_unit() {
     #[derive(Debug, EncodeLabelSet)]
@@ -665,3 +1382,70 @@ fn owned_string_labeled_family() {
         assert!(lines.contains(&line), "{lines:#?}");
     }
 }
+
+#[test]
+fn sharded_metrics() {
+    const THREAD_COUNT: u64 = 4;
+
+    #[derive(Debug, Metrics)]
+    #[metrics(crate = crate, prefix = "test")]
+    struct ShardedMetrics {
+        #[metrics(sharded)]
+        counter: Counter,
+        #[metrics(sharded)]
+        gauge: Gauge<u64>,
+        #[metrics(sharded)]
+        float_gauge: Gauge<f64>,
+        #[metrics(buckets = &[1.0, 10.0], sharded)]
+        histogram: Histogram<f64>,
+        #[metrics(labels = ["method"], sharded)]
+        family: LabeledFamily<&'static str, Counter>,
+    }
+
+    let metrics = ShardedMetrics::default();
+    metrics.gauge.set(100);
+    std::thread::scope(|scope| {
+        for _ in 0..THREAD_COUNT {
+            scope.spawn(|| {
+                for i in 0..100 {
+                    metrics.counter.inc();
+                    metrics.gauge.inc_by(2);
+                    metrics.float_gauge.inc_by(0.5);
+                    metrics.histogram.observe(f64::from(i % 20));
+                    metrics.family[&"call"].inc_by(3);
+                }
+                // Decrements are not necessarily performed by the same thread as increments.
+                let _guard = metrics.gauge.inc_guard(1);
+                metrics.gauge.dec_by(1);
+            });
+        }
+    });
+
+    assert_eq!(metrics.counter.get(), THREAD_COUNT * 100);
+    assert_eq!(metrics.gauge.get(), 100 + THREAD_COUNT * 199);
+    assert_eq!(metrics.float_gauge.get(), 200.0);
+    assert_eq!(metrics.gauge.set(42), 100 + THREAD_COUNT * 199);
+    assert_eq!(metrics.gauge.get(), 42);
+
+    let mut registry = Registry::empty();
+    registry.register_metrics(&metrics);
+    let mut buffer = String::new();
+    registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+    let lines: Vec<_> = buffer.lines().collect();
+    // Each thread observes values 0..=19 5 times: 2 values fall into the `le=1` bucket, 9 into `le=10`,
+    // and 9 into the `+Inf` bucket.
+    let expected_lines = [
+        "test_counter_total 400",
+        "test_gauge 42",
+        "test_float_gauge 200.0",
+        "test_histogram_sum 3800.0",
+        "test_histogram_count 400",
+        "test_histogram_bucket{le=\"1.0\"} 40",
+        "test_histogram_bucket{le=\"10.0\"} 220",
+        "test_histogram_bucket{le=\"+Inf\"} 400",
+        "test_family_total{method=\"call\"} 1200",
+    ];
+    for line in expected_lines {
+        assert!(lines.contains(&line), "{lines:#?}");
+    }
+}
@@ -0,0 +1,423 @@
+//! `tower` middleware collecting HTTP metrics.
+
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{ready, Context, Poll},
+    time::{Duration, Instant},
+};
+
+use http::{Extensions, Method, Request, Response, StatusCode, Uri};
+use http_body::Body;
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    Buckets, Counter, EncodeLabelSet, EncodeLabelValue, Family, Gauge, GaugeGuard, Global,
+    Histogram, Metrics, Unit,
+};
+
+/// Buckets for request and response body sizes: 64 B to 16 MiB.
+const SIZE_BUCKETS: Buckets = Buckets::exponential(64.0..=16_777_216.0, 4.0);
+
+/// HTTP request method used as a metric label. Non-standard methods are reported as `_OTHER`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue)]
+#[metrics(crate = crate, rename_all = "UPPERCASE")]
+#[allow(missing_docs)] // variants are self-explanatory
+pub enum HttpMethod {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Connect,
+    Options,
+    Trace,
+    Patch,
+    #[metrics(name = "_OTHER")]
+    Other,
+}
+
+impl From<&Method> for HttpMethod {
+    fn from(method: &Method) -> Self {
+        match *method {
+            Method::GET => Self::Get,
+            Method::HEAD => Self::Head,
+            Method::POST => Self::Post,
+            Method::PUT => Self::Put,
+            Method::DELETE => Self::Delete,
+            Method::CONNECT => Self::Connect,
+            Method::OPTIONS => Self::Options,
+            Method::TRACE => Self::Trace,
+            Method::PATCH => Self::Patch,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Class of the HTTP response status used as a metric label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue)]
+#[metrics(crate = crate, rename_all = "snake_case")]
+pub enum HttpStatusClass {
+    /// 1xx status codes.
+    #[metrics(name = "1xx")]
+    Informational,
+    /// 2xx status codes.
+    #[metrics(name = "2xx")]
+    Success,
+    /// 3xx status codes.
+    #[metrics(name = "3xx")]
+    Redirection,
+    /// 4xx status codes.
+    #[metrics(name = "4xx")]
+    ClientError,
+    /// 5xx status codes.
+    #[metrics(name = "5xx")]
+    ServerError,
+    /// The wrapped service returned an error instead of a response.
+    Error,
+}
+
+impl From<StatusCode> for HttpStatusClass {
+    fn from(status: StatusCode) -> Self {
+        match status.as_u16() {
+            100..=199 => Self::Informational,
+            200..=299 => Self::Success,
+            300..=399 => Self::Redirection,
+            400..=499 => Self::ClientError,
+            _ => Self::ServerError,
+        }
+    }
+}
+
+/// Labels for HTTP request metrics.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+#[metrics(crate = crate)]
+pub struct HttpRequestLabels {
+    /// Request method.
+    pub method: HttpMethod,
+    /// Route template, e.g. `/users/{id}`. Skipped if not determined.
+    pub route: Option<String>,
+}
+
+/// Labels for HTTP response metrics.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+#[metrics(crate = crate)]
+pub struct HttpResponseLabels {
+    /// Request labels.
+    #[metrics(flatten)]
+    pub request: HttpRequestLabels,
+    /// Response status class.
+    pub status: HttpStatusClass,
+}
+
+/// Standard HTTP metrics collected by [`HttpMetricsLayer`].
+#[derive(Debug, Metrics)]
+#[metrics(crate = crate, prefix = "http")]
+pub struct HttpMetrics {
+    /// Latency of handling HTTP requests until the response head is available.
+    #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
+    pub request_duration: Family<HttpResponseLabels, Histogram<Duration>>,
+    /// Number of HTTP requests currently being handled.
+    pub requests_in_flight: Family<HttpRequestLabels, Gauge>,
+    /// Size of HTTP request bodies. Only reported for bodies with the size known in advance.
+    #[metrics(buckets = SIZE_BUCKETS, unit = Unit::Bytes)]
+    pub request_size: Family<HttpRequestLabels, Histogram<u64>>,
+    /// Size of HTTP response bodies. Only reported for bodies with the size known in advance.
+    #[metrics(buckets = SIZE_BUCKETS, unit = Unit::Bytes)]
+    pub response_size: Family<HttpResponseLabels, Histogram<u64>>,
+    /// Number of handled HTTP requests.
+    pub responses: Family<HttpResponseLabels, Counter>,
+}
+
+#[crate::register]
+#[metrics(crate = crate)]
+static HTTP_METRICS: Global<HttpMetrics> = Global::new();
+
+type RouteFn = dyn Fn(&Uri, &Extensions) -> Option<String> + Send + Sync;
+
+/// [`Layer`] collecting [`HttpMetrics`] for wrapped HTTP services. Can be used both for servers
+/// (e.g., `hyper`, `axum` or `tonic` services) and clients.
+///
+/// By default, metrics are reported to a [`Global`] instance registered
+/// in [`METRICS_REGISTRATIONS`](crate::METRICS_REGISTRATIONS) with the `http` prefix
+/// (e.g., `http_request_duration_seconds`). To use another prefix (e.g., to distinguish between
+/// server and client metrics), nest `HttpMetrics` in your own metrics and pass them to [`Self::new()`].
+///
+/// Since request paths may have unbounded cardinality, the `route` label is only reported
+/// if a route template is extracted by a function provided via [`Self::with_route()`].
+///
+/// # Examples
+///
+/// ```
+/// use vise::{Global, HttpMetrics, HttpMetricsLayer, Metrics};
+///
+/// #[derive(Debug, Metrics)]
+/// struct ClientMetrics {
+///     #[metrics(prefix = "client")]
+///     http: HttpMetrics,
+/// }
+///
+/// #[vise::register]
+/// static CLIENT_METRICS: Global<ClientMetrics> = Global::new();
+///
+/// // Metrics will be reported as `client_http_request_duration_seconds` etc.
+/// let layer = HttpMetricsLayer::new(&CLIENT_METRICS.http)
+///     .with_route(|uri, _| uri.path().starts_with("/api").then(|| "/api".to_owned()));
+/// let client = tower::ServiceBuilder::new()
+///     .layer(layer)
+///     .service_fn(|_req: http::Request<String>| async {
+///         Ok::<_, std::convert::Infallible>(http::Response::new(String::new()))
+///     });
+/// ```
+#[derive(Clone)]
+pub struct HttpMetricsLayer {
+    metrics: &'static HttpMetrics,
+    route: Option<Arc<RouteFn>>,
+}
+
+impl fmt::Debug for HttpMetricsLayer {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("HttpMetricsLayer")
+            .field("metrics", &self.metrics)
+            .field("has_route", &self.route.is_some())
+            .finish()
+    }
+}
+
+impl Default for HttpMetricsLayer {
+    fn default() -> Self {
+        Self::new(&HTTP_METRICS)
+    }
+}
+
+impl HttpMetricsLayer {
+    /// Creates a layer reporting to the specified metrics.
+    pub fn new(metrics: &'static HttpMetrics) -> Self {
+        Self {
+            metrics,
+            route: None,
+        }
+    }
+
+    /// Sets the function extracting a route template from the request URI and extensions. For example,
+    /// the function may read `axum::extract::MatchedPath` from extensions.
+    #[must_use]
+    pub fn with_route<F>(mut self, route_fn: F) -> Self
+    where
+        F: Fn(&Uri, &Extensions) -> Option<String> + Send + Sync + 'static,
+    {
+        self.route = Some(Arc::new(route_fn));
+        self
+    }
+}
+
+impl<S> Layer<S> for HttpMetricsLayer {
+    type Service = HttpMetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HttpMetricsService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// HTTP service wrapped by [`HttpMetricsLayer`].
+#[derive(Debug, Clone)]
+pub struct HttpMetricsService<S> {
+    inner: S,
+    layer: HttpMetricsLayer,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for HttpMetricsService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    ReqBody: Body,
+    ResBody: Body,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = HttpMetricsFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let metrics = self.layer.metrics;
+        let route = self
+            .layer
+            .route
+            .as_ref()
+            .and_then(|route_fn| route_fn(request.uri(), request.extensions()));
+        let labels = HttpRequestLabels {
+            method: request.method().into(),
+            route,
+        };
+        if let Some(size) = request.body().size_hint().exact() {
+            metrics.request_size[&labels].observe(size);
+        }
+        let in_flight_guard = metrics.requests_in_flight[&labels].inc_guard(1);
+
+        HttpMetricsFuture {
+            inner: self.inner.call(request),
+            state: Some(RequestState {
+                metrics,
+                labels,
+                start: Instant::now(),
+                _in_flight_guard: in_flight_guard,
+            }),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RequestState {
+    metrics: &'static HttpMetrics,
+    labels: HttpRequestLabels,
+    start: Instant,
+    _in_flight_guard: GaugeGuard,
+}
+
+impl RequestState {
+    fn finish<B: Body, E>(self, output: &Result<Response<B>, E>) {
+        let latency = self.start.elapsed();
+        let status = match output {
+            Ok(response) => response.status().into(),
+            Err(_) => HttpStatusClass::Error,
+        };
+        let labels = HttpResponseLabels {
+            request: self.labels,
+            status,
+        };
+
+        self.metrics.request_duration[&labels].observe(latency);
+        self.metrics.responses[&labels].inc();
+        if let Ok(response) = output {
+            if let Some(size) = response.body().size_hint().exact() {
+                self.metrics.response_size[&labels].observe(size);
+            }
+        }
+    }
+}
+
+pin_project! {
+    /// Response future returned by [`HttpMetricsService`].
+    #[derive(Debug)]
+    pub struct HttpMetricsFuture<F> {
+        #[pin]
+        inner: F,
+        state: Option<RequestState>,
+    }
+}
+
+impl<F, B, E> Future for HttpMetricsFuture<F>
+where
+    F: Future<Output = Result<Response<B>, E>>,
+    B: Body,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let projection = self.project();
+        let output = ready!(projection.inner.poll(cx));
+        if let Some(state) = projection.state.take() {
+            state.finish(&output);
+        }
+        Poll::Ready(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower::{service_fn, ServiceExt};
+
+    use super::*;
+    use crate::{Format, Registry};
+
+    #[derive(Debug, Metrics)]
+    #[metrics(crate = crate)]
+    struct TestMetrics {
+        #[metrics(prefix = "server")]
+        http: HttpMetrics,
+    }
+
+    static TEST_METRICS: Global<TestMetrics> = Global::new();
+
+    #[tokio::test]
+    async fn collecting_http_metrics() {
+        let layer = HttpMetricsLayer::new(&TEST_METRICS.http).with_route(|uri, _| {
+            uri.path()
+                .starts_with("/users/")
+                .then(|| "/users/{id}".into())
+        });
+        let service = layer.layer(service_fn(|request: Request<String>| async move {
+            if request.uri().path() == "/fail" {
+                return Err("oops");
+            }
+            let in_flight_labels = HttpRequestLabels {
+                method: HttpMethod::Post,
+                route: Some("/users/{id}".into()),
+            };
+            let in_flight = TEST_METRICS.http.requests_in_flight[&in_flight_labels].get();
+            assert_eq!(in_flight, 1);
+
+            let mut response = Response::new(request.into_body().repeat(2));
+            *response.status_mut() = StatusCode::CREATED;
+            Ok(response)
+        }));
+
+        let request = Request::post("/users/1").body("test".to_owned()).unwrap();
+        let response = service.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.into_body(), "testtest");
+        let request = Request::post("/users/2").body(String::new()).unwrap();
+        service.clone().oneshot(request).await.unwrap();
+        let request = Request::post("/fail").body(String::new()).unwrap();
+        service.oneshot(request).await.unwrap_err();
+
+        let mut registry = Registry::empty();
+        registry.register_metrics(&*TEST_METRICS);
+        let mut buffer = String::new();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        let lines: Vec<_> = buffer.lines().collect();
+
+        let labels = r#"method="POST",route="/users/{id}""#;
+        let expected_lines = [
+            format!(r#"server_http_responses_total{{{labels},status="2xx"}} 2"#),
+            r#"server_http_responses_total{method="POST",status="error"} 1"#.to_owned(),
+            format!(r#"server_http_request_duration_seconds_count{{{labels},status="2xx"}} 2"#),
+            format!("server_http_requests_in_flight{{{labels}}} 0"),
+            format!("server_http_request_size_bytes_sum{{{labels}}} 4.0"),
+            format!("server_http_request_size_bytes_count{{{labels}}} 2"),
+            format!(r#"server_http_response_size_bytes_sum{{{labels},status="2xx"}} 8.0"#),
+        ];
+        for line in &expected_lines {
+            assert!(lines.contains(&line.as_str()), "{lines:#?}");
+        }
+    }
+
+    #[test]
+    fn converting_http_types() {
+        assert_eq!(HttpMethod::from(&Method::GET), HttpMethod::Get);
+        let custom_method = Method::from_bytes(b"PURGE").unwrap();
+        assert_eq!(HttpMethod::from(&custom_method), HttpMethod::Other);
+
+        assert_eq!(
+            HttpStatusClass::from(StatusCode::OK),
+            HttpStatusClass::Success
+        );
+        assert_eq!(
+            HttpStatusClass::from(StatusCode::NOT_FOUND),
+            HttpStatusClass::ClientError
+        );
+        assert_eq!(
+            HttpStatusClass::from(StatusCode::BAD_GATEWAY),
+            HttpStatusClass::ServerError
+        );
+    }
+}
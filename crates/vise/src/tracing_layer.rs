@@ -0,0 +1,513 @@
+//! `tracing` layer converting spans and events into metrics.
+
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use prometheus_client::encoding::{EncodeLabel, LabelSetEncoder};
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Metadata, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+use crate::{
+    descriptors::{MetricGroupDescriptor, Stability},
+    names::sanitize_label_name,
+    registry::MetricsVisitor,
+    traits::EncodeLabelSet,
+    wrappers::{Family, Histogram},
+    BeforeScrapeError, Buckets, Collector, Counter, MetricBuilder, Metrics, Unit,
+};
+
+/// Default maximum number of label sets with span fields.
+const DEFAULT_MAX_LABEL_SETS: usize = 1_000;
+/// Value substituted for all span field labels once the label set limit is reached.
+const OVERFLOW_VALUE: &str = "_overflow";
+
+/// Labels for span metrics: span name and target, together with allowlisted span fields.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SpanLabels {
+    name: &'static str,
+    target: &'static str,
+    /// Sorted by the field name.
+    fields: Vec<(&'static str, String)>,
+}
+
+impl SpanLabels {
+    fn new(metadata: &'static Metadata<'static>) -> Self {
+        Self {
+            name: metadata.name(),
+            target: metadata.target(),
+            fields: vec![],
+        }
+    }
+
+    fn set_field(&mut self, name: &'static str, value: String) {
+        match self.fields.binary_search_by_key(&name, |(name, _)| name) {
+            Ok(idx) => self.fields[idx].1 = value,
+            Err(idx) => self.fields.insert(idx, (name, value)),
+        }
+    }
+
+    fn overflow(&mut self) {
+        for (_, value) in &mut self.fields {
+            OVERFLOW_VALUE.clone_into(value);
+        }
+    }
+}
+
+impl EncodeLabelSet for SpanLabels {
+    fn encode(&self, encoder: &mut LabelSetEncoder<'_>) -> fmt::Result {
+        ("span", self.name).encode(encoder.encode_label())?;
+        ("target", self.target).encode(encoder.encode_label())?;
+        for (name, value) in &self.fields {
+            (sanitize_label_name(name), value.as_str()).encode(encoder.encode_label())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, crate::EncodeLabelSet)]
+#[metrics(crate = crate)]
+struct EventLabels {
+    level: &'static str,
+    target: &'static str,
+}
+
+impl EventLabels {
+    fn new(metadata: &'static Metadata<'static>) -> Self {
+        Self {
+            level: metadata.level().as_str(),
+            target: metadata.target(),
+        }
+    }
+}
+
+/// Timing information for a span stored in its extensions.
+#[derive(Debug)]
+struct SpanTiming {
+    labels: SpanLabels,
+    busy: Duration,
+    idle: Duration,
+    last_transition: Instant,
+    entered_count: usize,
+}
+
+impl SpanTiming {
+    fn new(labels: SpanLabels) -> Self {
+        Self {
+            labels,
+            busy: Duration::ZERO,
+            idle: Duration::ZERO,
+            last_transition: Instant::now(),
+            entered_count: 0,
+        }
+    }
+
+    fn enter(&mut self, now: Instant) {
+        if self.entered_count == 0 {
+            self.idle += now.saturating_duration_since(self.last_transition);
+            self.last_transition = now;
+        }
+        self.entered_count += 1;
+    }
+
+    fn exit(&mut self, now: Instant) {
+        self.entered_count = self.entered_count.saturating_sub(1);
+        if self.entered_count == 0 {
+            self.busy += now.saturating_duration_since(self.last_transition);
+            self.last_transition = now;
+        }
+    }
+
+    fn close(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_transition);
+        if self.entered_count == 0 {
+            self.idle += elapsed;
+        } else {
+            self.busy += elapsed;
+        }
+    }
+}
+
+/// Records allowlisted span fields as labels.
+struct LabelsVisitor<'a> {
+    label_fields: &'a HashSet<String>,
+    labels: &'a mut SpanLabels,
+}
+
+impl Visit for LabelsVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if self.label_fields.contains(field.name()) {
+            self.labels.set_field(field.name(), value.to_owned());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if self.label_fields.contains(field.name()) {
+            self.labels.set_field(field.name(), format!("{value:?}"));
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TracingMetrics {
+    span_busy_duration: Family<SpanLabels, Histogram<Duration>>,
+    span_idle_duration: Family<SpanLabels, Histogram<Duration>>,
+    span_label_overflows: Counter,
+    events: Family<EventLabels, Counter>,
+}
+
+impl TracingMetrics {
+    fn new(buckets: Buckets) -> Self {
+        let histogram_builder = MetricBuilder::new().with_buckets(buckets);
+        Self {
+            span_busy_duration: Family::new(histogram_builder, ()),
+            span_idle_duration: Family::new(histogram_builder, ()),
+            span_label_overflows: Counter::default(),
+            events: Family::new(MetricBuilder::new(), ()),
+        }
+    }
+}
+
+/// [`Layer`] for `tracing` subscribers that converts spans and events into metrics.
+///
+/// The layer reports the following metrics:
+///
+/// - `tracing_span_busy_duration_seconds` / `tracing_span_idle_duration_seconds`: histograms
+///   of the time spent by closed spans inside / outside of them. Labelled by the span name (`span`),
+///   its `target` and the allowlisted span fields.
+/// - `tracing_events_total`: number of emitted events labelled by their `level` and `target`.
+/// - `tracing_span_label_overflows_total`: number of times span field labels were replaced
+///   because of the label set limit.
+///
+/// By default, all spans are recorded without field labels, and histograms use [`Buckets::LATENCIES`].
+/// Since span fields may have unbounded cardinality, the number of label sets with span fields
+/// is (approximately) limited; once the limit is reached, all field labels in new label sets are replaced
+/// with `_overflow`.
+///
+/// Metrics can be registered in a [`Registry`](crate::Registry) manually, or globally
+/// using [`Self::register_collector()`].
+///
+/// # Examples
+///
+/// ```
+/// use tracing_subscriber::layer::SubscriberExt;
+/// use vise::MetricsLayer;
+///
+/// let layer = MetricsLayer::default()
+///     .with_span("handle_request")
+///     .with_label_field("method");
+/// layer.register_collector().expect("tracing metrics are already collected");
+/// let subscriber = tracing_subscriber::registry().with(layer);
+/// tracing::subscriber::with_default(subscriber, || {
+///     let span = tracing::info_span!("handle_request", method = "eth_call");
+///     let _guard = span.enter();
+///     // Handle the request...
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct MetricsLayer {
+    spans: HashSet<String>,
+    label_fields: HashSet<String>,
+    max_label_sets: usize,
+    label_sets: Arc<AtomicUsize>,
+    metrics: TracingMetrics,
+}
+
+impl Default for MetricsLayer {
+    fn default() -> Self {
+        Self {
+            spans: HashSet::new(),
+            label_fields: HashSet::new(),
+            max_label_sets: DEFAULT_MAX_LABEL_SETS,
+            label_sets: Arc::default(),
+            metrics: TracingMetrics::new(Buckets::LATENCIES),
+        }
+    }
+}
+
+impl MetricsLayer {
+    /// Configures buckets for span duration histograms.
+    #[must_use]
+    pub fn with_buckets(mut self, buckets: Buckets) -> Self {
+        self.metrics = TracingMetrics::new(buckets);
+        self
+    }
+
+    /// Adds a span name to the allowlist. If the allowlist is non-empty, only spans with the allowlisted
+    /// names are recorded; otherwise, all spans are recorded.
+    #[must_use]
+    pub fn with_span(mut self, name: impl Into<String>) -> Self {
+        self.spans.insert(name.into());
+        self
+    }
+
+    /// Adds a span field to use as a label. Field values are recorded using their `Debug` implementation
+    /// (except for strings, which are recorded as is). Chars not allowed in label names are replaced
+    /// with underscores; fields should not be named `span` or `target` to not clash with other labels.
+    #[must_use]
+    pub fn with_label_field(mut self, name: impl Into<String>) -> Self {
+        self.label_fields.insert(name.into());
+        self
+    }
+
+    /// Sets the maximum number of label sets with span fields. The default value is 1,000.
+    #[must_use]
+    pub fn with_max_label_sets(mut self, max_label_sets: usize) -> Self {
+        self.max_label_sets = max_label_sets;
+        self
+    }
+
+    /// Registers a collector exporting metrics of this layer in [`METRICS_REGISTRATIONS`](crate::METRICS_REGISTRATIONS).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the collector is already registered.
+    pub fn register_collector(&self) -> Result<(), BeforeScrapeError> {
+        let this = self.clone();
+        LAYER_COLLECTOR.before_scrape(move || this.clone())
+    }
+
+    fn is_span_recorded(&self, name: &str) -> bool {
+        self.spans.is_empty() || self.spans.contains(name)
+    }
+
+    fn observe(&self, timing: SpanTiming) {
+        let mut labels = timing.labels;
+        let durations = &self.metrics.span_busy_duration;
+        if !labels.fields.is_empty() && !durations.contains(&labels) {
+            let label_sets = self.label_sets.fetch_add(1, Ordering::Relaxed);
+            if label_sets >= self.max_label_sets {
+                self.label_sets.fetch_sub(1, Ordering::Relaxed);
+                self.metrics.span_label_overflows.inc();
+                labels.overflow();
+            }
+        }
+        self.metrics.span_busy_duration[&labels].observe(timing.busy);
+        self.metrics.span_idle_duration[&labels].observe(timing.idle);
+    }
+}
+
+impl<S> Layer<S> for MetricsLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let metadata = attrs.metadata();
+        if !self.is_span_recorded(metadata.name()) {
+            return;
+        }
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut labels = SpanLabels::new(metadata);
+        attrs.record(&mut LabelsVisitor {
+            label_fields: &self.label_fields,
+            labels: &mut labels,
+        });
+        span.extensions_mut().insert(SpanTiming::new(labels));
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(timing) = extensions.get_mut::<SpanTiming>() {
+            values.record(&mut LabelsVisitor {
+                label_fields: &self.label_fields,
+                labels: &mut timing.labels,
+            });
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        self.metrics.events[&EventLabels::new(event.metadata())].inc();
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(timing) = extensions.get_mut::<SpanTiming>() {
+            timing.enter(Instant::now());
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(timing) = extensions.get_mut::<SpanTiming>() {
+            timing.exit(Instant::now());
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let timing = span.extensions_mut().remove::<SpanTiming>();
+        if let Some(mut timing) = timing {
+            timing.close(Instant::now());
+            self.observe(timing);
+        }
+    }
+}
+
+impl Metrics for MetricsLayer {
+    const DESCRIPTOR: MetricGroupDescriptor = MetricGroupDescriptor {
+        crate_name: "vise",
+        crate_version: env!("CARGO_PKG_VERSION"),
+        module_path: module_path!(),
+        name: "MetricsLayer",
+        line: line!(),
+        labels: &[],
+        metrics: &[],
+        nested_groups: &[],
+    };
+
+    fn visit_metrics(&self, visitor: &mut dyn MetricsVisitor) {
+        let metrics = &self.metrics;
+        visitor.visit_metric(
+            Cow::Borrowed("tracing_span_busy_duration"),
+            "Time spent by closed spans while they were entered",
+            Some(Unit::Seconds),
+            Stability::Stable,
+            false,
+            Box::new(metrics.span_busy_duration.clone()),
+        );
+        visitor.visit_metric(
+            Cow::Borrowed("tracing_span_idle_duration"),
+            "Time spent by closed spans while they were not entered",
+            Some(Unit::Seconds),
+            Stability::Stable,
+            false,
+            Box::new(metrics.span_idle_duration.clone()),
+        );
+        visitor.visit_metric(
+            Cow::Borrowed("tracing_span_label_overflows"),
+            "Number of times span field labels were replaced because of the label set limit",
+            None,
+            Stability::Stable,
+            false,
+            Box::new(metrics.span_label_overflows.clone()),
+        );
+        visitor.visit_metric(
+            Cow::Borrowed("tracing_events"),
+            "Number of emitted tracing events",
+            None,
+            Stability::Stable,
+            false,
+            Box::new(metrics.events.clone()),
+        );
+    }
+}
+
+#[crate::register]
+#[metrics(crate = crate)]
+static LAYER_COLLECTOR: Collector<MetricsLayer> = Collector::new();
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::{Format, Registry};
+
+    fn encode(layer: &MetricsLayer) -> String {
+        let mut registry = Registry::empty();
+        registry.register_metrics(layer);
+        let mut buffer = String::new();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn recording_spans_and_events() {
+        let layer = MetricsLayer::default()
+            .with_buckets(Buckets::values(&[0.001, 1.0]))
+            .with_span("call")
+            .with_label_field("method")
+            .with_label_field("http.status");
+        let subscriber = tracing_subscriber::registry().with(layer.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!(
+                target: "test",
+                "call",
+                method = "get",
+                http.status = tracing::field::Empty,
+                id = 42
+            );
+            for _ in 0..2 {
+                let _guard = span.enter();
+                tracing::warn!(target: "test", "oops");
+            }
+            span.record("http.status", 200);
+            drop(span);
+
+            let _ignored = tracing::info_span!("ignored").entered();
+            tracing::info!(target: "test", "done");
+        });
+
+        let buffer = encode(&layer);
+        let lines: Vec<_> = buffer.lines().collect();
+        let labels = r#"span="call",target="test",http_status="200",method="get""#;
+        let expected_lines = [
+            format!("tracing_span_busy_duration_seconds_count{{{labels}}} 1"),
+            format!(r#"tracing_span_busy_duration_seconds_bucket{{le="1.0",{labels}}} 1"#),
+            format!("tracing_span_idle_duration_seconds_count{{{labels}}} 1"),
+            r#"tracing_events_total{level="WARN",target="test"} 2"#.to_owned(),
+            r#"tracing_events_total{level="INFO",target="test"} 1"#.to_owned(),
+            "tracing_span_label_overflows_total 0".to_owned(),
+        ];
+        for line in &expected_lines {
+            assert!(lines.contains(&line.as_str()), "{lines:#?}");
+        }
+        assert!(!buffer.contains("ignored"), "{buffer}");
+    }
+
+    #[test]
+    fn limiting_label_sets() {
+        let layer = MetricsLayer::default()
+            .with_label_field("id")
+            .with_max_label_sets(2);
+        let subscriber = tracing_subscriber::registry().with(layer.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            for id in [1, 2, 1, 3, 4] {
+                tracing::info_span!(target: "test", "call", id).in_scope(|| {});
+            }
+            tracing::info_span!(target: "test", "no_fields").in_scope(|| {});
+        });
+
+        let buffer = encode(&layer);
+        let lines: Vec<_> = buffer.lines().collect();
+        let expected_lines = [
+            r#"tracing_span_busy_duration_seconds_count{span="call",target="test",id="1"} 2"#,
+            r#"tracing_span_busy_duration_seconds_count{span="call",target="test",id="2"} 1"#,
+            r#"tracing_span_busy_duration_seconds_count{span="call",target="test",id="_overflow"} 2"#,
+            r#"tracing_span_busy_duration_seconds_count{span="no_fields",target="test"} 1"#,
+            "tracing_span_label_overflows_total 2",
+        ];
+        for line in expected_lines {
+            assert!(lines.contains(&line), "{lines:#?}");
+        }
+    }
+
+    #[test]
+    fn sanitizing_label_names() {
+        assert_eq!(sanitize_label_name("method"), "method");
+        assert_eq!(sanitize_label_name("http.status_code"), "http_status_code");
+    }
+}
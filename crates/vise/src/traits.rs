@@ -57,6 +57,32 @@ impl<T: EncodeLabel + Send + Sync> EncodeLabelSet for Vec<T> {
     }
 }
 
+/// Names of labels in a label set known during compilation. Used in [metric descriptors](crate::descriptors)
+/// for [`Family`](crate::Family) and [`MetricsFamily`](crate::MetricsFamily) labels.
+///
+/// This trait is implemented by the [`EncodeLabelSet`](macro@crate::EncodeLabelSet) derive macro.
+/// It is optional for [`Family`](crate::Family) labels in the [`Metrics`](macro@crate::Metrics) derive:
+/// families with a manually implemented `EncodeLabelSet` are reported without label names.
+pub trait LabelSetNames {
+    /// Names of labels that can be encoded by this set, in the encoding order.
+    ///
+    /// Empty if label names cannot be determined statically. Labels may be skipped during encoding
+    /// (e.g., `Option`al labels that are `None`), so this is an upper bound on the encoded labels.
+    const LABELS: &'static [&'static str] = &[];
+}
+
+impl LabelSetNames for () {}
+
+impl<T: LabelSetNames + ?Sized> LabelSetNames for &T {
+    const LABELS: &'static [&'static str] = T::LABELS;
+}
+
+impl<T> LabelSetNames for [T] {}
+
+impl<T, const N: usize> LabelSetNames for [T; N] {}
+
+impl<T> LabelSetNames for Vec<T> {}
+
 /// Encoded value of a gauge.
 #[derive(Debug, Clone, Copy)]
 pub enum EncodedGaugeValue {
@@ -274,6 +300,18 @@ impl<S: EncodeLabelSet> MapLabels<S> for () {
     }
 }
 
+/// [`MapLabels`] with label names known during compilation. Used in [metric descriptors](crate::descriptors).
+pub trait MapLabelNames<S>: MapLabels<S> {
+    /// Label names known statically from the mapping. Empty by default; label names of the label type `S`
+    /// are resolved separately via [`LabelSetNames`], and names specified as values
+    /// (like for [`LabeledFamily`](crate::LabeledFamily)) are taken from the `labels` attribute.
+    const LABELS: &'static [&'static str] = &[];
+}
+
+impl<S: EncodeLabelSet> MapLabelNames<S> for () {}
+
+impl<S, const N: usize> MapLabelNames<S> for [&'static str; N] where Self: MapLabels<S> {}
+
 /// Wrapper around a reference to labels proxying necessary trait implementations.
 // We cannot use a reference directly because there are no blanket implementations for `EncodeLabelSet`
 // and `EncodeLabelValue`.
@@ -111,6 +111,74 @@ pub const fn assert_metric_prefix(name: &str) {
     }
 }
 
+/// Maximum number of labels in a [`LabelNames`] set.
+pub const MAX_LABELS: usize = 32;
+
+const fn str_eq(lhs: &str, rhs: &str) -> bool {
+    let (lhs, rhs) = (lhs.as_bytes(), rhs.as_bytes());
+    if lhs.len() != rhs.len() {
+        return false;
+    }
+    let mut pos = 0;
+    while pos < lhs.len() {
+        if lhs[pos] != rhs[pos] {
+            return false;
+        }
+        pos += 1;
+    }
+    true
+}
+
+/// Label names of a label set concatenated from multiple parts (e.g., from flattened label sets)
+/// in compile time.
+#[derive(Debug)]
+pub struct LabelNames {
+    names: [&'static str; MAX_LABELS],
+    len: usize,
+}
+
+impl LabelNames {
+    /// Concatenates label names from the provided parts, checking that there are no duplicates.
+    #[track_caller]
+    pub const fn concat(parts: &[&'static [&'static str]]) -> Self {
+        let mut names = [""; MAX_LABELS];
+        let mut len = 0;
+        let mut part_idx = 0;
+        while part_idx < parts.len() {
+            let part = parts[part_idx];
+            let mut idx = 0;
+            while idx < part.len() {
+                let name = part[idx];
+                let mut prev_idx = 0;
+                while prev_idx < len {
+                    if str_eq(names[prev_idx], name) {
+                        compile_panic!(
+                            "Label `", name => clip(32, "…"), "` is defined multiple times in the label set"
+                        );
+                    }
+                    prev_idx += 1;
+                }
+                if len == MAX_LABELS {
+                    compile_panic!(
+                        "Label set has too many labels; the maximum supported number is ",
+                        MAX_LABELS => fmt::<usize>()
+                    );
+                }
+                names[len] = name;
+                len += 1;
+                idx += 1;
+            }
+            part_idx += 1;
+        }
+        Self { names, len }
+    }
+
+    /// Returns concatenated label names.
+    pub const fn as_slice(&'static self) -> &'static [&'static str] {
+        self.names.split_at(self.len).0
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -127,4 +195,16 @@ mod tests {
         validate_name("t!st").unwrap_err();
         validate_name("1est").unwrap_err();
     }
+
+    #[test]
+    fn concatenating_label_names() {
+        static NAMES: LabelNames = LabelNames::concat(&[&["method"], &[], &["network", "status"]]);
+        assert_eq!(NAMES.as_slice(), ["method", "network", "status"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Label `method` is defined multiple times")]
+    fn duplicate_label_names() {
+        LabelNames::concat(&[&["method"], &["network", "method"]]);
+    }
 }
@@ -1,119 +1,225 @@
 //! Wrappers for metric types defined in `prometheus-client`.
 
 use std::{
+    any::TypeId,
     borrow::Borrow,
-    collections::HashMap,
     fmt,
-    hash::Hash,
+    hash::{Hash, Hasher},
     marker::PhantomData,
     ops,
-    sync::Arc,
+    sync::{atomic::AtomicU64, Arc},
     time::{Duration, Instant},
 };
 
-use elsa::sync::FrozenMap;
+use elsa::sync::{FrozenMap, FrozenVec};
 use once_cell::sync::OnceCell;
 use prometheus_client::{
-    encoding::{
-        EncodeLabelKey, EncodeLabelValue, EncodeMetric, LabelKeyEncoder, LabelValueEncoder,
-        MetricEncoder,
-    },
+    encoding::{EncodeCounterValue, EncodeMetric, MetricEncoder, NoLabelSet},
     metrics::{
-        counter::Counter, gauge::Gauge as GaugeInner, histogram::Histogram as HistogramInner,
-        MetricType, TypedMetric,
+        counter::Atomic as CounterAtomic, gauge::Atomic as GaugeAtomic,
+        histogram::Histogram as HistogramInner, MetricType, TypedMetric,
     },
-    registry::Unit,
 };
 
 use crate::{
     buckets::Buckets,
     builder::BuildMetric,
     encoding::{EncodeGroupedMetric, FullLabelSet, LabelSetWrapper},
+    sharded::{HistogramShard, Shards},
+    switch::{GroupSwitch, MetricState},
     traits::{EncodeLabelSet, EncodedGaugeValue, GaugeValue, HistogramValue, MapLabels},
 };
 
-/// Label with a unit suffix implementing [`EncodeLabelKey`].
-#[doc(hidden)] // used in proc macros only
-#[derive(Debug)]
-pub struct LabelWithUnit {
-    name: &'static str,
-    unit: Unit,
+/// Counter metric.
+///
+/// Counters are integer or floating-point values that can only increase. By default, counters
+/// are `u64`-valued; use `Counter<f64>` for floating-point values.
+///
+/// A counter can be [sharded](crate::MetricBuilder::with_sharding()) to reduce contention if it's updated
+/// from many threads. For a sharded counter, [`Self::inc()`] and [`Self::inc_by()`] return the previous value
+/// of the shard updated by the current thread rather than of the entire counter.
+pub struct Counter<N = u64, A = AtomicU64> {
+    state: MetricState<Arc<Shards<A>>>,
+    _value: PhantomData<N>,
 }
 
-impl LabelWithUnit {
-    pub const fn new(name: &'static str, unit: Unit) -> Self {
-        Self { name, unit }
+impl<N, A: fmt::Debug> fmt::Debug for Counter<N, A> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Counter")
+            .field("value", &self.state.get())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<N, A> Clone for Counter<N, A> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            _value: PhantomData,
+        }
     }
 }
 
-impl EncodeLabelKey for LabelWithUnit {
-    fn encode(&self, encoder: &mut LabelKeyEncoder<'_>) -> fmt::Result {
-        use std::fmt::Write as _;
+impl<N, A: Default> Default for Counter<N, A> {
+    fn default() -> Self {
+        Self::new(None, false)
+    }
+}
 
-        write!(encoder, "{}_{}", self.name, self.unit.as_str())
+impl<N, A: Default> Counter<N, A> {
+    pub(crate) fn new(switch: Option<&'static GroupSwitch>, sharded: bool) -> Self {
+        Self {
+            state: MetricState::new(switch, || Arc::new(Shards::new(sharded, A::default))),
+            _value: PhantomData,
+        }
     }
 }
 
-/// Wraps a [`Duration`] so that it can be used as a label value, which will be set to the fractional
-/// number of seconds in the duration, i.e. [`Duration::as_secs_f64()`]. Mostly useful for [`Info`] metrics.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct DurationAsSecs(pub Duration);
+impl<N, A: CounterAtomic<N> + Default> Counter<N, A> {
+    /// Increases this counter by 1, returning the previous value.
+    pub fn inc(&self) -> N {
+        let Some(shards) = self.state.get() else {
+            return A::default().get();
+        };
+        let shard = shards.local();
+        if self.state.is_on() {
+            shard.inc()
+        } else {
+            shard.get()
+        }
+    }
 
-impl From<Duration> for DurationAsSecs {
-    fn from(duration: Duration) -> Self {
-        Self(duration)
+    /// Increases this counter by `v`, returning the previous value.
+    pub fn inc_by(&self, v: N) -> N {
+        let Some(shards) = self.state.get() else {
+            return A::default().get();
+        };
+        let shard = shards.local();
+        if self.state.is_on() {
+            shard.inc_by(v)
+        } else {
+            shard.get()
+        }
+    }
+
+    /// Gets the current value of this counter.
+    pub fn get(&self) -> N {
+        match self.state.get().map(|shards| &**shards) {
+            None => A::default().get(),
+            Some(Shards::Single(value)) => value.get(),
+            Some(shards @ Shards::Sharded(_)) => {
+                let total = A::default();
+                for shard in shards.iter() {
+                    total.inc_by(shard.get());
+                }
+                total.get()
+            }
+        }
     }
 }
 
-impl EncodeLabelValue for DurationAsSecs {
-    fn encode(&self, encoder: &mut LabelValueEncoder) -> fmt::Result {
-        EncodeLabelValue::encode(&self.0.as_secs_f64(), encoder)
+impl<N, A> EncodeMetric for Counter<N, A>
+where
+    N: EncodeCounterValue,
+    A: CounterAtomic<N> + Default,
+{
+    fn encode(&self, mut encoder: MetricEncoder<'_>) -> fmt::Result {
+        encoder.encode_counter::<NoLabelSet, _, u64>(&self.get(), None)
+    }
+
+    fn metric_type(&self) -> MetricType {
+        <Self as TypedMetric>::TYPE
     }
 }
 
-impl<N, A> EncodeGroupedMetric for Counter<N, A> where Self: EncodeMetric + TypedMetric {}
+impl<N, A> TypedMetric for Counter<N, A> {
+    const TYPE: MetricType = MetricType::Counter;
+}
+
+impl<N, A> EncodeGroupedMetric for Counter<N, A>
+where
+    Self: EncodeMetric + TypedMetric,
+{
+    fn is_enabled(&self) -> bool {
+        self.state.is_on()
+    }
+}
 
 /// Gauge metric.
 ///
 /// Gauges are integer or floating-point values that can go up or down. Logically, a reported gauge value
 /// can be treated as valid until the next value is reported.
 ///
-/// Gauge values must implement the [`GaugeValue`] trait.
-pub struct Gauge<V: GaugeValue = i64>(GaugeInner<V, V::Atomic>);
+/// A gauge can be [sharded](crate::MetricBuilder::with_sharding()) to reduce contention if it's incremented
+/// and decremented from many threads. For a sharded gauge, [`Self::inc_by()`] and [`Self::dec_by()`]
+/// return the previous value of the shard updated by the current thread, and [`Self::set()`] is not atomic
+/// with respect to concurrent updates. [`Duration`] gauges are never sharded since their shards could become
+/// negative.
+pub struct Gauge<V: GaugeValue = i64> {
+    state: MetricState<Arc<Shards<V::Atomic>>>,
+}
 
 impl<V: GaugeValue> fmt::Debug for Gauge<V> {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt::Debug::fmt(&self.0, formatter)
+        formatter
+            .debug_struct("Gauge")
+            .field("value", &self.state.get())
+            .finish_non_exhaustive()
     }
 }
 
 impl<V: GaugeValue> Clone for Gauge<V> {
     fn clone(&self) -> Self {
-        Self(self.0.clone())
+        Self {
+            state: self.state.clone(),
+        }
     }
 }
 
 impl<V: GaugeValue> Default for Gauge<V> {
     fn default() -> Self {
-        Self(GaugeInner::default())
+        Self::new(None, false)
     }
 }
 
 impl<V: GaugeValue> Gauge<V> {
+    pub(crate) fn new(switch: Option<&'static GroupSwitch>, sharded: bool) -> Self {
+        let sharded = sharded && TypeId::of::<V>() != TypeId::of::<Duration>();
+        Self {
+            state: MetricState::new(switch, || {
+                Arc::new(Shards::new(sharded, V::Atomic::default))
+            }),
+        }
+    }
+
     /// Increases this [`Gauge`] by `v`, returning the previous value.
     pub fn inc_by(&self, v: V) -> V {
-        self.0.inc_by(v)
+        let Some(shards) = self.state.get() else {
+            return V::Atomic::default().get();
+        };
+        let shard = shards.local();
+        if self.state.is_on() {
+            shard.inc_by(v)
+        } else {
+            shard.get()
+        }
     }
 
     /// Increases this [`Gauge`] by `v` and returns a guard that will decrement this value back
     /// when dropped. This can be useful for gauges that measure consumption of a certain resource.
+    ///
+    /// If the [group switch](GroupSwitch) is off when the guard is created, the gauge is not incremented,
+    /// and the guard doesn't decrement it.
     pub fn inc_guard(&self, v: V) -> GaugeGuard<V> {
-        let guard = GaugeGuard {
+        let shards = self.state.on();
+        if let Some(shards) = shards {
+            shards.local().inc_by(v);
+        }
+        GaugeGuard {
             gauge: self.clone(),
-            increment: v,
-        };
-        self.0.inc_by(v);
-        guard
+            increment: shards.map(|_| v),
+        }
     }
 
     /// Decreases this [`Gauge`] by `v`, returning the previous value.
@@ -122,17 +228,48 @@ impl<V: GaugeValue> Gauge<V> {
     ///
     /// Depending on the value type, this method may panic on underflow; use with care.
     pub fn dec_by(&self, v: V) -> V {
-        self.0.dec_by(v)
+        let Some(shards) = self.state.get() else {
+            return V::Atomic::default().get();
+        };
+        let shard = shards.local();
+        if self.state.is_on() {
+            shard.dec_by(v)
+        } else {
+            shard.get()
+        }
     }
 
     /// Sets the value of this [`Gauge`] returning the previous value.
     pub fn set(&self, value: V) -> V {
-        self.0.set(value)
+        let Some(shards) = self.state.on() else {
+            return self.get();
+        };
+        match &**shards {
+            Shards::Single(atomic) => atomic.set(value),
+            Shards::Sharded(_) => {
+                let prev_value = self.get();
+                let zero = V::Atomic::default().get();
+                for (i, shard) in shards.iter().enumerate() {
+                    shard.set(if i == 0 { value } else { zero });
+                }
+                prev_value
+            }
+        }
     }
 
     /// Gets the current value of the gauge.
     pub fn get(&self) -> V {
-        self.0.get()
+        match self.state.get().map(|shards| &**shards) {
+            None => V::Atomic::default().get(),
+            Some(Shards::Single(atomic)) => atomic.get(),
+            Some(shards @ Shards::Sharded(_)) => {
+                let total = V::Atomic::default();
+                for shard in shards.iter() {
+                    total.inc_by(shard.get());
+                }
+                total.get()
+            }
+        }
     }
 }
 
@@ -153,19 +290,27 @@ impl<V: GaugeValue> TypedMetric for Gauge<V> {
     const TYPE: MetricType = MetricType::Gauge;
 }
 
-impl<V: GaugeValue> EncodeGroupedMetric for Gauge<V> {}
+impl<V: GaugeValue> EncodeGroupedMetric for Gauge<V> {
+    fn is_enabled(&self) -> bool {
+        self.state.is_on()
+    }
+}
 
 /// Guard for a [`Gauge`] returned by [`Gauge::inc_guard()`]. When dropped, a guard decrements
 /// the gauge by the same value that it was increased by when creating the guard.
 #[derive(Debug)]
 pub struct GaugeGuard<V: GaugeValue = i64> {
     gauge: Gauge<V>,
-    increment: V,
+    /// `None` if the gauge wasn't incremented because its group was disabled.
+    increment: Option<V>,
 }
 
 impl<V: GaugeValue> Drop for GaugeGuard<V> {
     fn drop(&mut self) {
-        self.gauge.dec_by(self.increment);
+        // Decrement regardless of the group switch; otherwise, the gauge would drift.
+        if let (Some(increment), Some(shards)) = (self.increment, self.gauge.state.get()) {
+            shards.local().dec_by(increment);
+        }
     }
 }
 
@@ -174,33 +319,92 @@ impl<V: GaugeValue> Drop for GaugeGuard<V> {
 /// Histograms are floating-point values counted in configurable buckets. Logically, a histogram observes
 /// a certain probability distribution, and observations are transient (unlike gauge values).
 ///
-/// Histogram values must implement the [`HistogramValue`] trait.
+/// Histogram values must implement the [`HistogramValue`] trait. A histogram can be
+/// [sharded](crate::MetricBuilder::with_sharding()) to reduce contention if it's updated from many threads.
 #[derive(Debug)]
 pub struct Histogram<V: HistogramValue = f64> {
-    inner: HistogramInner,
+    state: MetricState<HistogramRepr>,
     _value: PhantomData<V>,
 }
 
+#[derive(Debug, Clone)]
+enum HistogramRepr {
+    Single(HistogramInner),
+    Sharded(Arc<ShardedHistogram>),
+}
+
+/// Histogram with lock-free shards aggregated on encoding.
+#[derive(Debug)]
+struct ShardedHistogram {
+    /// Upper bounds of buckets, including the `+Inf` bucket represented by `f64::MAX`
+    /// (same as in `prometheus-client`).
+    upper_bounds: Box<[f64]>,
+    shards: Shards<HistogramShard>,
+}
+
+impl ShardedHistogram {
+    fn new(buckets: Buckets) -> Self {
+        let upper_bounds: Box<[f64]> = buckets.iter().chain([f64::MAX]).collect();
+        let bucket_count = upper_bounds.len();
+        Self {
+            upper_bounds,
+            shards: Shards::new(true, || HistogramShard::new(bucket_count)),
+        }
+    }
+
+    fn observe(&self, value: f64) {
+        let bucket_idx = self
+            .upper_bounds
+            .iter()
+            .position(|&upper_bound| upper_bound >= value);
+        self.shards.local().observe(value, bucket_idx);
+    }
+
+    fn encode(&self, mut encoder: MetricEncoder<'_>) -> fmt::Result {
+        let (mut sum, mut count) = (0.0, 0);
+        let mut buckets: Vec<_> = self.upper_bounds.iter().map(|&bound| (bound, 0)).collect();
+        for shard in self.shards.iter() {
+            shard.aggregate(&mut sum, &mut count, &mut buckets);
+        }
+        encoder.encode_histogram::<NoLabelSet>(sum, count, &buckets, None)
+    }
+}
+
 impl<V: HistogramValue> Clone for Histogram<V> {
     fn clone(&self) -> Self {
         Self {
-            inner: self.inner.clone(),
+            state: self.state.clone(),
             _value: PhantomData,
         }
     }
 }
 
 impl<V: HistogramValue> Histogram<V> {
-    pub(crate) fn new(buckets: Buckets) -> Self {
+    pub(crate) fn new(
+        buckets: Buckets,
+        switch: Option<&'static GroupSwitch>,
+        sharded: bool,
+    ) -> Self {
+        let state = MetricState::new(switch, || {
+            if sharded {
+                HistogramRepr::Sharded(Arc::new(ShardedHistogram::new(buckets)))
+            } else {
+                HistogramRepr::Single(HistogramInner::new(buckets.iter()))
+            }
+        });
         Self {
-            inner: HistogramInner::new(buckets.iter()),
+            state,
             _value: PhantomData,
         }
     }
 
     /// Observes the specified `value` of the metric.
     pub fn observe(&self, value: V) {
-        self.inner.observe(value.encode());
+        match self.state.on() {
+            Some(HistogramRepr::Single(inner)) => inner.observe(value.encode()),
+            Some(HistogramRepr::Sharded(inner)) => inner.observe(value.encode()),
+            None => { /* the metric is switched off */ }
+        }
     }
 }
 
@@ -217,7 +421,11 @@ impl Histogram<Duration> {
 
 impl<V: HistogramValue> EncodeMetric for Histogram<V> {
     fn encode(&self, encoder: MetricEncoder<'_>) -> fmt::Result {
-        self.inner.encode(encoder)
+        match self.state.get() {
+            Some(HistogramRepr::Single(inner)) => inner.encode(encoder),
+            Some(HistogramRepr::Sharded(inner)) => inner.encode(encoder),
+            None => Ok(()),
+        }
     }
 
     fn metric_type(&self) -> MetricType {
@@ -229,9 +437,16 @@ impl<V: HistogramValue> TypedMetric for Histogram<V> {
     const TYPE: MetricType = MetricType::Histogram;
 }
 
-impl<V: HistogramValue> EncodeGroupedMetric for Histogram<V> {}
+impl<V: HistogramValue> EncodeGroupedMetric for Histogram<V> {
+    fn is_enabled(&self) -> bool {
+        self.state.is_on()
+    }
+}
 
 /// Observer of latency for a [`Histogram`].
+///
+/// If the observer is dropped without calling [`Self::observe()`], no latency is recorded. To instrument
+/// futures (which can be cancelled mid-flight), consider using [`InstrumentFuture`](crate::InstrumentFuture).
 #[must_use = "`LatencyObserver` should be `observe()`d"]
 #[derive(Debug)]
 pub struct LatencyObserver<'a> {
@@ -252,40 +467,63 @@ impl LatencyObserver<'_> {
 ///
 /// Information metrics represent pieces of information that are not changed during program lifetime
 /// (e.g., config parameters of a certain component).
-#[derive(Debug)]
-pub struct Info<S>(Arc<OnceCell<S>>);
+pub struct Info<S> {
+    state: MetricState<Arc<OnceCell<S>>>,
+}
+
+impl<S: fmt::Debug> fmt::Debug for Info<S> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Info")
+            .field("value", &self.get())
+            .finish_non_exhaustive()
+    }
+}
 
 impl<S> Default for Info<S> {
     fn default() -> Self {
-        Self(Arc::default())
+        Self::new(None)
     }
 }
 
 impl<S> Clone for Info<S> {
     fn clone(&self) -> Self {
-        Self(self.0.clone())
+        Self {
+            state: self.state.clone(),
+        }
     }
 }
 
-impl<S: EncodeLabelSet> Info<S> {
+impl<S> Info<S> {
+    pub(crate) fn new(switch: Option<&'static GroupSwitch>) -> Self {
+        Self {
+            state: MetricState::new(switch, Arc::default),
+        }
+    }
+
     /// Gets the current value of the metric.
     pub fn get(&self) -> Option<&S> {
-        self.0.get()
+        self.state.get()?.get()
     }
+}
 
-    /// Sets the value of this metric.
+impl<S: EncodeLabelSet> Info<S> {
+    /// Sets the value of this metric. If the [group switch](GroupSwitch) is off, this is a no-op.
     ///
     /// # Errors
     ///
     /// Returns an error if the value is already set.
     pub fn set(&self, value: S) -> Result<(), SetInfoError<S>> {
-        self.0.set(value).map_err(SetInfoError)
+        let Some(cell) = self.state.on() else {
+            return Ok(());
+        };
+        cell.set(value).map_err(SetInfoError)
     }
 }
 
 impl<S: EncodeLabelSet> EncodeMetric for Info<S> {
     fn encode(&self, mut encoder: MetricEncoder<'_>) -> fmt::Result {
-        if let Some(value) = self.0.get() {
+        if let Some(value) = self.get() {
             encoder.encode_info(&LabelSetWrapper(value))
         } else {
             Ok(())
@@ -301,7 +539,11 @@ impl<S: EncodeLabelSet> TypedMetric for Info<S> {
     const TYPE: MetricType = MetricType::Info;
 }
 
-impl<S: EncodeLabelSet> EncodeGroupedMetric for Info<S> {}
+impl<S: EncodeLabelSet> EncodeGroupedMetric for Info<S> {
+    fn is_enabled(&self) -> bool {
+        self.state.is_on()
+    }
+}
 
 /// Error returned from [`Info::set()`].
 #[derive(Debug)]
@@ -320,29 +562,110 @@ impl<S> fmt::Display for SetInfoError<S> {
     }
 }
 
+/// Family member together with its labels.
+struct FamilyEntry<S, M> {
+    /// Labels are wrapped in an `Arc` so that they can be shared with the family map and [`LabelGroups`](crate::encoding::LabelGroups)
+    /// without cloning.
+    labels: Arc<S>,
+    metric: M,
+}
+
+/// Key in the family map sharing labels with the corresponding [`FamilyEntry`].
+struct FamilyKey<S>(Arc<S>);
+
+impl<S: PartialEq> PartialEq for FamilyKey<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<S: Eq> Eq for FamilyKey<S> {}
+
+impl<S: Hash> Hash for FamilyKey<S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+/// Borrowed form of [`FamilyKey`]s allowing to look them up by any type the labels can be borrowed as
+/// (a [`FamilyKey`] cannot implement `Borrow<Q>` directly since it would conflict with the blanket `Borrow` impl).
+trait BorrowLabels<Q: ?Sized> {
+    fn labels(&self) -> &Q;
+}
+
+impl<S: Borrow<Q>, Q: ?Sized> BorrowLabels<Q> for FamilyKey<S> {
+    fn labels(&self) -> &Q {
+        (*self.0).borrow()
+    }
+}
+
+/// Borrowed labels used to look up a [`FamilyKey`].
+struct LabelsRef<'a, Q: ?Sized>(&'a Q);
+
+impl<Q: ?Sized> BorrowLabels<Q> for LabelsRef<'_, Q> {
+    fn labels(&self) -> &Q {
+        self.0
+    }
+}
+
+impl<'a, S: Borrow<Q> + 'a, Q: ?Sized + 'a> Borrow<dyn BorrowLabels<Q> + 'a> for FamilyKey<S> {
+    fn borrow(&self) -> &(dyn BorrowLabels<Q> + 'a) {
+        self
+    }
+}
+
+impl<Q: PartialEq + ?Sized> PartialEq for dyn BorrowLabels<Q> + '_ {
+    fn eq(&self, other: &Self) -> bool {
+        self.labels() == other.labels()
+    }
+}
+
+impl<Q: Eq + ?Sized> Eq for dyn BorrowLabels<Q> + '_ {}
+
+impl<Q: Hash + ?Sized> Hash for dyn BorrowLabels<Q> + '_ {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.labels().hash(state);
+    }
+}
+
+/// Storage for family members. Members are never removed; hence, references to them
+/// remain valid for the lifetime of the family.
+///
+/// The map is used to look up members by labels, and the append-only list allows iterating over members
+/// in place (in the order of their creation) without cloning labels or looking them up again. Both share
+/// the same entries, so labels are only stored (and cloned on insertion) once.
+///
+/// With the `noop` crate feature, members are never inserted; instead, all labels resolve
+/// to a single shared no-op member.
 pub(crate) struct FamilyInner<S, M: BuildMetric> {
-    map: FrozenMap<S, Box<M>>,
+    map: FrozenMap<FamilyKey<S>, Arc<FamilyEntry<S, M>>>,
+    entries: FrozenVec<Arc<FamilyEntry<S, M>>>,
     builder: M::Builder,
+    #[cfg(feature = "noop")]
+    noop_member: M,
 }
 
 impl<S, M> fmt::Debug for FamilyInner<S, M>
 where
-    S: fmt::Debug + Clone + Eq + Hash,
+    S: fmt::Debug,
     M: BuildMetric + fmt::Debug,
     M::Builder: fmt::Debug,
 {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let map_keys = self.map.keys_cloned();
-        let map_snapshot: HashMap<_, _> = map_keys
-            .iter()
-            .map(|key| (key, self.map.get(key).unwrap()))
-            .collect();
-
         formatter
             .debug_struct("Family")
-            .field("map", &map_snapshot)
+            .field("map", &DebugEntries(self))
             .field("builder", &self.builder)
-            .finish()
+            .finish_non_exhaustive()
+    }
+}
+
+/// Formats family members as a map without collecting them.
+struct DebugEntries<'a, S, M: BuildMetric>(&'a FamilyInner<S, M>);
+
+impl<S: fmt::Debug, M: BuildMetric + fmt::Debug> fmt::Debug for DebugEntries<'_, S, M> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.debug_map().entries(self.0.iter()).finish()
     }
 }
 
@@ -350,35 +673,79 @@ impl<S: Eq + Hash, M: BuildMetric> FamilyInner<S, M> {
     pub(crate) fn new(builder: M::Builder) -> Self {
         Self {
             map: FrozenMap::new(),
+            entries: FrozenVec::new(),
             builder,
+            #[cfg(feature = "noop")]
+            noop_member: M::build(builder),
         }
     }
 
+    fn get<Q>(&self, labels: &Q) -> Option<&M>
+    where
+        S: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let labels: &dyn BorrowLabels<Q> = &LabelsRef(labels);
+        self.map.get(labels).map(|entry| &entry.metric)
+    }
+
+    #[cfg(feature = "noop")]
+    pub(crate) fn get_or_create<Q>(&self, _labels: &Q) -> &M
+    where
+        S: Borrow<Q>,
+        Q: Eq + Hash + ?Sized + ToOwned<Owned = S>,
+    {
+        &self.noop_member
+    }
+
+    #[cfg(not(feature = "noop"))]
     pub(crate) fn get_or_create<Q>(&self, labels: &Q) -> &M
     where
         S: Borrow<Q>,
         Q: Eq + Hash + ?Sized + ToOwned<Owned = S>,
     {
-        if let Some(metric) = self.map.get(labels) {
+        if let Some(metric) = self.get(labels) {
             return metric;
         }
-        self.map
-            .insert_with(labels.to_owned(), || Box::new(M::build(self.builder)))
+
+        let labels = Arc::new(labels.to_owned());
+        let mut new_entry = None;
+        let entry = self.map.insert_with(FamilyKey(Arc::clone(&labels)), || {
+            let entry = Arc::new(FamilyEntry {
+                labels,
+                metric: M::build(self.builder),
+            });
+            new_entry = Some(Arc::clone(&entry));
+            entry
+        });
+        // The entry is only pushed by the thread that has created it. Since this happens after inserting
+        // the entry into the map, the entry may be missing from iteration for a short while, which is fine.
+        if let Some(new_entry) = new_entry {
+            self.entries.push(new_entry);
+        }
+        &entry.metric
     }
 }
 
-impl<S, M> FamilyInner<S, M>
-where
-    S: Clone + Eq + Hash,
-    M: BuildMetric,
-{
-    pub(crate) fn to_entries(&self) -> impl ExactSizeIterator<Item = (S, &M)> + '_ {
-        let labels = self.map.keys_cloned();
-        labels.into_iter().map(|key| {
-            let metric = self.map.get(&key).unwrap();
-            (key, metric)
+impl<S, M: BuildMetric> FamilyInner<S, M> {
+    /// Iterates over all members of this family in place.
+    pub(crate) fn iter(&self) -> impl ExactSizeIterator<Item = (&Arc<S>, &M)> + '_ {
+        // `FrozenVec::iter()` isn't an `ExactSizeIterator`, so we fix the length beforehand.
+        // Since entries are never removed, all of them can be accessed afterward.
+        let len = self.entries.len();
+        (0..len).map(|i| {
+            let entry = self.entries.get(i).unwrap();
+            (&entry.labels, &entry.metric)
         })
     }
+
+    pub(crate) fn to_entries(&self) -> impl ExactSizeIterator<Item = (S, &M)> + '_
+    where
+        S: Clone,
+    {
+        self.iter()
+            .map(|(labels, metric)| (S::clone(labels), metric))
+    }
 }
 
 /// Family of metrics labelled by one or more labels.
@@ -387,6 +754,7 @@ where
 pub struct Family<S, M: BuildMetric, L = ()> {
     inner: Arc<FamilyInner<S, M>>,
     labels: L,
+    switch: Option<&'static GroupSwitch>,
 }
 
 /// [`Family`] with separately specified label names.
@@ -482,6 +850,7 @@ impl<S, M: BuildMetric, L: Clone> Clone for Family<S, M, L> {
         Self {
             inner: Arc::clone(&self.inner),
             labels: self.labels.clone(),
+            switch: self.switch,
         }
     }
 }
@@ -493,19 +862,30 @@ where
 {
     pub(crate) fn new(builder: M::Builder, labels: L) -> Self {
         let inner = Arc::new(FamilyInner::new(builder));
-        Self { inner, labels }
+        Self {
+            inner,
+            labels,
+            switch: None,
+        }
+    }
+
+    /// Sets the switch of the group the family belongs to. Family members are built with the same switch;
+    /// the family needs it separately, so that it is hidden from encoding even if it has no members.
+    pub(crate) fn with_switch(mut self, switch: Option<&'static GroupSwitch>) -> Self {
+        self.switch = switch;
+        self
     }
 
     /// Checks whether this family contains a metric with the specified labels. This is mostly useful
     /// for testing.
     pub fn contains(&self, labels: &S) -> bool {
-        self.inner.map.get(labels).is_some()
+        self.inner.get(labels).is_some()
     }
 
     /// Gets a metric with the specified labels if it was reported previously. This is mostly useful
     /// for testing; use indexing for reporting.
     pub fn get(&self, labels: &S) -> Option<&M> {
-        self.inner.map.get(labels)
+        self.inner.get(labels)
     }
 
     /// Gets or creates a metric with the specified labels *lazily* (i.e., on first access). This is useful
@@ -592,8 +972,7 @@ where
     L: MapLabels<S>,
 {
     fn encode(&self, mut encoder: MetricEncoder<'_>) -> fmt::Result {
-        for labels in &self.inner.map.keys_cloned() {
-            let metric = self.inner.map.get(labels).unwrap();
+        for (labels, metric) in self.inner.iter() {
             let mapped_labels = LabelSetWrapper(self.labels.map_labels(labels));
             let encoder = encoder.encode_family(&mapped_labels)?;
             metric.encode(encoder)?;
@@ -621,24 +1000,33 @@ where
         group_labels: &dyn EncodeLabelSet,
         encoder: &mut MetricEncoder<'_>,
     ) -> fmt::Result {
-        for labels in &self.inner.map.keys_cloned() {
-            let metric = self.inner.map.get(labels).unwrap();
+        for (labels, metric) in self.inner.iter() {
             let mapped_labels = self.labels.map_labels(labels);
             let all_labels = FullLabelSet::new(group_labels, &mapped_labels);
             metric.encode(encoder.encode_family(&all_labels)?)?;
         }
         Ok(())
     }
+
+    fn is_enabled(&self) -> bool {
+        GroupSwitch::is_on(self.switch)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{sync::mpsc, thread};
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc,
+        },
+        thread,
+    };
 
     use prometheus_client::metrics::family::Family as StandardFamily;
 
     use super::*;
-    use crate::MetricBuilder;
+    use crate::{EncodeLabelSet, Format, MetricBuilder, Metrics, Registry};
 
     type Label = (&'static str, &'static str);
 
@@ -676,4 +1064,126 @@ mod tests {
         // See its docs for more details. As an added bonus, we can use indexing notation instead of
         // clunky methods!
     }
+
+    #[test]
+    fn accessing_family_by_borrowed_labels() {
+        let family = Family::<String, Counter>::new(MetricBuilder::new(), ());
+        family["call"].inc();
+        family[&"call".to_owned()].inc_by(2);
+        family["send"].inc();
+        assert_eq!(family.get(&"call".to_owned()).unwrap().get(), 3);
+        assert!(!family.contains(&"estimate".to_owned()));
+
+        let entries: Vec<_> = family
+            .to_entries()
+            .map(|(labels, counter)| (labels, counter.get()))
+            .collect();
+        assert_eq!(entries, [("call".to_owned(), 3), ("send".to_owned(), 1)]);
+    }
+
+    #[test]
+    fn encoding_family_does_not_clone_labels() {
+        static CLONE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Debug, PartialEq, Eq, Hash, EncodeLabelSet)]
+        #[metrics(crate = crate)]
+        struct CountedLabels {
+            method: &'static str,
+        }
+
+        impl Clone for CountedLabels {
+            fn clone(&self) -> Self {
+                CLONE_COUNT.fetch_add(1, Ordering::Relaxed);
+                Self {
+                    method: self.method,
+                }
+            }
+        }
+
+        #[derive(Debug, Metrics)]
+        #[metrics(crate = crate, prefix = "test")]
+        struct TestMetrics {
+            requests: Family<CountedLabels, Counter>,
+        }
+
+        let metrics = TestMetrics::default();
+        for method in ["call", "send", "estimate"] {
+            metrics.requests[&CountedLabels { method }].inc();
+        }
+        // Labels are cloned once per created member.
+        let clone_count = CLONE_COUNT.load(Ordering::Relaxed);
+        assert_eq!(clone_count, 3);
+        metrics.requests[&CountedLabels { method: "call" }].inc();
+        assert_eq!(CLONE_COUNT.load(Ordering::Relaxed), clone_count);
+        let mut registry = Registry::empty();
+        registry.register_metrics(&metrics);
+        let mut buffer = String::new();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        assert_eq!(CLONE_COUNT.load(Ordering::Relaxed), clone_count);
+
+        // Members are encoded in the order of their creation.
+        let lines: Vec<_> = buffer
+            .lines()
+            .filter(|line| line.starts_with("test_requests_total"))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "test_requests_total{method=\"call\"} 2",
+                "test_requests_total{method=\"send\"} 1",
+                "test_requests_total{method=\"estimate\"} 1",
+            ]
+        );
+        let entries: Vec<_> = metrics.requests.to_entries().collect();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(CLONE_COUNT.load(Ordering::Relaxed), clone_count + 3);
+    }
+
+    #[cfg(feature = "noop")]
+    #[test]
+    fn noop_metrics() {
+        #[derive(Debug, Metrics)]
+        #[metrics(crate = crate, prefix = "noop")]
+        struct NoopMetrics {
+            counter: Counter,
+            gauge: Gauge<u64>,
+            #[metrics(buckets = Buckets::LATENCIES)]
+            latency: Histogram<Duration>,
+            info: Info<InfoLabels>,
+            #[metrics(labels = ["method"])]
+            requests: LabeledFamily<&'static str, Counter>,
+        }
+
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+        #[metrics(crate = crate)]
+        struct InfoLabels {
+            method: &'static str,
+        }
+
+        let metrics = NoopMetrics::default();
+        metrics.counter.inc();
+        metrics.gauge.set(5);
+        metrics.gauge.inc_by(1);
+        metrics.latency.start().observe();
+        metrics.info.set(InfoLabels { method: "call" }).unwrap();
+        metrics.requests[&"call"].inc_by(3);
+        assert_eq!(metrics.counter.get(), 0);
+        assert_eq!(metrics.gauge.get(), 0);
+        assert!(metrics.info.get().is_none());
+        assert_eq!(metrics.requests[&"call"].get(), 0);
+        assert!(!metrics.requests.contains(&"call"));
+        assert_eq!(metrics.requests.to_entries().len(), 0);
+
+        assert_eq!(std::mem::size_of::<Counter>(), 0);
+        assert_eq!(std::mem::size_of::<Counter<f64>>(), 0);
+        assert_eq!(std::mem::size_of::<Gauge<u64>>(), 0);
+        assert_eq!(std::mem::size_of::<Histogram<Duration>>(), 0);
+        assert_eq!(std::mem::size_of::<Info<InfoLabels>>(), 0);
+
+        let mut registry = Registry::empty();
+        registry.register_metrics(&metrics);
+        let mut buffer = String::new();
+        registry.encode(&mut buffer, Format::OpenMetrics).unwrap();
+        assert_eq!(buffer, "");
+    }
 }
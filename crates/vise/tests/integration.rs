@@ -5,4 +5,5 @@ fn ui() {
     let t = trybuild::TestCases::new();
     t.compile_fail("tests/ui/metrics/*.rs");
     t.compile_fail("tests/ui/labels/*.rs");
+    t.compile_fail("tests/ui/instrument/*.rs");
 }
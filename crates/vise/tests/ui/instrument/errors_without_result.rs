@@ -0,0 +1,15 @@
+use vise::{Counter, Global, Metrics};
+
+#[derive(Debug, Metrics)]
+struct TestMetrics {
+    errors: Counter,
+}
+
+static METRICS: Global<TestMetrics> = Global::new();
+
+#[vise::instrument(metrics = METRICS, errors = errors)]
+fn compute(x: u64) -> u64 {
+    x * 2
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use vise::{Counter, Global, Metrics};
+
+#[derive(Debug, Metrics)]
+struct TestMetrics {
+    errors: Counter,
+}
+
+static METRICS: Global<TestMetrics> = Global::new();
+
+#[vise::instrument(metrics = METRICS)]
+fn compute(x: u64) -> Result<u64, ()> {
+    Ok(x * 2)
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+use vise::EncodeLabelSet;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+struct RpcContext {
+    method: &'static str,
+    network: &'static str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+struct RpcLabels {
+    #[metrics(flatten)]
+    context: RpcContext,
+    method: &'static str,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use vise::{Counter, Metrics};
+
+#[derive(Debug, Metrics)]
+struct TestMetrics {
+    /// Test counter.
+    #[metrics(deprecated_name = "old-counter")]
+    counter: Counter,
+}
+
+fn main() {}
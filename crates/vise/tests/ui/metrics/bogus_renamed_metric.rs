@@ -0,0 +1,10 @@
+use vise::{Counter, Metrics};
+
+#[derive(Debug, Metrics)]
+struct TestMetrics {
+    /// Test counter.
+    #[metrics(rename = "Invalid name")]
+    counter: Counter,
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use vise::{Counter, Metrics};
+
+#[derive(Debug, Metrics)]
+struct NestedMetrics {
+    /// Test counter.
+    counter: Counter,
+}
+
+#[derive(Debug, Metrics)]
+struct TestMetrics {
+    #[metrics(flatten, prefix = "nested")]
+    nested: NestedMetrics,
+}
+
+fn main() {}
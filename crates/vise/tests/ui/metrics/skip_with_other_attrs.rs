@@ -0,0 +1,10 @@
+use vise::{Counter, Metrics};
+
+#[derive(Debug, Metrics)]
+struct TestMetrics {
+    /// Test counter.
+    #[metrics(skip, rename = "other_counter")]
+    counter: Counter,
+}
+
+fn main() {}
@@ -10,9 +10,4 @@ struct UnsupportedConstParam<const N: usize> {
     labels: [&'static str; N],
 }
 
-#[derive(Debug, Metrics)]
-struct UnsupportedTypeParam<T> {
-    counter: T,
-}
-
 fn main() {}
@@ -0,0 +1,10 @@
+use vise::{Counter, Metrics};
+
+#[derive(Debug, Metrics)]
+struct TestMetrics {
+    /// Test counter.
+    #[metrics(stability = "beta")]
+    counter: Counter,
+}
+
+fn main() {}